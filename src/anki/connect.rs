@@ -0,0 +1,464 @@
+//! AnkiConnect actions that `anki_direct` does not wrap.
+//!
+//! Requests are plain `{"action", "version", "params"}` JSON bodies POSTed with the same
+//! `reqwest` client `anki_direct` uses, see [DisplayAnki::connect](crate::anki::DisplayAnki::connect)
+//! and the [AnkiConnect docs](https://git.foosoft.net/alex/anki-connect).
+
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use indexmap::IndexMap;
+use reqwest::blocking::Client;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{anki::media::MediaSource, japanese::kana::is_kanji};
+
+/// The AnkiConnect api version requests are sent with.
+pub const ANKI_CONNECT_VERSION: u8 = 6;
+
+#[derive(thiserror::Error, Debug)]
+pub enum AnkiConnectError {
+    #[error("ankiconnect request to {url} failed: {source}")]
+    Request { url: String, source: reqwest::Error },
+    #[error("malformed ankiconnect response: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("ankiconnect action `{action}` failed: {reason}")]
+    Action { action: String, reason: String },
+}
+
+impl AnkiConnectError {
+    /// Whether AnkiConnect couldn't be reached at all (e.g. Anki isn't running),
+    /// as opposed to rejecting a request.
+    pub fn is_unreachable(&self) -> bool {
+        matches!(self, AnkiConnectError::Request { source, .. } if source.is_connect() || source.is_timeout())
+    }
+}
+
+#[derive(Deserialize)]
+struct AnkiConnectResponse {
+    result: Value,
+    error: Option<String>,
+}
+
+/// A connection target for AnkiConnect.
+#[derive(Clone, Debug)]
+pub struct AnkiConnect {
+    /// `http` or `https`.
+    scheme: String,
+    /// `host:port` of the AnkiConnect server.
+    addr: String,
+    api_key: Option<String>,
+    client: Client,
+}
+
+impl AnkiConnect {
+    /// Creates a connection from an [AnkiOptions](crate::settings::core::AnkiOptions) `server` value.
+    ///
+    /// Accepts a bare port (`"8765"`), `host:port`, or a full `http://host:port`
+    /// or `https://host:port` url. Without a scheme, `http` is used.
+    pub fn from_server(server: &str) -> Self {
+        let server = server.trim().trim_end_matches('/');
+        let (scheme, addr) = match server.split_once("://") {
+            Some((scheme, addr)) => (scheme.to_ascii_lowercase(), addr),
+            None => ("http".to_string(), server),
+        };
+        let addr = if addr.chars().all(|c| c.is_ascii_digit()) {
+            format!("127.0.0.1:{addr}")
+        } else {
+            addr.to_string()
+        };
+        let client = Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap_or_default();
+        Self {
+            scheme,
+            addr,
+            api_key: None,
+            client,
+        }
+    }
+
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = (!api_key.is_empty()).then(|| api_key.to_string());
+        self
+    }
+
+    /// Sends requests with `client`, e.g. the one of an `anki_direct::AnkiClient`,
+    /// so every request shares its connection pool.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    /// The url requests are POSTed to, e.g. `http://127.0.0.1:8765`.
+    pub fn url(&self) -> String {
+        format!("{}://{}", self.scheme, self.addr)
+    }
+
+    /// The port of [AnkiConnect::addr].
+    pub fn port(&self) -> &str {
+        self.addr
+            .rsplit_once(':')
+            .map_or(self.addr.as_str(), |(_, port)| port)
+    }
+
+    /// Sends `action` with `params` and deserializes its `result`.
+    pub fn invoke<R: DeserializeOwned>(
+        &self,
+        action: &str,
+        params: Value,
+    ) -> Result<R, AnkiConnectError> {
+        let mut request = json!({
+            "action": action,
+            "version": ANKI_CONNECT_VERSION,
+            "params": params,
+        });
+        if let Some(key) = &self.api_key {
+            request["key"] = Value::String(key.clone());
+        }
+        let url = self.url();
+        let body = self
+            .client
+            .post(&url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(&request)?)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.bytes())
+            .map_err(|source| AnkiConnectError::Request { url, source })?;
+        let response: AnkiConnectResponse = serde_json::from_slice(&body)?;
+        if let Some(reason) = response.error {
+            return Err(AnkiConnectError::Action {
+                action: action.to_string(),
+                reason,
+            });
+        }
+        Ok(serde_json::from_value(response.result)?)
+    }
+
+    /// `version`: AnkiConnect's api version, e.g. to check that Anki is running.
+    pub fn version(&self) -> Result<u64, AnkiConnectError> {
+        self.invoke("version", json!({}))
+    }
+
+    /// `findNotes`: returns the ids of all notes matching an Anki search `query`.
+    pub fn find_notes(&self, query: &str) -> Result<Vec<u64>, AnkiConnectError> {
+        self.invoke("findNotes", json!({ "query": query }))
+    }
+
+    /// `notesInfo`: returns the fields, tags and model of each note id.
+    pub fn notes_info(&self, note_ids: &[u64]) -> Result<Vec<NoteInfo>, AnkiConnectError> {
+        self.invoke("notesInfo", json!({ "notes": note_ids }))
+    }
+
+    /// `addNote`: adds a note and returns its id.
+    ///
+    /// Fails if Anki considers the note a duplicate, unless its `allow_duplicate` option is set.
+    pub fn add_note(&self, note: &NewNote) -> Result<u64, AnkiConnectError> {
+        self.invoke("addNote", json!({ "note": note }))
+    }
+
+    /// `addNotes`: adds every note, returning `None` for the notes that couldn't be added.
+    pub fn add_notes(&self, notes: &[NewNote]) -> Result<Vec<Option<u64>>, AnkiConnectError> {
+        self.invoke("addNotes", json!({ "notes": notes }))
+    }
+
+    /// `canAddNotesWithErrorDetail`: whether each note can be added, with the reason if not.
+    ///
+    /// Applies Anki's own duplicate check: the model's first field, without html,
+    /// in the note's duplicate scope.
+    pub fn can_add_notes(&self, notes: &[NewNote]) -> Result<Vec<CanAddNote>, AnkiConnectError> {
+        self.invoke("canAddNotesWithErrorDetail", json!({ "notes": notes }))
+    }
+
+    /// `deckNames`: the names of every deck.
+    pub fn deck_names(&self) -> Result<Vec<String>, AnkiConnectError> {
+        self.invoke("deckNames", json!({}))
+    }
+
+    /// `createDeck`: creates a deck (and its parents) if it doesn't exist yet.
+    pub fn create_deck(&self, deck_name: &str) -> Result<(), AnkiConnectError> {
+        self.invoke::<Value>("createDeck", json!({ "deck": deck_name }))?;
+        Ok(())
+    }
+
+    /// `modelNames`: the names of every note model.
+    pub fn model_names(&self) -> Result<Vec<String>, AnkiConnectError> {
+        self.invoke("modelNames", json!({}))
+    }
+
+    /// `modelFieldNames`: the field names of a note model, in order.
+    pub fn model_field_names(&self, model_name: &str) -> Result<Vec<String>, AnkiConnectError> {
+        self.invoke("modelFieldNames", json!({ "modelName": model_name }))
+    }
+
+    /// `storeMediaFile`: stores a file in the collection's media folder,
+    /// replacing any file with the same name. Returns the name it was stored under.
+    pub fn store_media_file(
+        &self,
+        filename: &str,
+        source: &MediaSource,
+    ) -> Result<String, AnkiConnectError> {
+        let params = match source {
+            MediaSource::Bytes(data) => {
+                json!({ "filename": filename, "data": BASE64.encode(data) })
+            }
+            MediaSource::Url(url) => json!({ "filename": filename, "url": url }),
+        };
+        self.invoke("storeMediaFile", params)
+    }
+
+    /// `deleteNotes`: deletes the notes and all of their cards.
+    pub fn delete_notes(&self, note_ids: &[u64]) -> Result<(), AnkiConnectError> {
+        self.invoke::<Value>("deleteNotes", json!({ "notes": note_ids }))?;
+        Ok(())
+    }
+
+    /// `updateNoteFields`: replaces the given fields of a note, other fields are kept.
+    pub fn update_note_fields(
+        &self,
+        note_id: u64,
+        fields: &IndexMap<String, String>,
+    ) -> Result<(), AnkiConnectError> {
+        self.invoke::<Value>(
+            "updateNoteFields",
+            json!({ "note": { "id": note_id, "fields": fields } }),
+        )?;
+        Ok(())
+    }
+}
+
+/// A note as `addNote`, `addNotes` and `canAddNotes` take it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewNote {
+    pub deck_name: String,
+    pub model_name: String,
+    pub fields: IndexMap<String, String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub options: NewNoteOptions,
+}
+
+/// How Anki checks a [NewNote] for duplicates.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewNoteOptions {
+    /// Whether the note is added even if it is a duplicate.
+    #[serde(default)]
+    pub allow_duplicate: bool,
+    /// `"deck"` or `"collection"`; AnkiConnect uses `"collection"` if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicate_scope: Option<String>,
+    #[serde(default)]
+    pub duplicate_scope_options: DuplicateScopeOptions,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateScopeOptions {
+    /// The deck checked with the `"deck"` scope; the note's deck if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deck_name: Option<String>,
+    /// Whether the subdecks of the `"deck"` scope are checked too.
+    #[serde(default)]
+    pub check_children: bool,
+    /// Whether notes of every model are checked, instead of the note's model only.
+    #[serde(default)]
+    pub check_all_models: bool,
+}
+
+/// An item of the `canAddNotesWithErrorDetail` result.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CanAddNote {
+    pub can_add: bool,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl CanAddNote {
+    /// Whether the note can't be added because Anki considers it a duplicate.
+    pub fn is_duplicate(&self) -> bool {
+        !self.can_add
+            && self
+                .error
+                .as_deref()
+                .is_some_and(|error| error.contains("duplicate"))
+    }
+}
+
+/// A note as returned by the `notesInfo` action.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoteInfo {
+    pub note_id: u64,
+    pub model_name: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub fields: IndexMap<String, NoteFieldInfo>,
+}
+
+impl NoteInfo {
+    /// The raw (html) value of `field`, if the note has it.
+    pub fn field(&self, field: &str) -> Option<&str> {
+        self.fields.get(field).map(|f| f.value.as_str())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NoteFieldInfo {
+    pub value: String,
+    pub order: usize,
+}
+
+/// Tags that start a new line, like Anki's `<br>` and `<div>` line breaks.
+const BLOCK_TAGS: &[&str] = &[
+    "br",
+    "div",
+    "p",
+    "li",
+    "ol",
+    "ul",
+    "tr",
+    "table",
+    "hr",
+    "blockquote",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+];
+
+/// Converts an Anki field value to plain text.
+///
+/// Strips html tags, decodes the common entities, and drops Anki furigana
+/// readings so ` 漢字[かんじ]` becomes `漢字`, along with the space that separates
+/// a furigana group from the text before it. Other spaces are kept.
+/// Block-level tags (`<br>`, `</div>`, `</li>`...) become line breaks,
+/// and empty lines are dropped.
+pub fn field_plain_text(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut tag: Option<String> = None;
+    let mut in_ruby_reading = false;
+    for c in value.chars() {
+        if let Some(name) = &mut tag {
+            if c == '>' {
+                if is_block_tag(name) {
+                    out.push('\n');
+                }
+                tag = None;
+            } else {
+                name.push(c);
+            }
+            continue;
+        }
+        match c {
+            '<' => tag = Some(String::new()),
+            '[' if is_furigana_reading(&out) => {
+                remove_furigana_separator(&mut out);
+                in_ruby_reading = true;
+            }
+            ']' if in_ruby_reading => in_ruby_reading = false,
+            _ if in_ruby_reading => {}
+            _ => out.push(c),
+        }
+    }
+    let out = out
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&");
+    out.lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Whether a `[` after `text` starts the reading of a furigana group,
+/// i.e. `text` ends with the group's kanji.
+fn is_furigana_reading(text: &str) -> bool {
+    text.chars().next_back().is_some_and(is_kanji)
+}
+
+/// Removes the space before the kanji that end `text`, which furigana syntax uses to
+/// separate a group from the text before it: `日本[にほん] 語[ご]`.
+fn remove_furigana_separator(text: &mut String) {
+    let kanji_start = text
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_kanji(*c))
+        .last()
+        .map_or(text.len(), |(i, _)| i);
+    if text[..kanji_start].ends_with(' ') {
+        text.remove(kanji_start - 1);
+    }
+}
+
+/// Whether the contents of `<tag>` (e.g. `/div` or `br /`) is a [BLOCK_TAGS] tag.
+fn is_block_tag(tag: &str) -> bool {
+    let name = tag
+        .trim_start_matches('/')
+        .split(|c: char| c.is_whitespace() || c == '/')
+        .next()
+        .unwrap_or_default();
+    BLOCK_TAGS
+        .iter()
+        .any(|block| name.eq_ignore_ascii_case(block))
+}
+
+#[cfg(test)]
+mod connect_tests {
+    use super::*;
+
+    #[test]
+    fn from_server() {
+        assert_eq!(AnkiConnect::from_server("8765").addr(), "127.0.0.1:8765");
+        assert_eq!(
+            AnkiConnect::from_server("http://localhost:8765/").addr(),
+            "localhost:8765"
+        );
+        assert_eq!(
+            AnkiConnect::from_server("localhost:8765").url(),
+            "http://localhost:8765"
+        );
+        assert_eq!(
+            AnkiConnect::from_server("https://anki.example.com:8765").url(),
+            "https://anki.example.com:8765"
+        );
+    }
+
+    #[test]
+    fn port() {
+        assert_eq!(AnkiConnect::from_server("8765").port(), "8765");
+        assert_eq!(AnkiConnect::from_server("localhost:8766").port(), "8766");
+    }
+
+    #[test]
+    fn plain_text() {
+        assert_eq!(field_plain_text("<b>猫</b>&nbsp;"), "猫");
+        assert_eq!(field_plain_text(" 日本[にほん] 語[ご]"), "日本語");
+        assert_eq!(field_plain_text("猫が 鳴[な]く"), "猫が鳴く");
+        assert_eq!(field_plain_text("the cat [a pet]"), "the cat [a pet]");
+        assert_eq!(field_plain_text("猫 [ねこ] です"), "猫 [ねこ] です");
+        assert_eq!(field_plain_text("猫<br>ねこ"), "猫\nねこ");
+        assert_eq!(field_plain_text("<div>猫</div><div>犬</div>"), "猫\n犬");
+        assert_eq!(
+            field_plain_text("<ol><li>cat</li><li>pussycat</li></ol>"),
+            "cat\npussycat"
+        );
+        assert_eq!(field_plain_text("日本[にほん]<BR/>語[ご]"), "日本\n語");
+        assert_eq!(field_plain_text("<span>猫</span><b>犬</b>"), "猫犬");
+    }
+}
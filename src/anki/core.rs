@@ -51,17 +51,32 @@
 //! ```
 
 #[cfg(feature = "anki")]
-use crate::database::dictionary_database::DictionaryDatabase;
 use crate::{
+    anki::{
+        connect::{field_plain_text, NewNote, NoteInfo},
+        duplicate::DuplicateOptions,
+        media::NoteMedia,
+    },
+    database::dictionary_database::DictionaryDatabase,
+    TermDictionaryEntry,
+};
+use crate::{
+    anki::{
+        connect::{AnkiConnect, AnkiConnectError},
+        glossary::GlossaryRenderer,
+        template::{FieldTemplate, FieldTemplateError, NoteContext},
+    },
     settings::core::{
-        AnkiFields, AnkiFieldsError, AnkiOptions, AnkiTermFieldType, DecksMap, FieldIndex,
-        NoteModelsMap, ProfileError, ProfileResult, YomichanOptions,
+        AnkiFields, AnkiFieldsError, AnkiFrequencyFormat, AnkiGlossaryFormat, AnkiOptions,
+        AnkiTermFieldType, DecksMap, FieldIndex, NoteModelsMap, ProfileError, ProfileResult,
+        YomichanOptions, YomichanProfile,
     },
     utils::errors::error_helpers,
-    Ptr, TermDictionaryEntry,
+    Ptr,
 };
-use anki_direct::{error::AnkiResult, notes::Note, AnkiClient};
+use anki_direct::{error::AnkiResult, AnkiClient};
 use getset::Getters;
+use indexmap::IndexMap;
 
 // Important !: ONLY EVER USE parking_lot pointers!
 
@@ -77,6 +92,8 @@ pub enum DisplayAnkiError {
     Database(#[from] native_db::db_type::Error),
     #[error("AnkiConnect error: {0}")]
     AnkiConnect(#[from] anki_direct::error::AnkiError),
+    #[error("AnkiConnect request error: {0}")]
+    Request(#[from] AnkiConnectError),
     #[error("Model '{0}' not found")]
     ModelNotFound(String),
     #[error("Deck '{0}' not found")]
@@ -91,35 +108,133 @@ pub enum DisplayAnkiError {
     NoDecksFound,
     #[error("[anki-fields]")]
     AnkiFields(#[from] AnkiFieldsError),
+    #[error("[anki-field-template] {0}")]
+    FieldTemplate(#[from] FieldTemplateError),
+    #[error("duplicate of anki notes {note_ids:?}: {term}")]
+    DuplicateNote { term: String, note_ids: Vec<u64> },
     #[error("custom anki error: {0}")]
     Custom(String),
 }
 
+impl DisplayAnkiError {
+    /// Whether AnkiConnect couldn't be reached, see [AnkiConnectError::is_unreachable].
+    pub fn is_unreachable(&self) -> bool {
+        matches!(self, DisplayAnkiError::Request(e) if e.is_unreachable())
+    }
+}
+
+/// The current profile's note settings, see [DisplayAnki::note_config].
+#[derive(Clone, Debug)]
+pub(crate) struct NoteConfig {
+    pub model_name: String,
+    pub deck_name: String,
+    pub tags: Vec<String>,
+    pub field_mappings: Vec<AnkiTermFieldType>,
+    pub field_templates: IndexMap<String, FieldTemplate>,
+    pub frequency_format: AnkiFrequencyFormat,
+    pub glossary: GlossaryRenderer,
+}
+
+impl NoteConfig {
+    /// The value of every field that gets filled, by field name.
+    ///
+    /// Mapped fields get their default marker, unless a template overrides them.
+    pub fn render_fields(&self, context: &NoteContext) -> IndexMap<String, String> {
+        let context = &match context.glossary {
+            Some(_) => *context,
+            None => context.with_glossary(&self.glossary),
+        };
+        let mut field_values = IndexMap::new();
+        for mapping in &self.field_mappings {
+            let marker = mapping.default_marker(self.frequency_format);
+            field_values.insert(mapping.field_name().to_string(), marker.render(context));
+        }
+        for (field_name, template) in &self.field_templates {
+            field_values.insert(field_name.clone(), template.render(context));
+        }
+        field_values
+    }
+
+    /// The name of every field that gets filled, mapped fields first.
+    pub fn field_names(&self) -> Vec<String> {
+        let mut field_names: Vec<String> = self
+            .field_mappings
+            .iter()
+            .map(|mapping| mapping.field_name().to_string())
+            .collect();
+        for field_name in self.field_templates.keys() {
+            if !field_names.contains(field_name) {
+                field_names.push(field_name.clone());
+            }
+        }
+        field_names
+    }
+
+    #[cfg(feature = "anki")]
+    /// A note with the fields rendered from `context` and the profile's tags plus `extra_tags`,
+    /// added in the duplicate scope of `options`.
+    pub fn new_note(
+        &self,
+        context: &NoteContext,
+        extra_tags: &[String],
+        options: DuplicateOptions,
+    ) -> NewNote {
+        let mut tags = self.tags.clone();
+        tags.extend_from_slice(extra_tags);
+        NewNote {
+            deck_name: self.deck_name.clone(),
+            model_name: self.model_name.clone(),
+            fields: self.render_fields(context),
+            tags,
+            options: options.note_options(&self.deck_name),
+        }
+    }
+
+    #[cfg(feature = "anki")]
+    /// The name of the field `Term` is mapped to.
+    pub fn term_field(&self) -> Option<&str> {
+        self.field_mappings
+            .iter()
+            .find_map(|mapping| match mapping {
+                AnkiTermFieldType::Term(name) => Some(name.as_str()),
+                _ => None,
+            })
+    }
+}
+
 #[derive(Clone, Getters, Debug)]
 #[getset(get = "pub")]
 pub struct DisplayAnki {
     client: Ptr<AnkiClient>,
     /// a [Ptr] to the global options
     options: Ptr<YomichanOptions>,
+    /// The `styles.css` of every imported dictionary, by dictionary name.
+    dictionary_styles: Ptr<IndexMap<String, String>>,
 }
 
 /// Setup Impl
 impl DisplayAnki {
     pub fn new_auto(port: &str, options: Ptr<YomichanOptions>) -> AnkiResult<Self> {
         let client = AnkiClient::new_port(port)?.into();
-        let res = Self { client, options };
+        let res = Self {
+            client,
+            options,
+            dictionary_styles: Ptr::default(),
+        };
         Ok(res)
     }
     pub fn new_sync(port: &str, version: u8, options: Ptr<YomichanOptions>) -> Self {
         Self {
             client: AnkiClient::new_port_version(port, version).into(),
             options,
+            dictionary_styles: Ptr::default(),
         }
     }
     pub fn default_latest(options: Ptr<YomichanOptions>) -> Self {
         Self {
             client: AnkiClient::default().into(),
             options,
+            dictionary_styles: Ptr::default(),
         }
     }
 }
@@ -147,14 +262,23 @@ impl DisplayAnki {
     }
 
     /// Updates in memory [anki_direct::cache::Cache] & [YomichanOptions]
-    /// to contain up to date note & deck models. Does not persist to db.
+    /// to contain up to date note & deck models, fetched from the port of the current
+    /// profile's `server`. Does not persist to db.
     ///
     /// To persist to the database, call [Yomichan::update_options]
     pub fn update_all_anki_maps(&self) -> AnkiResult<()> {
         // --- Phase 1: Fetch Data ---
         let (latest_models, latest_decks) = {
-            let mut cache = self.client.write_arc();
-            let cache = cache.cache_mut();
+            let mut client = self.client.write_arc();
+            // the server may have changed since the client was created
+            if let Ok(port) = self.with_anki_options(|anki_opts| {
+                AnkiConnect::from_server(anki_opts.server())
+                    .port()
+                    .to_string()
+            }) {
+                *client = AnkiClient::new_port(&port)?;
+            }
+            let cache = client.cache_mut();
             // Fetch (note) models data
             let models_data = cache.models_mut().hydrate()?.get_cache().clone();
             // Fetch deck data
@@ -198,6 +322,88 @@ impl DisplayAnki {
             .unwrap_or_default()
     }
 
+    /// Replaces the dictionaries' `styles.css`, by dictionary name,
+    /// that [AnkiGlossaryFormat::Full] and [AnkiGlossaryFormat::Compact] glossaries include.
+    ///
+    /// Set from the database when [Yomichan](crate::Yomichan) is created
+    /// and after dictionaries are imported.
+    pub fn set_dictionary_styles(&self, styles: IndexMap<String, String>) {
+        *self.dictionary_styles.write() = styles;
+    }
+
+    #[cfg(feature = "anki")]
+    /// Returns an [AnkiConnect] pointed at the current profile's `server`,
+    /// sending its requests with the [AnkiClient]'s http client.
+    pub fn connect(&self) -> Result<AnkiConnect, DisplayAnkiError> {
+        let connect = self.with_anki_options(|anki_opts| {
+            AnkiConnect::from_server(anki_opts.server()).with_api_key(anki_opts.api_key())
+        })?;
+        let client = self.client.read().reqwest_client().clone();
+        Ok(connect.with_client(client))
+    }
+
+    #[cfg(feature = "anki")]
+    /// Finds all notes matching an Anki search `query` and returns their info.
+    pub fn find_notes_info(&self, query: &str) -> Result<Vec<NoteInfo>, DisplayAnkiError> {
+        let connect = self.connect()?;
+        let ids = connect.find_notes(query)?;
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(connect.notes_info(&ids)?)
+    }
+
+    #[cfg(feature = "anki")]
+    /// Reads the mapped `Term` (and `Reading`, if mapped) field of every note matching `query`,
+    /// as plain text `(term, reading)` pairs. The reading is empty if no reading field is mapped.
+    ///
+    /// Notes missing the term field, or with an empty term, are skipped.
+    pub fn term_field_values(
+        &self,
+        query: &str,
+    ) -> Result<Vec<(String, String)>, DisplayAnkiError> {
+        let (term_field, reading_field) = {
+            let profile = self.options.read().get_current_profile()?;
+            let profile_guard = profile.read();
+            let anki_fields = profile_guard
+                .anki_options()
+                .anki_fields()
+                .as_ref()
+                .ok_or(DisplayAnkiError::AnkiFieldsUninitialized)?;
+            let mut term_field = None;
+            let mut reading_field = None;
+            for mapping in anki_fields.fields() {
+                match mapping {
+                    AnkiTermFieldType::Term(name) => term_field = Some(name.clone()),
+                    AnkiTermFieldType::Reading(name) => reading_field = Some(name.clone()),
+                    _ => {}
+                }
+            }
+            let term_field = term_field.ok_or_else(|| {
+                DisplayAnkiError::Custom("no anki field is mapped to `Term`".into())
+            })?;
+            (term_field, reading_field)
+        };
+
+        let values = self
+            .find_notes_info(query)?
+            .into_iter()
+            .filter_map(|note| {
+                let term = field_plain_text(note.field(&term_field)?);
+                if term.is_empty() {
+                    return None;
+                }
+                let reading = reading_field
+                    .as_deref()
+                    .and_then(|f| note.field(f))
+                    .map(field_plain_text)
+                    .unwrap_or_default();
+                Some((term, reading))
+            })
+            .collect();
+        Ok(values)
+    }
+
     /// Selects an Anki deck by its index for the current profile.
     pub fn select_deck(&self, deck_idx: usize) -> Result<(), DisplayAnkiError> {
         let profile_ptr = self.options.read().get_current_profile()?;
@@ -269,105 +475,289 @@ impl DisplayAnki {
         Ok(())
     }
 
+    /// Fills the Anki field `field_name` with `template` for the current profile,
+    /// e.g. `"{furigana}<br>{glossary-first}"`. See [FieldMarker](crate::anki::FieldMarker)
+    /// for the markers.
+    ///
+    /// Returns [DisplayAnkiError::FieldTemplate] if the template contains an unknown marker.
+    /// Only updates the profile in memory.
+    /// To save it to the db, call [Yomichan::update_options](crate::Yomichan::update_options).
+    pub fn set_field_template(
+        &self,
+        field_name: &str,
+        template: &str,
+    ) -> Result<(), DisplayAnkiError> {
+        let template = FieldTemplate::parse(template)?;
+        let profile_ptr = self.options.read().get_current_profile()?;
+        let mut profile_guard = profile_ptr.write();
+        profile_guard
+            .anki_options_mut()
+            .field_templates_mut()
+            .insert(field_name.to_string(), template);
+        Ok(())
+    }
+
+    /// Removes the template of `field_name`, so the field falls back to its [FieldIndex] mapping.
+    ///
+    /// Returns the removed template.
+    pub fn remove_field_template(
+        &self,
+        field_name: &str,
+    ) -> Result<Option<FieldTemplate>, DisplayAnkiError> {
+        let profile_ptr = self.options.read().get_current_profile()?;
+        let mut profile_guard = profile_ptr.write();
+        let removed = profile_guard
+            .anki_options_mut()
+            .field_templates_mut()
+            .shift_remove(field_name);
+        Ok(removed)
+    }
+
+    /// Fills glossary fields with the definitions of `dictionaries` (names or aliases) only,
+    /// in this order. Every dictionary is used again if `dictionaries` is empty.
+    ///
+    /// Only updates the profile in memory.
+    /// To save it to the db, call [Yomichan::update_options](crate::Yomichan::update_options).
+    pub fn set_glossary_dictionaries(&self, dictionaries: &[&str]) -> Result<(), DisplayAnkiError> {
+        let profile_ptr = self.options.read().get_current_profile()?;
+        let mut profile_guard = profile_ptr.write();
+        profile_guard
+            .anki_options_mut()
+            .set_glossary_dictionaries(dictionaries.iter().map(|d| d.to_string()).collect());
+        Ok(())
+    }
+
+    #[cfg(feature = "anki")]
     /// Builds a note from a dictionary entry and adds it to Anki.
+    ///
+    /// Duplicates are handled by the profile's `duplicate_behavior`, see
+    /// [DisplayAnki::can_add_entries]: [DisplayAnkiError::DuplicateNote] for `Prevent`,
+    /// and `Overwrite` updates the first duplicate with [DisplayAnki::update_note_from_entry]
+    /// and returns its id.
     pub fn add_entry(
         &self,
         entry: &TermDictionaryEntry,
         sentence: Option<&str>,
     ) -> Result<Vec<usize>, DisplayAnkiError> {
-        let note = self.build_note_from_entry(entry, sentence)?;
-        let ids = self.client.read().notes().add_notes(&[note])?;
-        Ok(ids.into_iter().map(|id| id as usize).collect())
+        self.add_entry_with_media(entry, sentence, &NoteMedia::default())
     }
 
-    pub fn build_note_from_entry(
+    #[cfg(feature = "anki")]
+    /// Same as [DisplayAnki::add_entry], but stores `media` first
+    /// to fill the `TermAudio`, `SentenceAudio` and `Image` fields.
+    pub fn add_entry_with_media(
         &self,
         entry: &TermDictionaryEntry,
         sentence: Option<&str>,
-    ) -> Result<Note, DisplayAnkiError> {
-        // --- Phase 1: Configuration Extraction (Locking Scope) ---
-        // This is the ONLY part we are changing.
-        // We will extract all needed values into owned variables here.
+        media: &NoteMedia,
+    ) -> Result<Vec<usize>, DisplayAnkiError> {
+        self.add_entry_with_tags(entry, sentence, media, &[])
+    }
 
-        // CHANGE 1: Define variables to hold the owned data we will extract.
-        let model_name: String;
-        let deck_name: String;
-        let tags: Vec<String>;
-        let field_mappings: Vec<AnkiTermFieldType>;
+    #[cfg(feature = "anki")]
+    /// Same as [DisplayAnki::add_entry_with_media], but tags a newly added note
+    /// with `extra_tags` on top of the profile's configured tags.
+    pub fn add_entry_with_tags(
+        &self,
+        entry: &TermDictionaryEntry,
+        sentence: Option<&str>,
+        media: &NoteMedia,
+        extra_tags: &[String],
+    ) -> Result<Vec<usize>, DisplayAnkiError> {
+        let config = self.note_config()?;
+        let options = self.duplicate_options()?;
+        let connect = self.connect()?;
+        let status = self.entry_add_status(&connect, &config, options, entry, sentence)?;
+        if !status.can_add {
+            return Err(DisplayAnkiError::DuplicateNote {
+                term: entry.get_headword_text_joined(),
+                note_ids: status.duplicate_note_ids,
+            });
+        }
 
-        {
-            let profile = self.options.read().get_current_profile()?;
-            let profile_guard = profile.read();
-            let anki_opts = profile_guard.anki_options();
-            let anki_fields = anki_opts
-                .anki_fields()
-                .as_ref()
-                .ok_or(DisplayAnkiError::AnkiFieldsUninitialized)?;
+        let media = self.store_note_media(media)?;
+        let context = NoteContext::new(entry, sentence).with_media(&media);
+        if let Some(note_id) = status.overwrite_note_id() {
+            self.update_note_from_context(note_id, &context)?;
+            return Ok(vec![note_id as usize]);
+        }
 
-            tags = anki_opts.tags().clone();
-            field_mappings = anki_fields.fields().clone();
+        let note = config.new_note(&context, extra_tags, options);
+        let id = connect.add_note(&note)?;
+        Ok(vec![id as usize])
+    }
 
-            let (model_name_owned, deck_name_owned) = {
-                let global_opts = self.options.read();
-                let global_anki_opts = global_opts.anki().read();
+    #[cfg(feature = "anki")]
+    /// Re-renders the fields of the existing note `note_id` from `entry`,
+    /// except the profile's `overwrite_keep_fields` and fields that would be empty.
+    pub fn update_note_from_entry(
+        &self,
+        note_id: u64,
+        entry: &TermDictionaryEntry,
+        sentence: Option<&str>,
+    ) -> Result<(), DisplayAnkiError> {
+        self.update_note_from_context(note_id, &NoteContext::new(entry, sentence))
+    }
 
-                let selected_model_idx = *anki_fields.selected_model();
-                let selected_deck_idx = *anki_fields.selected_deck();
+    #[cfg(feature = "anki")]
+    /// Same as [DisplayAnki::update_note_from_entry], with the entry, sentence and media
+    /// in `context`.
+    pub fn update_note_from_context(
+        &self,
+        note_id: u64,
+        context: &NoteContext,
+    ) -> Result<(), DisplayAnkiError> {
+        let keep_fields = self.with_anki_options(|opts| opts.overwrite_keep_fields().clone())?;
+        let mut fields = self.note_config()?.render_fields(context);
+        fields.retain(|field_name, _| !keep_fields.contains(field_name));
+        self.update_note_fields(note_id, fields)
+    }
 
-                let (model_ref, _) = global_anki_opts.get_selected_model(selected_model_idx)?;
-                let (deck_ref, _) = global_anki_opts.get_selected_deck(selected_deck_idx)?;
+    #[cfg(feature = "anki")]
+    /// Re-renders only `field_names` of the existing note `note_id` from `entry`,
+    /// e.g. to replace the sentence of a note with a better one.
+    ///
+    /// Fields that the profile doesn't fill, or that would be empty, are skipped.
+    pub fn update_note_fields_from_entry(
+        &self,
+        note_id: u64,
+        entry: &TermDictionaryEntry,
+        sentence: Option<&str>,
+        field_names: &[&str],
+    ) -> Result<(), DisplayAnkiError> {
+        let mut fields = self
+            .note_config()?
+            .render_fields(&NoteContext::new(entry, sentence));
+        fields.retain(|field_name, _| field_names.contains(&field_name.as_str()));
+        self.update_note_fields(note_id, fields)
+    }
 
-                (model_ref.to_string(), deck_ref.to_string())
-            };
+    #[cfg(feature = "anki")]
+    /// Writes the non-empty `fields` to the note, so fields without data
+    /// (e.g. audio that wasn't passed in) keep their current value.
+    fn update_note_fields(
+        &self,
+        note_id: u64,
+        mut fields: IndexMap<String, String>,
+    ) -> Result<(), DisplayAnkiError> {
+        fields.retain(|_, value| !value.is_empty());
+        if fields.is_empty() {
+            return Ok(());
+        }
+        self.connect()?.update_note_fields(note_id, &fields)?;
+        Ok(())
+    }
 
-            model_name = model_name_owned;
-            deck_name = deck_name_owned
+    #[cfg(feature = "anki")]
+    /// Deletes the notes `note_ids` and all of their cards from Anki.
+    pub fn delete_notes(&self, note_ids: &[u64]) -> Result<(), DisplayAnkiError> {
+        if note_ids.is_empty() {
+            return Ok(());
         }
+        self.connect()?.delete_notes(note_ids)?;
+        Ok(())
+    }
 
-        let mut note_builder = anki_direct::notes::NoteBuilder::default();
+    #[cfg(feature = "anki")]
+    /// Builds the note [DisplayAnki::add_entry] would add for `entry`,
+    /// to be added with [AnkiConnect::add_note] or [AnkiConnect::add_notes].
+    pub fn build_note_from_entry(
+        &self,
+        entry: &TermDictionaryEntry,
+        sentence: Option<&str>,
+    ) -> Result<NewNote, DisplayAnkiError> {
+        self.build_note_from_entry_with_tags(entry, sentence, &[])
+    }
 
-        note_builder.model_name(model_name).deck_name(deck_name);
+    #[cfg(feature = "anki")]
+    /// Same as [DisplayAnki::build_note_from_entry], but adds `extra_tags`
+    /// on top of the profile's configured tags.
+    pub fn build_note_from_entry_with_tags(
+        &self,
+        entry: &TermDictionaryEntry,
+        sentence: Option<&str>,
+        extra_tags: &[String],
+    ) -> Result<NewNote, DisplayAnkiError> {
+        self.build_note_from_context(&NoteContext::new(entry, sentence), extra_tags)
+    }
 
-        for mapping in &field_mappings {
-            let headwords_iter = entry.headwords.iter();
-            match mapping {
-                AnkiTermFieldType::Term(field_name) => {
-                    let value: String = headwords_iter
-                        .map(|hw| hw.term.clone())
-                        .collect::<Vec<_>>()
-                        .join(" ");
-                    note_builder.field(field_name, &value);
-                }
-                AnkiTermFieldType::Reading(field_name) => {
-                    let readings: String = headwords_iter
-                        .map(|hw| hw.reading.clone())
-                        .collect::<Vec<_>>()
-                        .join(" ");
-                    note_builder.field(field_name, &readings);
-                }
-                AnkiTermFieldType::Definition(field_name) => {
-                    let value: String = entry
-                        .definitions
-                        .iter()
-                        .flat_map(|def| &def.entries)
-                        .map(|g| g.plain_text.clone())
-                        .collect::<Vec<_>>()
-                        .join("<br>");
-                    note_builder.field(field_name, &value);
-                }
-                AnkiTermFieldType::Sentence(field_name) => {
-                    if let Some(s) = sentence {
-                        note_builder.field(field_name, s);
-                    }
-                }
-                _ => {}
-            }
-        }
+    #[cfg(feature = "anki")]
+    /// Builds a note from the entry, sentence and media in `context`,
+    /// adding `extra_tags` on top of the profile's configured tags.
+    pub fn build_note_from_context(
+        &self,
+        context: &NoteContext,
+        extra_tags: &[String],
+    ) -> Result<NewNote, DisplayAnkiError> {
+        let config = self.note_config()?;
+        Ok(config.new_note(context, extra_tags, self.duplicate_options()?))
+    }
 
-        note_builder.tags(tags);
+    /// Reads the current profile's note settings.
+    pub(crate) fn note_config(&self) -> Result<NoteConfig, DisplayAnkiError> {
+        let profile = self.options.read().get_current_profile()?;
+        let profile_guard = profile.read();
+        let anki_opts = profile_guard.anki_options();
+        let anki_fields = anki_opts
+            .anki_fields()
+            .as_ref()
+            .ok_or(DisplayAnkiError::AnkiFieldsUninitialized)?;
+
+        let (model_name, deck_name) = {
+            let global_opts = self.options.read();
+            let global_anki_opts = global_opts.anki().read();
 
-        let note = note_builder.build(Some(self.client.read_arc().reqwest_client()))?;
-        Ok(note)
+            let selected_model_idx = *anki_fields.selected_model();
+            let selected_deck_idx = *anki_fields.selected_deck();
+
+            let (model_ref, _) = global_anki_opts.get_selected_model(selected_model_idx)?;
+            let (deck_ref, _) = global_anki_opts.get_selected_deck(selected_deck_idx)?;
+
+            (model_ref.to_string(), deck_ref.to_string())
+        };
+
+        Ok(NoteConfig {
+            model_name,
+            deck_name,
+            tags: anki_opts.tags().clone(),
+            field_mappings: anki_fields.fields().clone(),
+            field_templates: anki_opts.field_templates().clone(),
+            frequency_format: *anki_opts.frequency_format(),
+            glossary: self.glossary_renderer(anki_opts, &profile_guard),
+        })
+    }
+
+    /// A renderer with the profile's glossary options and each dictionary's `styles.css`,
+    /// followed by the profile's custom styles.
+    fn glossary_renderer(
+        &self,
+        anki_opts: &AnkiOptions,
+        profile: &YomichanProfile,
+    ) -> GlossaryRenderer {
+        let format = *anki_opts.glossary_format();
+        let max_senses = match *anki_opts.glossary_max_senses() {
+            0 => None,
+            max => Some(max as usize),
+        };
+        let mut glossary = GlossaryRenderer::new(format)
+            .with_dictionaries(anki_opts.glossary_dictionaries().clone())
+            .with_max_senses(max_senses)
+            .with_labels(
+                *anki_opts.glossary_dictionary_names(),
+                *anki_opts.glossary_tags(),
+            );
+        if format == AnkiGlossaryFormat::PlainText {
+            return glossary;
+        }
+        for (dictionary, css) in self.dictionary_styles.read().iter() {
+            glossary = glossary.with_dictionary_styles(dictionary, css);
+        }
+        for (dictionary, options) in profile.dictionaries() {
+            if let Some(css) = &options.styles {
+                glossary = glossary.with_dictionary_styles(dictionary, css);
+            }
+        }
+        glossary
     }
 
     /// Configures note creation options for the current profile in one go.
@@ -618,12 +1008,12 @@ impl DisplayAnki {
 #[cfg(feature = "anki")]
 mod displayanki {
     use crate::anki::core::DisplayAnki;
+    use crate::anki::mock::MockAnkiConnect;
     use crate::settings::core::{FieldIndex, YomichanOptions};
-    use crate::utils::test_utils::{TEST_PATHS, YCD};
+    use crate::utils::test_utils::{TestYomichan, FIXTURE_DICTIONARY, TEST_PATHS};
     use crate::{Ptr, Yomichan};
     use parking_lot::Mutex;
-    use scopeguard;
-    use std::sync::{Arc, LazyLock};
+    use std::sync::LazyLock;
 
     static DISPLAYANKI: LazyLock<Mutex<DisplayAnki>> = LazyLock::new(|| {
         let options = Ptr::new(YomichanOptions::new());
@@ -733,13 +1123,27 @@ mod displayanki {
     //     display_anki..notes().gui_edit(id[0]).unwrap();
     // }
 
-    #[ignore]
+    /// A [TestYomichan] with the fixture dictionary, whose profile's `server` is a
+    /// [MockAnkiConnect] with only the `Japanese` model with `fields`.
+    fn mock_yomichan(name: &str, fields: &[&str]) -> (TestYomichan, MockAnkiConnect) {
+        let ycd = TestYomichan::new(name, &[FIXTURE_DICTIONARY]);
+        let anki = MockAnkiConnect::start().unwrap();
+        anki.remove_model("Basic").add_model("Japanese", fields);
+        ycd.with_profile_mut(|profile| {
+            profile.anki_options_mut().set_server(anki.server());
+        })
+        .unwrap();
+        (ycd, anki)
+    }
+
     #[test]
     fn auto_note() {
-        let ycd = &YCD;
-        ycd.set_language("ja").unwrap();
-
-        ycd.anki()
+        let (ycd, anki) = mock_yomichan(
+            "anki_auto_note",
+            &["Word", "Sentence", "Reading", "Meaning"],
+        );
+        let display_anki = ycd.anki();
+        display_anki
             .configure_note_creation_with_first_available(&[
                 FieldIndex::Term(0),
                 FieldIndex::Sentence(1),
@@ -748,82 +1152,74 @@ mod displayanki {
             ])
             .unwrap();
 
-        // The rest of the test logic remains the same.
-        let sentence = "日本語が好きです";
-        let res = ycd.search(sentence).unwrap();
-        let mut notes = vec![];
-        let display_anki = ycd.anki();
-        for item in res {
-            let entry: Arc<crate::TermSearchResults> = item.results.unwrap();
-            let first = &entry.dictionary_entries[0];
-            let note = display_anki
-                .build_note_from_entry(first, Some(sentence))
-                .unwrap();
-            notes.push(note);
-        }
-        dbg!(&notes);
-        let id = display_anki
-            .client()
-            .read_arc()
-            .notes()
-            .add_notes(&[notes[0].clone()])
-            .unwrap();
-        display_anki
-            .client()
-            .read_arc()
-            .notes()
-            .gui_edit(id[0])
-            .unwrap();
+        let sentence = "猫が鳴いた";
+        let notes: Vec<_> = ycd
+            .search(sentence)
+            .unwrap()
+            .into_iter()
+            .filter_map(|segment| segment.results)
+            .map(|results| {
+                display_anki
+                    .build_note_from_entry(&results.dictionary_entries[0], Some(sentence))
+                    .unwrap()
+            })
+            .collect();
+        let words: Vec<_> = notes
+            .iter()
+            .map(|note| note.fields["Word"].as_str())
+            .collect();
+        assert_eq!(words, ["猫", "鳴く"]);
+        assert_eq!(notes[0].model_name, "Japanese");
+        assert_eq!(notes[0].deck_name, "Default");
+
+        let id = display_anki.connect().unwrap().add_note(&notes[0]).unwrap();
+        let added = anki.notes();
+        assert_eq!(added.len(), 1);
+        assert_eq!(added[0].id, id);
+        assert_eq!(added[0].fields["Sentence"], sentence);
+        assert_eq!(added[0].fields["Reading"], "ねこ");
+        assert!(added[0].fields["Meaning"].contains("cat"));
     }
 
-    #[ignore]
     #[test]
     fn build_note_auto_config() {
-        let ycd = &YCD;
-        ycd.set_language("ja").unwrap();
-
+        let (ycd, anki) = mock_yomichan("anki_auto_config", &["Word", "Reading", "Meaning"]);
         let display_anki = ycd.anki();
         display_anki.configure_note_creation_auto().unwrap();
 
-        // The rest of the test logic is identical.
-        let sentence = format!(
-            "日本語が好きです - {}",
-            chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0)
-        );
-        let res = ycd.search(sentence.as_str()).unwrap();
-        let display_anki = ycd.anki();
-        let entry: Arc<crate::TermSearchResults> = res.into_iter().next().unwrap().results.unwrap();
-        let first = &entry.dictionary_entries[0];
-        let note = display_anki
-            .build_note_from_entry(first, Some(sentence.as_str()))
-            .unwrap();
-        dbg!(&note);
-        let id = display_anki
-            .client()
-            .read_arc()
-            .notes()
-            .add_notes(&[note])
+        let sentence = "犬です";
+        let results = ycd
+            .search(sentence)
+            .unwrap()
+            .into_iter()
+            .find_map(|segment| segment.results)
             .unwrap();
-
-        display_anki
-            .client()
-            .read_arc()
-            .notes()
-            .gui_edit(id[0])
+        let note = display_anki
+            .build_note_from_entry(&results.dictionary_entries[0], Some(sentence))
             .unwrap();
-
-        // Use scopeguard to ensure the note is deleted after the test, even if it panics.
-        let client = display_anki.client().clone();
-        scopeguard::defer! {
-            client.read_arc().notes().delete_notes_by_ids(&id).unwrap();
-        }
+        // the model has no field left for the sentence
+        assert_eq!(
+            note.fields.keys().collect::<Vec<_>>(),
+            ["Word", "Reading", "Meaning"]
+        );
+        assert_eq!(note.fields["Word"], "犬");
+        assert_eq!(note.fields["Reading"], "いぬ");
+        assert!(note.fields["Meaning"].contains("dog"));
+
+        let connect = display_anki.connect().unwrap();
+        let id = connect.add_note(&note).unwrap();
+        assert_eq!(anki.notes()[0].id, id);
+        connect.delete_notes(&[id]).unwrap();
+        assert!(anki.notes().is_empty());
     }
 
-    #[ignore]
     #[test]
     fn streamlined_anki_api_test() {
-        let ycd = &YCD;
-        ycd.set_language("es").unwrap();
+        let (ycd, anki) = mock_yomichan(
+            "anki_streamlined",
+            &["Word", "Sentence", "Reading", "Meaning"],
+        );
+        anki.add_deck("Mining");
 
         // 1. Sync Anki data (One-time hydration)
         ycd.anki().update_all_anki_maps().unwrap();
@@ -831,17 +1227,12 @@ mod displayanki {
         // 2. Discover (Populate GUI dropdowns)
         let decks = ycd.anki().deck_names();
         let models = ycd.anki().model_names();
-
-        println!("Available Decks: {:?}", decks);
-        println!("Available Models: {:?}", models);
-
-        if models.is_empty() {
-            return; // Skip if no models available in local Anki
-        }
+        assert_eq!(models, ["Japanese"]);
+        let mining = decks.iter().position(|deck| deck == "Mining").unwrap();
+        assert!(decks.iter().any(|deck| deck == "Default"));
 
         // 3. Configure (The "Hooking Up" part via indices)
-        // Let's assume the user selects index 0 for both
-        ycd.anki().select_deck(0).unwrap();
+        ycd.anki().select_deck(mining).unwrap();
         ycd.anki().select_model(0).unwrap();
 
         // Map fields by index (GUI friendly)
@@ -854,22 +1245,30 @@ mod displayanki {
             .unwrap();
 
         // 4. Action (The "Star" button)
-        let sentence = "espanol es muy bueno";
-        let res = ycd.search(sentence).unwrap();
-        let first_segment = res.into_iter().find(|s| s.results.is_some()).unwrap();
-        let results = first_segment.results.unwrap();
+        let sentence = "犬が鳴いた";
+        let results = ycd
+            .search(sentence)
+            .unwrap()
+            .into_iter()
+            .find_map(|segment| segment.results)
+            .unwrap();
         let first_entry = &results.dictionary_entries[0];
 
         // This one call builds and adds the note
         let note_ids = ycd.anki().add_entry(first_entry, Some(sentence)).unwrap();
-        println!("Added note IDs: {:?}", note_ids);
+        let notes = anki.notes();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(note_ids, [notes[0].id as usize]);
+        assert_eq!(notes[0].deck_name, "Mining");
+        assert_eq!(notes[0].model_name, "Japanese");
+        assert_eq!(notes[0].fields["Word"], "犬");
+        assert_eq!(notes[0].fields["Reading"], "いぬ");
+        // not mapped
+        assert_eq!(notes[0].fields["Sentence"], "");
 
         // 5. Cleanup
-        let client = ycd.anki().client().clone();
-        client
-            .read_arc()
-            .notes()
-            .delete_notes_by_ids(&note_ids)
-            .unwrap();
+        let note_ids: Vec<u64> = note_ids.into_iter().map(|id| id as u64).collect();
+        ycd.anki().delete_notes(&note_ids).unwrap();
+        assert!(anki.notes().is_empty());
     }
 }
@@ -0,0 +1,422 @@
+//! Duplicate detection for new notes.
+//!
+//! Whether a note is a duplicate is decided by AnkiConnect's `canAddNotesWithErrorDetail`,
+//! which applies Anki's own rules: the model's first field, without html,
+//! in the profile's [AnkiDuplicateScope].
+//! `findNotes` only looks up the ids of the duplicates, e.g. to overwrite them.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    anki::{
+        connect::{
+            field_plain_text, AnkiConnect, CanAddNote, DuplicateScopeOptions, NewNote,
+            NewNoteOptions,
+        },
+        core::{DisplayAnki, DisplayAnkiError, NoteConfig},
+        template::NoteContext,
+    },
+    settings::core::{AnkiDuplicateBehavior, AnkiDuplicateScope},
+    TermDictionaryEntry,
+};
+
+/// Whether an entry can be added to Anki, see [DisplayAnki::can_add_entries].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EntryAddStatus {
+    /// Whether Anki considers the entry's note a duplicate.
+    pub duplicate: bool,
+    /// The duplicates found with `findNotes`, which can miss some of the notes Anki compares
+    /// equal (e.g. a first field with different html), so this can be empty for a duplicate.
+    pub duplicate_note_ids: Vec<u64>,
+    /// What [DisplayAnki::add_entry] does with a duplicate.
+    pub behavior: AnkiDuplicateBehavior,
+    /// Whether [DisplayAnki::add_entry] adds (or overwrites) a note for the entry;
+    /// `false` for a duplicate with [AnkiDuplicateBehavior::Prevent],
+    /// or with [AnkiDuplicateBehavior::Overwrite] if no duplicate to overwrite was found.
+    pub can_add: bool,
+}
+
+impl EntryAddStatus {
+    fn new(duplicate_note_ids: Option<Vec<u64>>, behavior: AnkiDuplicateBehavior) -> Self {
+        let duplicate = duplicate_note_ids.is_some();
+        let duplicate_note_ids = duplicate_note_ids.unwrap_or_default();
+        let can_add = match behavior {
+            _ if !duplicate => true,
+            AnkiDuplicateBehavior::Allow => true,
+            AnkiDuplicateBehavior::Overwrite => !duplicate_note_ids.is_empty(),
+            AnkiDuplicateBehavior::Prevent => false,
+        };
+        Self {
+            duplicate,
+            duplicate_note_ids,
+            behavior,
+            can_add,
+        }
+    }
+
+    pub fn is_duplicate(&self) -> bool {
+        self.duplicate
+    }
+
+    /// The note [DisplayAnki::add_entry] overwrites instead of adding a new one.
+    pub fn overwrite_note_id(&self) -> Option<u64> {
+        match self.behavior {
+            AnkiDuplicateBehavior::Overwrite => self.duplicate_note_ids.first().copied(),
+            _ => None,
+        }
+    }
+}
+
+/// The current profile's duplicate settings.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct DuplicateOptions {
+    pub check_for_duplicates: bool,
+    pub scope: AnkiDuplicateScope,
+    pub check_all_models: bool,
+    pub behavior: AnkiDuplicateBehavior,
+}
+
+impl DuplicateOptions {
+    /// The options of a new note in `deck_name`, so Anki checks it in the duplicate scope.
+    ///
+    /// Anki accepts duplicates if duplicates aren't checked,
+    /// or if the behavior is [AnkiDuplicateBehavior::Allow].
+    pub fn note_options(&self, deck_name: &str) -> NewNoteOptions {
+        let (scope, deck_name, check_children) = match self.scope {
+            AnkiDuplicateScope::Collection => ("collection", None, false),
+            AnkiDuplicateScope::Deck => ("deck", Some(deck_name), false),
+            AnkiDuplicateScope::DeckRoot => {
+                let root = deck_name.split("::").next().unwrap_or_default();
+                ("deck", Some(root), true)
+            }
+        };
+        NewNoteOptions {
+            allow_duplicate: !self.check_for_duplicates
+                || self.behavior == AnkiDuplicateBehavior::Allow,
+            duplicate_scope: Some(scope.to_string()),
+            duplicate_scope_options: DuplicateScopeOptions {
+                deck_name: deck_name.map(str::to_string),
+                check_children,
+                check_all_models: self.check_all_models,
+            },
+        }
+    }
+}
+
+impl DisplayAnki {
+    /// Checks every entry for duplicate notes, in order, without adding anything.
+    ///
+    /// Uses the current profile's `check_for_duplicates`, `duplicate_scope`,
+    /// `duplicate_scope_check_all_models` and `duplicate_behavior`.
+    /// If no field is mapped to `Term`, entries are never duplicates.
+    ///
+    /// ```rust,no_run
+    /// # use yomichan_rs::Yomichan;
+    /// # let ycd = Yomichan::new("path/to/db.ycd").unwrap();
+    /// # let entries: Vec<yomichan_rs::TermDictionaryEntry> = vec![];
+    /// let statuses = ycd.anki().can_add_entries(&entries)?;
+    /// for status in statuses {
+    ///     // grey out the add button
+    ///     let enabled = status.can_add;
+    /// }
+    /// # Ok::<(), yomichan_rs::anki::DisplayAnkiError>(())
+    /// ```
+    pub fn can_add_entries(
+        &self,
+        entries: &[TermDictionaryEntry],
+    ) -> Result<Vec<EntryAddStatus>, DisplayAnkiError> {
+        let config = self.note_config()?;
+        let options = self.duplicate_options()?;
+        let connect = self.connect()?;
+        entries
+            .iter()
+            .map(|entry| self.entry_add_status(&connect, &config, options, entry, None))
+            .collect()
+    }
+
+    /// The ids of the notes that were made from `entry`: the notes in the profile's duplicate
+    /// scope whose `Term` field has the entry's value.
+    ///
+    /// Empty if no field is mapped to `Term`.
+    pub fn find_entry_notes(
+        &self,
+        entry: &TermDictionaryEntry,
+    ) -> Result<Vec<u64>, DisplayAnkiError> {
+        let config = self.note_config()?;
+        let options = self.duplicate_options()?;
+        let connect = self.connect()?;
+        self.find_notes_with_term_of(&connect, &config, options, entry, None)
+    }
+
+    pub(crate) fn duplicate_options(&self) -> Result<DuplicateOptions, DisplayAnkiError> {
+        let options = self.with_anki_options(|anki_opts| DuplicateOptions {
+            check_for_duplicates: *anki_opts.check_for_duplicates(),
+            scope: *anki_opts.duplicate_scope(),
+            check_all_models: *anki_opts.duplicate_scope_check_all_models(),
+            behavior: *anki_opts.duplicate_behavior(),
+        })?;
+        Ok(options)
+    }
+
+    pub(crate) fn entry_add_status(
+        &self,
+        connect: &AnkiConnect,
+        config: &NoteConfig,
+        options: DuplicateOptions,
+        entry: &TermDictionaryEntry,
+        sentence: Option<&str>,
+    ) -> Result<EntryAddStatus, DisplayAnkiError> {
+        let note = config.new_note(&NoteContext::new(entry, sentence), &[], options);
+        let duplicate_note_ids = find_duplicates(connect, options, &note)?;
+        Ok(EntryAddStatus::new(duplicate_note_ids, options.behavior))
+    }
+
+    fn find_notes_with_term_of(
+        &self,
+        connect: &AnkiConnect,
+        config: &NoteConfig,
+        options: DuplicateOptions,
+        entry: &TermDictionaryEntry,
+        sentence: Option<&str>,
+    ) -> Result<Vec<u64>, DisplayAnkiError> {
+        let Some(term_field) = config.term_field() else {
+            return Ok(vec![]);
+        };
+        let fields = config.render_fields(&NoteContext::new(entry, sentence));
+        let value = fields
+            .get(term_field)
+            .map(String::as_str)
+            .unwrap_or_default();
+        if value.is_empty() {
+            return Ok(vec![]);
+        }
+        find_notes_with_field(
+            connect,
+            options,
+            term_field,
+            value,
+            &config.model_name,
+            &config.deck_name,
+        )
+    }
+}
+
+/// Checks `note` with Anki's own duplicate rules, in the duplicate scope of `options`.
+///
+/// Returns the ids of its duplicates (see [EntryAddStatus::duplicate_note_ids]),
+/// or `None` if it isn't a duplicate or duplicates aren't checked.
+/// Notes that can't be added for other reasons (e.g. an empty first field) aren't duplicates;
+/// adding them returns AnkiConnect's error.
+pub(crate) fn find_duplicates(
+    connect: &AnkiConnect,
+    options: DuplicateOptions,
+    note: &NewNote,
+) -> Result<Option<Vec<u64>>, DisplayAnkiError> {
+    if !options.check_for_duplicates {
+        return Ok(None);
+    }
+    let mut check = note.clone();
+    check.options = options.note_options(&note.deck_name);
+    check.options.allow_duplicate = false;
+    let is_duplicate = connect
+        .can_add_notes(std::slice::from_ref(&check))?
+        .first()
+        .is_some_and(CanAddNote::is_duplicate);
+    if !is_duplicate {
+        return Ok(None);
+    }
+
+    // Anki compares the first field, so look the duplicates up by it
+    let model_fields = connect.model_field_names(&note.model_name)?;
+    let Some(first_field) = model_fields.first() else {
+        return Ok(Some(vec![]));
+    };
+    let value = note
+        .fields
+        .get(first_field)
+        .map(String::as_str)
+        .unwrap_or_default();
+    let find = |value: &str| {
+        find_notes_with_field(
+            connect,
+            options,
+            first_field,
+            value,
+            &note.model_name,
+            &note.deck_name,
+        )
+    };
+    let mut note_ids = find(value)?;
+    let plain_text = field_plain_text(value);
+    if note_ids.is_empty() && plain_text != value {
+        note_ids = find(&plain_text)?;
+    }
+    Ok(Some(note_ids))
+}
+
+/// The notes in the duplicate scope of `model_name` and `deck_name` whose `field` is `value`.
+pub(crate) fn find_notes_with_field(
+    connect: &AnkiConnect,
+    options: DuplicateOptions,
+    field: &str,
+    value: &str,
+    model_name: &str,
+    deck_name: &str,
+) -> Result<Vec<u64>, DisplayAnkiError> {
+    let query = duplicate_query(field, value, model_name, deck_name, options);
+    Ok(connect.find_notes(&query)?)
+}
+
+/// The Anki search for notes whose `field` is `value`, limited to the duplicate scope.
+fn duplicate_query(
+    field: &str,
+    value: &str,
+    model_name: &str,
+    deck_name: &str,
+    options: DuplicateOptions,
+) -> String {
+    let mut query = format!("\"{}:{}\"", escape_search(field), escape_search(value));
+    if !options.check_all_models {
+        query.push_str(&format!(" \"note:{}\"", escape_search(model_name)));
+    }
+    match options.scope {
+        AnkiDuplicateScope::Collection => {}
+        AnkiDuplicateScope::Deck => {
+            // `deck:` includes subdecks
+            let deck = escape_search(deck_name);
+            query.push_str(&format!(" \"deck:{deck}\" -\"deck:{deck}::*\""));
+        }
+        AnkiDuplicateScope::DeckRoot => {
+            let root = deck_name.split("::").next().unwrap_or_default();
+            query.push_str(&format!(" \"deck:{}\"", escape_search(root)));
+        }
+    }
+    query
+}
+
+/// Escapes Anki's search wildcards and quotes, for use inside a quoted search term.
+fn escape_search(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '"' | '*' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod duplicate_tests {
+    use super::*;
+
+    fn options(scope: AnkiDuplicateScope, check_all_models: bool) -> DuplicateOptions {
+        DuplicateOptions {
+            check_for_duplicates: true,
+            scope,
+            check_all_models,
+            behavior: AnkiDuplicateBehavior::Prevent,
+        }
+    }
+
+    #[test]
+    fn scopes() {
+        let query = |scope, all_models| {
+            duplicate_query(
+                "Word",
+                "食べる",
+                "Japanese_Vocab",
+                "Mining::Novels",
+                options(scope, all_models),
+            )
+        };
+        assert_eq!(
+            query(AnkiDuplicateScope::Collection, true),
+            "\"Word:食べる\""
+        );
+        assert_eq!(
+            query(AnkiDuplicateScope::Collection, false),
+            "\"Word:食べる\" \"note:Japanese\\_Vocab\""
+        );
+        assert_eq!(
+            query(AnkiDuplicateScope::Deck, true),
+            "\"Word:食べる\" \"deck:Mining::Novels\" -\"deck:Mining::Novels::*\""
+        );
+        assert_eq!(
+            query(AnkiDuplicateScope::DeckRoot, true),
+            "\"Word:食べる\" \"deck:Mining\""
+        );
+    }
+
+    #[test]
+    fn escapes_wildcards() {
+        assert_eq!(escape_search(r#"a*b_c"d\"#), r#"a\*b\_c\"d\\"#);
+    }
+
+    #[test]
+    fn status() {
+        let duplicate = EntryAddStatus::new(Some(vec![1]), AnkiDuplicateBehavior::Prevent);
+        assert!(duplicate.is_duplicate() && !duplicate.can_add);
+        let overwrite = EntryAddStatus::new(Some(vec![1, 2]), AnkiDuplicateBehavior::Overwrite);
+        assert!(overwrite.can_add);
+        assert_eq!(overwrite.overwrite_note_id(), Some(1));
+        let unfound = EntryAddStatus::new(Some(vec![]), AnkiDuplicateBehavior::Overwrite);
+        assert!(unfound.is_duplicate() && !unfound.can_add);
+        let allow = EntryAddStatus::new(Some(vec![1]), AnkiDuplicateBehavior::Allow);
+        assert!(allow.can_add && allow.overwrite_note_id().is_none());
+        let new = EntryAddStatus::new(None, AnkiDuplicateBehavior::Prevent);
+        assert!(new.can_add && new.overwrite_note_id().is_none());
+    }
+
+    #[test]
+    fn note_options() {
+        let deck_root = options(AnkiDuplicateScope::DeckRoot, false).note_options("Mining::Novels");
+        assert!(!deck_root.allow_duplicate);
+        assert_eq!(deck_root.duplicate_scope.as_deref(), Some("deck"));
+        assert_eq!(
+            deck_root.duplicate_scope_options.deck_name.as_deref(),
+            Some("Mining")
+        );
+        assert!(deck_root.duplicate_scope_options.check_children);
+
+        let mut allow = options(AnkiDuplicateScope::Collection, true);
+        allow.behavior = AnkiDuplicateBehavior::Allow;
+        let allow = allow.note_options("Mining");
+        assert!(allow.allow_duplicate && allow.duplicate_scope_options.check_all_models);
+    }
+
+    #[test]
+    fn finds_duplicates() {
+        use crate::anki::mock::MockAnkiConnect;
+
+        let anki = MockAnkiConnect::start().unwrap();
+        anki.add_model("Japanese", &["Word", "Meaning"]);
+        let connect = anki.connect();
+        let options = options(AnkiDuplicateScope::Collection, false);
+        let note = |word: &str| NewNote {
+            deck_name: "Default".to_string(),
+            model_name: "Japanese".to_string(),
+            fields: [("Word", word), ("Meaning", "cat")]
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            tags: vec![],
+            options: options.note_options("Default"),
+        };
+        assert_eq!(
+            find_duplicates(&connect, options, &note("猫")).unwrap(),
+            None
+        );
+        let id = connect.add_note(&note("猫")).unwrap();
+        assert_eq!(
+            find_duplicates(&connect, options, &note("猫")).unwrap(),
+            Some(vec![id])
+        );
+        assert_eq!(
+            find_duplicates(&connect, options, &note("犬")).unwrap(),
+            None
+        );
+        // an empty first field can't be added, but isn't a duplicate
+        assert_eq!(find_duplicates(&connect, options, &note("")).unwrap(), None);
+    }
+}
@@ -0,0 +1,576 @@
+//! Exports notes without AnkiConnect, for machines that can't run it (e.g. AnkiMobile only).
+//!
+//! Notes are rendered with the same model, deck, field mappings and templates as
+//! [DisplayAnki::add_entry], and written either as a TSV file for Anki's text import,
+//! or as a standalone `.apkg` package with the note type and media.
+
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use indexmap::IndexMap;
+use rusqlite::{params, Connection};
+use serde_json::json;
+use sha1::{Digest, Sha1};
+
+use crate::{
+    anki::{
+        connect::field_plain_text,
+        core::{DisplayAnki, DisplayAnkiError, NoteConfig},
+        media::{fnv1a, AnkiMedia, MediaSource, NoteMedia},
+        template::NoteContext,
+    },
+    TermDictionaryEntry,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum AnkiExportError {
+    #[error("filesystem error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to write the apkg collection: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("failed to write the apkg package: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("media `{filename}` is a url ({url}), which can't be downloaded without ankiconnect")]
+    RemoteMedia { filename: String, url: String },
+}
+
+/// A note rendered for export.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExportedNote {
+    /// The value of every field of the model, in the model's order.
+    pub fields: Vec<String>,
+    pub tags: Vec<String>,
+}
+
+/// Notes rendered with the current profile's note settings, see [DisplayAnki::note_export].
+#[derive(Clone, Debug)]
+pub struct NoteExport {
+    config: NoteConfig,
+    /// The fields of the note type, in order.
+    field_names: Vec<String>,
+    notes: Vec<ExportedNote>,
+    /// Media by filename.
+    media: IndexMap<String, AnkiMedia>,
+}
+
+impl DisplayAnki {
+    /// Starts an export with the current profile's model, deck, field mappings and templates.
+    ///
+    /// The model's fields are taken from the cached note models
+    /// (see [DisplayAnki::update_all_anki_maps]), so Anki doesn't need to be running.
+    ///
+    /// ```rust,no_run
+    /// # use yomichan_rs::Yomichan;
+    /// # let ycd = Yomichan::new("path/to/db.ycd").unwrap();
+    /// # let segments = ycd.search("日本語").unwrap();
+    /// # let entry = &segments[0].results.as_ref().unwrap().dictionary_entries[0];
+    /// let mut export = ycd.anki().note_export()?;
+    /// export.add_entry(entry, Some("日本語を勉強しています。"));
+    /// export.write_tsv("mined.txt")?;
+    /// // or, with the note type and media
+    /// export.write_apkg("mined.apkg")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn note_export(&self) -> Result<NoteExport, DisplayAnkiError> {
+        let config = self.note_config()?;
+        let model_fields = {
+            let opts = self.options().read();
+            let global_anki = opts.anki().read();
+            global_anki
+                .note_models_map()
+                .get(&config.model_name)
+                .map(|details| details.fields.clone())
+                .unwrap_or_default()
+        };
+        Ok(NoteExport::new(config, model_fields))
+    }
+}
+
+impl NoteExport {
+    pub(crate) fn new(config: NoteConfig, model_fields: Vec<String>) -> Self {
+        // fields the profile fills but the cached model lacks still get a column
+        let mut field_names = model_fields;
+        for field_name in config.field_names() {
+            if !field_names.contains(&field_name) {
+                field_names.push(field_name);
+            }
+        }
+        Self {
+            config,
+            field_names,
+            notes: vec![],
+            media: IndexMap::new(),
+        }
+    }
+
+    pub fn model_name(&self) -> &str {
+        &self.config.model_name
+    }
+
+    pub fn deck_name(&self) -> &str {
+        &self.config.deck_name
+    }
+
+    pub fn field_names(&self) -> &[String] {
+        &self.field_names
+    }
+
+    pub fn notes(&self) -> &[ExportedNote] {
+        &self.notes
+    }
+
+    /// Renders a note from `entry`, the same way [DisplayAnki::build_note_from_entry] does.
+    pub fn add_entry(&mut self, entry: &TermDictionaryEntry, sentence: Option<&str>) -> &mut Self {
+        self.add_entry_with_media(entry, sentence, &NoteMedia::default())
+    }
+
+    /// Same as [NoteExport::add_entry], filling the `TermAudio`, `SentenceAudio`
+    /// and `Image` fields with `media`, which is packaged by [NoteExport::write_apkg].
+    pub fn add_entry_with_media(
+        &mut self,
+        entry: &TermDictionaryEntry,
+        sentence: Option<&str>,
+        media: &NoteMedia,
+    ) -> &mut Self {
+        let filenames = media.filenames();
+        let context = NoteContext::new(entry, sentence).with_media(&filenames);
+        let fields = self.config.render_fields(&context);
+        for file in media.files() {
+            self.media.insert(file.filename(), file);
+        }
+        self.push_note(fields, self.config.tags.clone());
+        self
+    }
+
+    fn push_note(&mut self, mut fields: IndexMap<String, String>, tags: Vec<String>) {
+        let fields = self
+            .field_names
+            .iter()
+            .map(|field_name| fields.swap_remove(field_name).unwrap_or_default())
+            .collect();
+        self.notes.push(ExportedNote { fields, tags });
+    }
+
+    /// The notes as a tab separated file for Anki's `File > Import`,
+    /// with `#notetype`, `#deck` and `#columns` headers so no import settings need changing.
+    pub fn to_tsv(&self) -> String {
+        let mut tsv = String::from("#separator:tab\n#html:true\n");
+        tsv.push_str(&format!("#notetype:{}\n", self.config.model_name));
+        tsv.push_str(&format!("#deck:{}\n", self.config.deck_name));
+        let tags_column = self.field_names.len() + 1;
+        tsv.push_str(&format!("#tags column:{tags_column}\n"));
+        let mut columns: Vec<&str> = self.field_names.iter().map(String::as_str).collect();
+        columns.push("Tags");
+        tsv.push_str(&format!("#columns:{}\n", columns.join("\t")));
+        for note in &self.notes {
+            let mut row: Vec<String> = note.fields.iter().map(|f| tsv_field(f)).collect();
+            row.push(tsv_field(&note.tags.join(" ")));
+            tsv.push_str(&row.join("\t"));
+            tsv.push('\n');
+        }
+        tsv
+    }
+
+    pub fn write_tsv(&self, path: impl AsRef<Path>) -> Result<(), AnkiExportError> {
+        std::fs::write(path, self.to_tsv())?;
+        Ok(())
+    }
+
+    /// Writes the media the notes refer to into `dir`, e.g. Anki's `collection.media` folder,
+    /// since a TSV import doesn't carry media. Returns the written paths.
+    ///
+    /// Returns [AnkiExportError::RemoteMedia] for media from a url.
+    pub fn write_media(&self, dir: impl AsRef<Path>) -> Result<Vec<PathBuf>, AnkiExportError> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let mut paths = Vec::with_capacity(self.media.len());
+        for (filename, media) in &self.media {
+            let path = dir.join(filename);
+            std::fs::write(&path, media_bytes(filename, media)?)?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+
+    /// Writes the notes, their note type and their media as an `.apkg` package.
+    ///
+    /// The note type has the model's name and fields with a single basic card
+    /// (the first field on the front); importing into a collection that already has
+    /// the model keeps the existing one. Note guids are derived from the fields,
+    /// so importing the same note twice updates it instead of adding a copy.
+    ///
+    /// Returns [AnkiExportError::RemoteMedia] for media from a url.
+    pub fn write_apkg(&self, path: impl AsRef<Path>) -> Result<(), AnkiExportError> {
+        let path = path.as_ref();
+        let collection_path = path.with_extension("anki2.tmp");
+        let _ = std::fs::remove_file(&collection_path);
+        let collection = self
+            .write_collection(&collection_path)
+            .and_then(|_| Ok(std::fs::read(&collection_path)?));
+        let _ = std::fs::remove_file(&collection_path);
+        let collection = collection?;
+
+        let mut zip = zip::ZipWriter::new(File::create(path)?);
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        zip.start_file("collection.anki2", options)?;
+        zip.write_all(&collection)?;
+
+        let mut media_map = serde_json::Map::new();
+        for (i, (filename, media)) in self.media.iter().enumerate() {
+            zip.start_file(i.to_string(), options)?;
+            zip.write_all(&media_bytes(filename, media)?)?;
+            media_map.insert(i.to_string(), json!(filename));
+        }
+        zip.start_file("media", options)?;
+        zip.write_all(serde_json::Value::Object(media_map).to_string().as_bytes())?;
+        zip.finish()?;
+        Ok(())
+    }
+
+    /// Writes a schema 11 collection, the format `.apkg` importers accept.
+    fn write_collection(&self, path: &Path) -> Result<(), AnkiExportError> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(COLLECTION_SCHEMA)?;
+
+        let now_ms = Utc::now().timestamp_millis();
+        let now = now_ms / 1000;
+        let model_id = now_ms;
+        let deck_id = now_ms + 1;
+        let models = json!({ model_id.to_string(): self.model_json(model_id, deck_id, now) });
+        let decks = json!({
+            "1": deck_json(1, "Default", now),
+            deck_id.to_string(): deck_json(deck_id, &self.config.deck_name, now),
+        });
+        conn.execute(
+            "INSERT INTO col VALUES (1, ?1, ?2, ?3, 11, 0, 0, 0, ?4, ?5, ?6, ?7, '{}')",
+            params![
+                now,
+                now_ms,
+                now_ms,
+                json!({ "curDeck": deck_id, "curModel": model_id.to_string() }).to_string(),
+                models.to_string(),
+                decks.to_string(),
+                json!({ "1": deck_config_json(now) }).to_string(),
+            ],
+        )?;
+
+        for (i, note) in self.notes.iter().enumerate() {
+            let note_id = now_ms + i as i64;
+            let sort_field = note
+                .fields
+                .first()
+                .map(|f| field_plain_text(f))
+                .unwrap_or_default();
+            let tags = match note.tags.is_empty() {
+                true => String::new(),
+                false => format!(" {} ", note.tags.join(" ")),
+            };
+            conn.execute(
+                "INSERT INTO notes VALUES (?1, ?2, ?3, ?4, -1, ?5, ?6, ?7, ?8, 0, '')",
+                params![
+                    note_id,
+                    self.note_guid(note),
+                    model_id,
+                    now,
+                    tags,
+                    note.fields.join("\x1f"),
+                    sort_field,
+                    field_checksum(&sort_field),
+                ],
+            )?;
+            conn.execute(
+                "INSERT INTO cards
+                 VALUES (?1, ?1, ?2, 0, ?3, -1, 0, 0, ?4, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+                params![note_id, deck_id, now, i as i64 + 1],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn model_json(&self, model_id: i64, deck_id: i64, now: i64) -> serde_json::Value {
+        let fields: Vec<_> = self
+            .field_names
+            .iter()
+            .enumerate()
+            .map(|(ord, name)| {
+                json!({
+                    "name": name, "ord": ord, "sticky": false, "rtl": false,
+                    "font": "Arial", "size": 20, "media": [],
+                })
+            })
+            .collect();
+        let front = self
+            .field_names
+            .first()
+            .map(String::as_str)
+            .unwrap_or("Front");
+        let back = self
+            .field_names
+            .iter()
+            .skip(1)
+            .map(|name| format!("{{{{{name}}}}}"))
+            .collect::<Vec<_>>()
+            .join("<br>\n");
+        json!({
+            "id": model_id,
+            "name": self.config.model_name,
+            "type": 0,
+            "mod": now,
+            "usn": -1,
+            "sortf": 0,
+            "did": deck_id,
+            "tmpls": [{
+                "name": "Card 1",
+                "ord": 0,
+                "qfmt": format!("{{{{{front}}}}}"),
+                "afmt": format!("{{{{FrontSide}}}}\n\n<hr id=answer>\n\n{back}"),
+                "bqfmt": "",
+                "bafmt": "",
+                "did": null,
+                "bfont": "",
+                "bsize": 0,
+            }],
+            "flds": fields,
+            "css": DEFAULT_CSS,
+            "latexPre": LATEX_PRE,
+            "latexPost": "\\end{document}",
+            "latexsvg": false,
+            "req": [[0, "any", [0]]],
+            "tags": [],
+            "vers": [],
+        })
+    }
+
+    /// A stable guid, so re-importing an exported note updates it.
+    fn note_guid(&self, note: &ExportedNote) -> String {
+        let key = format!("{}\x1f{}", self.config.model_name, note.fields.join("\x1f"));
+        format!("yc{:016x}", fnv1a(key.as_bytes()))
+    }
+}
+
+fn media_bytes<'a>(filename: &str, media: &'a AnkiMedia) -> Result<&'a [u8], AnkiExportError> {
+    match media.source() {
+        MediaSource::Bytes(data) => Ok(data.as_slice()),
+        MediaSource::Url(url) => Err(AnkiExportError::RemoteMedia {
+            filename: filename.to_string(),
+            url: url.clone(),
+        }),
+    }
+}
+
+/// Quotes a field if it contains a tab, newline or quote, as Anki's csv reader expects.
+fn tsv_field(value: &str) -> String {
+    match value.contains(['\t', '\n', '\r', '"']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
+
+/// Anki's note checksum: the first 8 hex digits of the sha1 of the sort field.
+fn field_checksum(sort_field: &str) -> i64 {
+    let digest = Sha1::digest(sort_field.as_bytes());
+    i64::from(u32::from_be_bytes([
+        digest[0], digest[1], digest[2], digest[3],
+    ]))
+}
+
+fn deck_json(id: i64, name: &str, now: i64) -> serde_json::Value {
+    json!({
+        "id": id,
+        "name": name,
+        "mod": now,
+        "usn": -1,
+        "desc": "",
+        "dyn": 0,
+        "conf": 1,
+        "collapsed": false,
+        "browserCollapsed": false,
+        "extendNew": 0,
+        "extendRev": 0,
+        "newToday": [0, 0],
+        "revToday": [0, 0],
+        "lrnToday": [0, 0],
+        "timeToday": [0, 0],
+    })
+}
+
+/// Anki's default deck options.
+fn deck_config_json(now: i64) -> serde_json::Value {
+    json!({
+        "id": 1,
+        "name": "Default",
+        "mod": now,
+        "usn": -1,
+        "maxTaken": 60,
+        "autoplay": true,
+        "timer": 0,
+        "replayq": true,
+        "dyn": false,
+        "new": {
+            "delays": [1.0, 10.0],
+            "ints": [1, 4, 0],
+            "initialFactor": 2500,
+            "order": 1,
+            "perDay": 20,
+            "bury": false,
+        },
+        "rev": {
+            "perDay": 200,
+            "ease4": 1.3,
+            "ivlFct": 1.0,
+            "maxIvl": 36500,
+            "hardFactor": 1.2,
+            "bury": false,
+        },
+        "lapse": {
+            "delays": [10.0],
+            "mult": 0.0,
+            "minInt": 1,
+            "leechFails": 8,
+            "leechAction": 1,
+        },
+    })
+}
+
+const DEFAULT_CSS: &str = ".card {
+    font-family: arial;
+    font-size: 20px;
+    text-align: center;
+    color: black;
+    background-color: white;
+}
+";
+
+const LATEX_PRE: &str = "\\documentclass[12pt]{article}
+\\special{papersize=3in,5in}
+\\usepackage[utf8]{inputenc}
+\\usepackage{amssymb,amsmath}
+\\pagestyle{empty}
+\\setlength{\\parindent}{0in}
+\\begin{document}
+";
+
+const COLLECTION_SCHEMA: &str = "
+CREATE TABLE col (
+    id integer PRIMARY KEY, crt integer NOT NULL, mod integer NOT NULL,
+    scm integer NOT NULL, ver integer NOT NULL, dty integer NOT NULL, usn integer NOT NULL,
+    ls integer NOT NULL, conf text NOT NULL, models text NOT NULL, decks text NOT NULL,
+    dconf text NOT NULL, tags text NOT NULL
+);
+CREATE TABLE notes (
+    id integer PRIMARY KEY, guid text NOT NULL, mid integer NOT NULL, mod integer NOT NULL,
+    usn integer NOT NULL, tags text NOT NULL, flds text NOT NULL, sfld integer NOT NULL,
+    csum integer NOT NULL, flags integer NOT NULL, data text NOT NULL
+);
+CREATE TABLE cards (
+    id integer PRIMARY KEY, nid integer NOT NULL, did integer NOT NULL, ord integer NOT NULL,
+    mod integer NOT NULL, usn integer NOT NULL, type integer NOT NULL, queue integer NOT NULL,
+    due integer NOT NULL, ivl integer NOT NULL, factor integer NOT NULL, reps integer NOT NULL,
+    lapses integer NOT NULL, left integer NOT NULL, odue integer NOT NULL,
+    odid integer NOT NULL, flags integer NOT NULL, data text NOT NULL
+);
+CREATE TABLE revlog (
+    id integer PRIMARY KEY, cid integer NOT NULL, usn integer NOT NULL, ivl integer NOT NULL,
+    lastIvl integer NOT NULL, factor integer NOT NULL, time integer NOT NULL,
+    type integer NOT NULL
+);
+CREATE TABLE graves (usn integer NOT NULL, oid integer NOT NULL, type integer NOT NULL);
+CREATE INDEX ix_notes_usn ON notes (usn);
+CREATE INDEX ix_cards_usn ON cards (usn);
+CREATE INDEX ix_revlog_usn ON revlog (usn);
+CREATE INDEX ix_cards_nid ON cards (nid);
+CREATE INDEX ix_cards_sched ON cards (did, queue, due);
+CREATE INDEX ix_revlog_cid ON revlog (cid);
+CREATE INDEX ix_notes_csum ON notes (csum);
+";
+
+#[cfg(test)]
+mod export_tests {
+    use super::*;
+    use crate::{settings::core::AnkiTermFieldType, utils::test_utils::TEST_PATHS};
+    use std::io::Read;
+
+    fn export() -> NoteExport {
+        let config = NoteConfig {
+            model_name: "Japanese".to_string(),
+            deck_name: "Mining::Novels".to_string(),
+            tags: vec!["yomichan".to_string()],
+            field_mappings: vec![
+                AnkiTermFieldType::Term("Word".to_string()),
+                AnkiTermFieldType::Definition("Meaning".to_string()),
+            ],
+            field_templates: IndexMap::new(),
+            frequency_format: Default::default(),
+            glossary: Default::default(),
+        };
+        let mut export = NoteExport::new(config, vec!["Word".into(), "Notes".into()]);
+        export.push_note(
+            IndexMap::from([
+                ("Meaning".to_string(), "cat\t(animal)".to_string()),
+                ("Word".to_string(), "<b>猫</b>".to_string()),
+            ]),
+            vec!["yomichan".to_string()],
+        );
+        export
+    }
+
+    #[test]
+    fn tsv() {
+        let export = export();
+        assert_eq!(export.field_names(), ["Word", "Notes", "Meaning"]);
+        assert_eq!(
+            export.to_tsv(),
+            "#separator:tab\n#html:true\n#notetype:Japanese\n#deck:Mining::Novels\n\
+             #tags column:4\n#columns:Word\tNotes\tMeaning\tTags\n\
+             <b>猫</b>\t\t\"cat\t(animal)\"\tyomichan\n"
+        );
+    }
+
+    #[test]
+    fn apkg_roundtrip() {
+        let dir = TEST_PATHS.tests_dir.join("export_tmp");
+        let _ = std::fs::create_dir_all(&dir);
+        scopeguard::defer! {
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+        let mut export = export();
+        let audio = AnkiMedia::from_bytes(vec![1, 2, 3], "mp3");
+        export.media.insert(audio.filename(), audio.clone());
+        let apkg = dir.join("mined.apkg");
+        export.write_apkg(&apkg).unwrap();
+
+        let mut zip = zip::ZipArchive::new(File::open(&apkg).unwrap()).unwrap();
+        let mut media = String::new();
+        zip.by_name("media")
+            .unwrap()
+            .read_to_string(&mut media)
+            .unwrap();
+        assert_eq!(media, json!({ "0": audio.filename() }).to_string());
+
+        let collection = dir.join("collection.anki2");
+        let mut bytes = vec![];
+        zip.by_name("collection.anki2")
+            .unwrap()
+            .read_to_end(&mut bytes)
+            .unwrap();
+        std::fs::write(&collection, bytes).unwrap();
+        let conn = Connection::open(&collection).unwrap();
+        let (flds, sfld, tags): (String, String, String) = conn
+            .query_row("SELECT flds, sfld, tags FROM notes", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap();
+        assert_eq!(flds, "<b>猫</b>\x1f\x1fcat\t(animal)");
+        assert_eq!(sfld, "猫");
+        assert_eq!(tags, " yomichan ");
+        let cards: i64 = conn
+            .query_row("SELECT count(*) FROM cards", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(cards, 1);
+    }
+}
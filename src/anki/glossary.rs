@@ -0,0 +1,497 @@
+//! Html glossaries for Anki definition fields.
+//!
+//! Each glossary is written with the html the importer rendered from the dictionary's
+//! structured content (lists, tables, links, images, ruby), falling back to its plain text.
+//! Every definition is wrapped in an element with a `data-dictionary` attribute,
+//! and each dictionary's css is scoped to it, so dictionary styles can't leak into
+//! other definitions or the rest of the card.
+//!
+//! Which definitions are rendered is picked by dictionary, see
+//! [GlossaryRenderer::with_dictionaries] and [GlossaryRenderer::with_max_senses].
+
+use better_default::Default;
+use indexmap::IndexMap;
+
+use crate::{models::dictionary::TermDefinition, settings::core::AnkiGlossaryFormat};
+
+/// The class of the element every html glossary is wrapped in.
+pub const GLOSSARY_CLASS: &str = "yomichan-glossary";
+
+/// Renders definitions into a note field, see [AnkiGlossaryFormat].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GlossaryRenderer {
+    format: AnkiGlossaryFormat,
+    /// Dictionary names or aliases whose definitions are rendered, in this order;
+    /// every definition in the entry's order if empty.
+    dictionaries: Vec<String>,
+    /// The maximum number of definitions rendered.
+    max_senses: Option<usize>,
+    /// Whether each definition is labeled with its dictionary's alias.
+    #[default(true)]
+    dictionary_names: bool,
+    /// Whether each definition is labeled with its tags.
+    #[default(true)]
+    tags: bool,
+    /// Scoped css by dictionary name.
+    styles: IndexMap<String, String>,
+}
+
+impl GlossaryRenderer {
+    pub fn new(format: AnkiGlossaryFormat) -> Self {
+        Self {
+            format,
+            ..Default::default()
+        }
+    }
+
+    /// Only renders the definitions of `dictionaries` (names or aliases), in this order.
+    ///
+    /// Every definition is rendered in the entry's order if `dictionaries` is empty.
+    pub fn with_dictionaries(mut self, dictionaries: Vec<String>) -> Self {
+        self.dictionaries = dictionaries;
+        self
+    }
+
+    /// Renders at most `max_senses` definitions, every definition if `None`.
+    pub fn with_max_senses(mut self, max_senses: Option<usize>) -> Self {
+        self.max_senses = max_senses;
+        self
+    }
+
+    /// Whether each definition is labeled with its dictionary's alias and its tags.
+    pub fn with_labels(mut self, dictionary_names: bool, tags: bool) -> Self {
+        self.dictionary_names = dictionary_names;
+        self.tags = tags;
+        self
+    }
+
+    /// Adds `css` for the definitions of `dictionary`, scoped with [scope_css].
+    ///
+    /// Css added for the same dictionary later is appended,
+    /// so a user's custom styles can override the dictionary's `styles.css`.
+    pub fn with_dictionary_styles(mut self, dictionary: &str, css: &str) -> Self {
+        let scoped = scope_css(css, &dictionary_selector(dictionary));
+        if !scoped.is_empty() {
+            self.styles
+                .entry(dictionary.to_string())
+                .or_default()
+                .push_str(&scoped);
+        }
+        self
+    }
+
+    pub fn format(&self) -> AnkiGlossaryFormat {
+        self.format
+    }
+
+    /// Renders the selected `definitions` as one field value.
+    pub fn render<'a>(&self, definitions: impl IntoIterator<Item = &'a TermDefinition>) -> String {
+        self.render_selected(self.select(definitions, None))
+    }
+
+    /// Renders the first of the selected `definitions`.
+    pub fn render_first<'a>(
+        &self,
+        definitions: impl IntoIterator<Item = &'a TermDefinition>,
+    ) -> String {
+        self.render_selected(self.select(definitions, None).into_iter().take(1).collect())
+    }
+
+    /// Renders the definitions of `dictionary`, given as a name, alias or [dictionary_slug],
+    /// regardless of [Self::with_dictionaries].
+    pub fn render_dictionary<'a>(
+        &self,
+        definitions: impl IntoIterator<Item = &'a TermDefinition>,
+        dictionary: &str,
+    ) -> String {
+        self.render_selected(self.select(definitions, Some(dictionary)))
+    }
+
+    /// The definitions of `only`, or of the selected dictionaries, up to `max_senses`.
+    fn select<'a>(
+        &self,
+        definitions: impl IntoIterator<Item = &'a TermDefinition>,
+        only: Option<&str>,
+    ) -> Vec<&'a TermDefinition> {
+        let definitions = definitions.into_iter();
+        let mut selected: Vec<&TermDefinition> = match only {
+            Some(dictionary) => definitions
+                .filter(|def| is_from_dictionary(def, dictionary))
+                .collect(),
+            None if self.dictionaries.is_empty() => definitions.collect(),
+            None => {
+                let definitions: Vec<&TermDefinition> = definitions.collect();
+                let mut selected: Vec<&TermDefinition> = vec![];
+                for dictionary in &self.dictionaries {
+                    for def in &definitions {
+                        // a dictionary can be listed by both its name and alias
+                        let listed = selected.iter().any(|s| std::ptr::eq(*s, *def));
+                        if !listed && is_from_dictionary(def, dictionary) {
+                            selected.push(def);
+                        }
+                    }
+                }
+                selected
+            }
+        };
+        if let Some(max) = self.max_senses {
+            selected.truncate(max);
+        }
+        selected
+    }
+
+    fn render_selected(&self, definitions: Vec<&TermDefinition>) -> String {
+        if definitions.is_empty() {
+            return String::new();
+        }
+        if self.format == AnkiGlossaryFormat::PlainText {
+            return definitions
+                .iter()
+                .flat_map(|def| &def.entries)
+                .map(|g| g.plain_text.clone())
+                .collect::<Vec<_>>()
+                .join("<br>");
+        }
+
+        let mut html = String::new();
+        let mut styled: Vec<&str> = vec![];
+        for def in &definitions {
+            if let Some(css) = self.styles.get(&def.dictionary) {
+                if !styled.contains(&def.dictionary.as_str()) {
+                    styled.push(&def.dictionary);
+                    html.push_str(css);
+                }
+            }
+        }
+        if !html.is_empty() {
+            html = format!("<style>{html}</style>");
+        }
+
+        let compact = self.format == AnkiGlossaryFormat::Compact;
+        let class = match compact {
+            true => format!("{GLOSSARY_CLASS} compact"),
+            false => GLOSSARY_CLASS.to_string(),
+        };
+        html.push_str(&format!(
+            "<div class=\"{class}\" style=\"text-align: left;\">"
+        ));
+        // a single definition isn't numbered
+        let (list, item) = match definitions.len() {
+            1 => (None, "div"),
+            _ => (Some("ol"), "li"),
+        };
+        if let Some(list) = list {
+            html.push_str(&format!("<{list}>"));
+        }
+        for def in definitions {
+            html.push_str(&format!(
+                "<{item} data-dictionary=\"{}\">",
+                escape_html(&def.dictionary)
+            ));
+            html.push_str(&self.definition_html(def, compact));
+            html.push_str(&format!("</{item}>"));
+        }
+        if let Some(list) = list {
+            html.push_str(&format!("</{list}>"));
+        }
+        html.push_str("</div>");
+        html
+    }
+
+    /// `(tags, dictionary) glossaries` of one definition.
+    fn definition_html(&self, def: &TermDefinition, compact: bool) -> String {
+        let mut labels: Vec<String> = match self.tags {
+            true => def
+                .tags
+                .iter()
+                .filter(|tag| !tag.redundant)
+                .map(|tag| escape_html(&tag.name))
+                .collect(),
+            false => vec![],
+        };
+        if self.dictionary_names {
+            labels.push(escape_html(&def.dictionary_alias));
+        }
+        let mut html = match labels.is_empty() {
+            true => String::new(),
+            false => format!("<i>({})</i> ", labels.join(", ")),
+        };
+
+        let glossaries: Vec<String> = def
+            .entries
+            .iter()
+            .map(
+                |g| match g.html.as_deref().filter(|html| !html.trim().is_empty()) {
+                    Some(html) => html.to_string(),
+                    None => escape_html(&g.plain_text).replace('\n', "<br>"),
+                },
+            )
+            .collect();
+        match (compact, glossaries.len()) {
+            (_, 0) => {}
+            (true, _) => {
+                let items: Vec<String> = glossaries
+                    .iter()
+                    .map(|g| format!("<span class=\"glossary-item\">{g}</span>"))
+                    .collect();
+                html.push_str(&items.join(" | "));
+            }
+            (false, 1) => html.push_str(&glossaries[0]),
+            (false, _) => {
+                html.push_str("<ul>");
+                for glossary in glossaries {
+                    html.push_str(&format!("<li>{glossary}</li>"));
+                }
+                html.push_str("</ul>");
+            }
+        }
+        html
+    }
+}
+
+/// `dictionary` in lowercase with whitespace replaced by `-`,
+/// as written in `{single-glossary-<dictionary>}` markers.
+///
+/// ```
+/// # use yomichan_rs::anki::glossary::dictionary_slug;
+/// assert_eq!(dictionary_slug("JMdict English"), "jmdict-english");
+/// ```
+pub fn dictionary_slug(dictionary: &str) -> String {
+    dictionary
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase()
+}
+
+/// Whether `def` is from `dictionary`, given as a name, alias or [dictionary_slug].
+fn is_from_dictionary(def: &TermDefinition, dictionary: &str) -> bool {
+    [&def.dictionary, &def.dictionary_alias]
+        .into_iter()
+        .any(|name| name == dictionary || dictionary_slug(name) == dictionary)
+}
+
+/// The selector of the definitions of `dictionary` inside a glossary.
+fn dictionary_selector(dictionary: &str) -> String {
+    let name = dictionary.replace('\\', "\\\\").replace('"', "\\\"");
+    format!(".{GLOSSARY_CLASS} [data-dictionary=\"{name}\"]")
+}
+
+/// Prefixes every selector of `css` with `scope`, so it only applies inside of it.
+///
+/// `:root`, `html` and `body` become `scope` itself. Rules inside `@media`, `@supports`,
+/// `@container` and `@layer` blocks are scoped as well; other at-rules (e.g. `@font-face`,
+/// `@keyframes`) are kept as they are, and comments are dropped.
+pub fn scope_css(css: &str, scope: &str) -> String {
+    let css = strip_css_comments(css);
+    let mut out = String::new();
+    let mut rest = css.as_str();
+    while let Some(open) = rest.find(['{', ';']) {
+        let prelude = rest[..open].trim();
+        if rest.as_bytes()[open] == b';' {
+            // statement at-rules like `@import` and stray semicolons
+            if prelude.starts_with('@') {
+                out.push_str(&format!("{prelude};"));
+            }
+            rest = &rest[open + 1..];
+            continue;
+        }
+        let close = matching_brace(rest, open).unwrap_or(rest.len());
+        let body = &rest[open + 1..close.min(rest.len())];
+        rest = rest.get(close + 1..).unwrap_or_default();
+
+        if let Some(at_rule) = prelude.strip_prefix('@') {
+            let name = at_rule.split_whitespace().next().unwrap_or_default();
+            if matches!(name, "media" | "supports" | "container" | "layer") {
+                out.push_str(&format!("{prelude}{{{}}}", scope_css(body, scope)));
+            } else {
+                out.push_str(&format!("{prelude}{{{}}}", body.trim()));
+            }
+            continue;
+        }
+        if prelude.is_empty() {
+            continue;
+        }
+        let selectors: Vec<String> = split_selectors(prelude)
+            .into_iter()
+            .map(|selector| scope_selector(selector, scope))
+            .collect();
+        out.push_str(&format!("{}{{{}}}", selectors.join(","), body.trim()));
+    }
+    out
+}
+
+fn scope_selector(selector: &str, scope: &str) -> String {
+    for root in [":root", "html", "body"] {
+        if let Some(rest) = selector.strip_prefix(root) {
+            // `html` but not `htmlx` or `html-foo`
+            if rest.is_empty() || rest.starts_with([' ', '>', '+', '~', '.', '#', ':', '[']) {
+                return format!("{scope}{rest}");
+            }
+        }
+    }
+    format!("{scope} {selector}")
+}
+
+/// Splits a selector list at the commas outside of parentheses and brackets.
+fn split_selectors(prelude: &str) -> Vec<&str> {
+    let mut selectors = vec![];
+    let mut depth = 0i32;
+    let mut start = 0;
+    for (i, c) in prelude.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                selectors.push(prelude[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    selectors.push(prelude[start..].trim());
+    selectors.retain(|selector| !selector.is_empty());
+    selectors
+}
+
+/// The index of the `}` closing the `{` at `open`.
+fn matching_brace(css: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in css[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn strip_css_comments(css: &str) -> String {
+    let mut out = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        out.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    out.push_str(rest);
+    out
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod glossary_tests {
+    use super::*;
+
+    #[test]
+    fn scopes_css() {
+        let css =
+            "/* jmdict */ :root { --x: 1 } .gloss-sc-ul, li[data-a=\"b,c\"] { color: red; }\n\
+                   @media (max-width: 600px) { a:hover { color: blue } }\n\
+                   @font-face { font-family: x; }";
+        let scope = dictionary_selector("JMdict");
+        assert_eq!(
+            scope_css(css, &scope),
+            format!(
+                "{scope}{{--x: 1}}{scope} .gloss-sc-ul,{scope} li[data-a=\"b,c\"]{{color: red;}}\
+                 @media (max-width: 600px){{{scope} a:hover{{color: blue}}}}\
+                 @font-face{{font-family: x;}}"
+            )
+        );
+        assert_eq!(scope, ".yomichan-glossary [data-dictionary=\"JMdict\"]");
+        assert_eq!(scope_selector("htmlx", "s"), "s htmlx");
+        assert_eq!(scope_selector("body > p", "s"), "s > p");
+    }
+
+    fn definition(dictionary: &str, alias: &str) -> TermDefinition {
+        TermDefinition {
+            id: String::new(),
+            index: 0,
+            headword_indices: vec![0],
+            dictionary: dictionary.to_string(),
+            dictionary_index: 0,
+            dictionary_alias: alias.to_string(),
+            score: 0,
+            frequency_order: 0,
+            sequences: vec![],
+            is_primary: true,
+            tags: vec![],
+            entries: vec![],
+        }
+    }
+
+    #[test]
+    fn selects_dictionaries() {
+        let definitions = [
+            definition("JMdict English", "JMdict"),
+            definition("大辞林", "大辞林"),
+            definition("JMdict English", "JMdict"),
+            definition("新明解", "SMK8"),
+        ];
+        let dictionaries = |selected: Vec<&TermDefinition>| -> Vec<String> {
+            selected.iter().map(|def| def.dictionary.clone()).collect()
+        };
+
+        let all = GlossaryRenderer::default();
+        assert_eq!(all.select(&definitions, None).len(), 4);
+
+        let renderer = GlossaryRenderer::default().with_dictionaries(vec![
+            "SMK8".to_string(),
+            "JMdict English".to_string(),
+            "JMdict".to_string(),
+        ]);
+        assert_eq!(
+            dictionaries(renderer.select(&definitions, None)),
+            ["新明解", "JMdict English", "JMdict English"]
+        );
+        let renderer = renderer.with_max_senses(Some(2));
+        assert_eq!(
+            dictionaries(renderer.select(&definitions, None)),
+            ["新明解", "JMdict English"]
+        );
+        assert_eq!(
+            dictionaries(renderer.select(&definitions, Some("jmdict-english"))),
+            ["JMdict English", "JMdict English"]
+        );
+        assert_eq!(
+            dictionaries(renderer.select(&definitions, Some("大辞林"))),
+            ["大辞林"]
+        );
+
+        let labeled = GlossaryRenderer::default().render_first(&definitions);
+        assert!(labeled.contains("<i>(JMdict)</i>"));
+        let unlabeled = GlossaryRenderer::default()
+            .with_labels(false, false)
+            .render_first(&definitions);
+        assert!(!unlabeled.contains("<i>"));
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            escape_html("<a href=\"x\">&</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;"
+        );
+    }
+}
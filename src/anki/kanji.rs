@@ -0,0 +1,391 @@
+//! Kanji notes, made from a [KanjiDictionaryEntry],
+//! see [Yomichan::find_kanji](crate::Yomichan::find_kanji).
+//!
+//! Kanji notes have their own model, deck and field mapping ([AnkiKanjiFields]),
+//! so they can go to a different note type than term notes.
+//! Duplicates are checked like term notes, with the same duplicate settings.
+
+#[cfg(feature = "anki")]
+use indexmap::IndexMap;
+
+#[cfg(feature = "anki")]
+use crate::{
+    anki::{
+        connect::{AnkiConnect, NewNote},
+        duplicate::{find_duplicates, find_notes_with_field, DuplicateOptions},
+    },
+    settings::core::AnkiDuplicateBehavior,
+};
+use crate::{
+    anki::{
+        core::{DisplayAnki, DisplayAnkiError},
+        glossary::escape_html,
+    },
+    models::dictionary::KanjiDictionaryEntry,
+    settings::core::{AnkiKanjiFieldType, AnkiKanjiFields, KanjiFieldIndex},
+};
+
+impl AnkiKanjiFieldType {
+    /// The value of this field for `entry`.
+    pub fn render(&self, entry: &KanjiDictionaryEntry) -> String {
+        match self {
+            AnkiKanjiFieldType::Character(_) => entry.character.clone(),
+            AnkiKanjiFieldType::Onyomi(_) => entry.onyomi.join(", "),
+            AnkiKanjiFieldType::Kunyomi(_) => entry.kunyomi.join(", "),
+            AnkiKanjiFieldType::Meanings(_) => match entry.definitions.as_slice() {
+                [] => String::new(),
+                [meaning] => escape_html(meaning),
+                meanings => {
+                    let items: Vec<String> = meanings
+                        .iter()
+                        .map(|meaning| format!("<li>{}</li>", escape_html(meaning)))
+                        .collect();
+                    format!("<ol>{}</ol>", items.concat())
+                }
+            },
+            AnkiKanjiFieldType::StrokeCount(_) => entry.stroke_count().unwrap_or_default(),
+            AnkiKanjiFieldType::Frequency(_) => entry
+                .frequencies
+                .iter()
+                .map(|f| {
+                    let value = f
+                        .display_value
+                        .clone()
+                        .unwrap_or_else(|| f.frequency.to_string());
+                    format!("{}: {value}", f.dictionary)
+                })
+                .collect::<Vec<_>>()
+                .join("<br>"),
+        }
+    }
+}
+
+/// The current profile's kanji note settings, see [DisplayAnki::kanji_note_config].
+#[cfg(feature = "anki")]
+#[derive(Clone, Debug)]
+pub(crate) struct KanjiNoteConfig {
+    pub model_name: String,
+    pub deck_name: String,
+    pub tags: Vec<String>,
+    pub field_mappings: Vec<AnkiKanjiFieldType>,
+}
+
+#[cfg(feature = "anki")]
+impl KanjiNoteConfig {
+    /// The value of every mapped field, by field name.
+    pub fn render_fields(&self, entry: &KanjiDictionaryEntry) -> IndexMap<String, String> {
+        self.field_mappings
+            .iter()
+            .map(|mapping| (mapping.field_name().to_string(), mapping.render(entry)))
+            .collect()
+    }
+
+    /// A note with the fields rendered from `entry`,
+    /// added in the duplicate scope of `options`.
+    pub fn new_note(&self, entry: &KanjiDictionaryEntry, options: DuplicateOptions) -> NewNote {
+        NewNote {
+            deck_name: self.deck_name.clone(),
+            model_name: self.model_name.clone(),
+            fields: self.render_fields(entry),
+            tags: self.tags.clone(),
+            options: options.note_options(&self.deck_name),
+        }
+    }
+
+    /// The name of the field `Character` is mapped to.
+    pub fn character_field(&self) -> Option<&str> {
+        self.field_mappings
+            .iter()
+            .find_map(|mapping| match mapping {
+                AnkiKanjiFieldType::Character(name) => Some(name.as_str()),
+                _ => None,
+            })
+    }
+}
+
+impl DisplayAnki {
+    #[cfg(feature = "anki")]
+    /// Builds a kanji note from `entry` and adds it to Anki, see [DisplayAnki::add_entry].
+    ///
+    /// Duplicates are handled by the profile's `duplicate_behavior`:
+    /// [DisplayAnkiError::DuplicateNote] for `Prevent`, and `Overwrite` updates the first
+    /// duplicate (except the profile's `overwrite_keep_fields`) and returns its id.
+    pub fn add_kanji_entry(
+        &self,
+        entry: &KanjiDictionaryEntry,
+    ) -> Result<Vec<usize>, DisplayAnkiError> {
+        let config = self.kanji_note_config()?;
+        let connect = self.connect()?;
+        let options = self.duplicate_options()?;
+        let note = config.new_note(entry, options);
+
+        if let Some(duplicates) = find_duplicates(&connect, options, &note)? {
+            match (options.behavior, duplicates.first()) {
+                (AnkiDuplicateBehavior::Allow, _) => {}
+                (AnkiDuplicateBehavior::Overwrite, Some(&note_id)) => {
+                    let keep_fields =
+                        self.with_anki_options(|opts| opts.overwrite_keep_fields().clone())?;
+                    let mut fields = note.fields;
+                    fields.retain(|field_name, value| {
+                        !keep_fields.contains(field_name) && !value.is_empty()
+                    });
+                    if !fields.is_empty() {
+                        connect.update_note_fields(note_id, &fields)?;
+                    }
+                    return Ok(vec![note_id as usize]);
+                }
+                _ => {
+                    return Err(DisplayAnkiError::DuplicateNote {
+                        term: entry.character.clone(),
+                        note_ids: duplicates,
+                    })
+                }
+            }
+        }
+
+        let note_id = connect.add_note(&note)?;
+        Ok(vec![note_id as usize])
+    }
+
+    #[cfg(feature = "anki")]
+    /// The ids of the kanji notes in the profile's duplicate scope
+    /// whose `Character` field is `entry`'s character.
+    ///
+    /// Empty if no field is mapped to `Character`.
+    pub fn find_kanji_entry_notes(
+        &self,
+        entry: &KanjiDictionaryEntry,
+    ) -> Result<Vec<u64>, DisplayAnkiError> {
+        let config = self.kanji_note_config()?;
+        kanji_notes(&self.connect()?, &config, self.duplicate_options()?, entry)
+    }
+
+    #[cfg(feature = "anki")]
+    /// Reads the current profile's kanji note settings.
+    pub(crate) fn kanji_note_config(&self) -> Result<KanjiNoteConfig, DisplayAnkiError> {
+        let profile = self.options().read().get_current_profile()?;
+        let profile_guard = profile.read();
+        let anki_opts = profile_guard.anki_options();
+        let kanji_fields = anki_opts
+            .kanji_fields()
+            .as_ref()
+            .ok_or(DisplayAnkiError::AnkiFieldsUninitialized)?;
+
+        let global_opts = self.options().read();
+        let global_anki_opts = global_opts.anki().read();
+        let (model_name, _) =
+            global_anki_opts.get_selected_model(*kanji_fields.selected_model())?;
+        let (deck_name, _) = global_anki_opts.get_selected_deck(*kanji_fields.selected_deck())?;
+
+        Ok(KanjiNoteConfig {
+            model_name: model_name.to_string(),
+            deck_name: deck_name.to_string(),
+            tags: anki_opts.tags().clone(),
+            field_mappings: kanji_fields.fields().clone(),
+        })
+    }
+
+    /// Same as [DisplayAnki::configure_note_creation], for kanji notes.
+    pub fn configure_kanji_note_creation(
+        &self,
+        model_name: &str,
+        deck_name: &str,
+        field_mappings: &[KanjiFieldIndex],
+    ) -> Result<(), DisplayAnkiError> {
+        self.update_all_anki_maps()?;
+        let (model_idx, model_details, deck_idx) = {
+            let global_opts_guard = self.options().read();
+            let global_anki_opts = global_opts_guard.anki().read();
+            let (model_idx, _, details) = global_anki_opts
+                .find_model_by_name(model_name)
+                .map_err(|_| DisplayAnkiError::ModelNotFound(model_name.to_string()))?;
+            let (deck_idx, _, _) = global_anki_opts
+                .find_deck_by_name(deck_name)
+                .map_err(|_| DisplayAnkiError::DeckNotFound(deck_name.to_string()))?;
+            (model_idx, details.clone(), deck_idx)
+        };
+        let fields = AnkiKanjiFieldType::from_field_indices(field_mappings, &model_details)?;
+        self.set_kanji_fields(fields, model_idx, deck_idx)
+    }
+
+    /// Automatically configures kanji note creation, like
+    /// [DisplayAnki::configure_note_creation_auto]:
+    /// 1. Fetches the latest model and deck information from Anki.
+    /// 2. Selects the first model and deck with "kanji" in their name,
+    ///    or the first available ones.
+    /// 3. Maps the model's fields in order to `Character`, `Meanings`, `Onyomi`, `Kunyomi`,
+    ///    `StrokeCount` and `Frequency`, stopping when the model runs out of fields.
+    ///
+    /// # Errors
+    /// Returns an error if no models or decks are found in the user's Anki collection.
+    pub fn configure_kanji_note_creation_auto(&self) -> Result<(), DisplayAnkiError> {
+        self.update_all_anki_maps()?;
+        let (model_idx, model_details, deck_idx) = {
+            let global_opts_guard = self.options().read();
+            let global_anki_opts = global_opts_guard.anki().read();
+            let model_idx = kanji_index(global_anki_opts.note_models_map().keys());
+            let (_, details) = global_anki_opts
+                .get_selected_model(model_idx)
+                .map_err(|_| DisplayAnkiError::NoModelsFound)?;
+            let deck_idx = kanji_index(global_anki_opts.decks_map().keys());
+            global_anki_opts
+                .get_selected_deck(deck_idx)
+                .map_err(|_| DisplayAnkiError::NoDecksFound)?;
+            (model_idx, details.clone(), deck_idx)
+        };
+        let field_mappings = auto_kanji_field_mappings(model_details.fields.len());
+        let fields = AnkiKanjiFieldType::from_field_indices(&field_mappings, &model_details)?;
+        self.set_kanji_fields(fields, model_idx, deck_idx)
+    }
+
+    fn set_kanji_fields(
+        &self,
+        fields: Vec<AnkiKanjiFieldType>,
+        model_idx: usize,
+        deck_idx: usize,
+    ) -> Result<(), DisplayAnkiError> {
+        let mut kanji_fields = AnkiKanjiFields::default();
+        kanji_fields
+            .set_fields(fields)
+            .set_selected_model(model_idx)
+            .set_selected_deck(deck_idx);
+        let profile_ptr = self.options().read().get_current_profile()?;
+        let mut profile_guard = profile_ptr.write();
+        profile_guard
+            .anki_options_mut()
+            .set_kanji_fields(Some(kanji_fields));
+        Ok(())
+    }
+}
+
+#[cfg(feature = "anki")]
+fn kanji_notes(
+    connect: &AnkiConnect,
+    config: &KanjiNoteConfig,
+    options: DuplicateOptions,
+    entry: &KanjiDictionaryEntry,
+) -> Result<Vec<u64>, DisplayAnkiError> {
+    let Some(character_field) = config.character_field() else {
+        return Ok(vec![]);
+    };
+    find_notes_with_field(
+        connect,
+        options,
+        character_field,
+        &entry.character,
+        &config.model_name,
+        &config.deck_name,
+    )
+}
+
+/// The index of the first name containing "kanji", or 0.
+fn kanji_index<'a>(names: impl Iterator<Item = &'a String>) -> usize {
+    names
+        .position(|name| name.to_lowercase().contains("kanji"))
+        .unwrap_or(0)
+}
+
+/// The field mappings of [DisplayAnki::configure_kanji_note_creation_auto]
+/// for a model with `fields_len` fields.
+fn auto_kanji_field_mappings(fields_len: usize) -> Vec<KanjiFieldIndex> {
+    let constructors: [fn(usize) -> KanjiFieldIndex; 6] = [
+        KanjiFieldIndex::Character,
+        KanjiFieldIndex::Meanings,
+        KanjiFieldIndex::Onyomi,
+        KanjiFieldIndex::Kunyomi,
+        KanjiFieldIndex::StrokeCount,
+        KanjiFieldIndex::Frequency,
+    ];
+    constructors
+        .into_iter()
+        .take(fields_len)
+        .enumerate()
+        .map(|(i, constructor)| constructor(i))
+        .collect()
+}
+
+#[cfg(test)]
+mod kanji_tests {
+    use super::*;
+    use crate::models::dictionary::{DictionaryEntryType, KanjiStat, NumOrStr};
+    use indexmap::IndexMap;
+
+    fn cat_entry() -> KanjiDictionaryEntry {
+        KanjiDictionaryEntry {
+            entry_type: DictionaryEntryType::Kanji,
+            character: "猫".to_string(),
+            dictionary: "KANJIDIC".to_string(),
+            onyomi: vec!["ビョウ".to_string()],
+            kunyomi: vec!["ねこ".to_string()],
+            tags: vec![],
+            stats: IndexMap::from([(
+                "misc".to_string(),
+                vec![KanjiStat {
+                    name: "strokes".to_string(),
+                    category: "misc".to_string(),
+                    content: String::new(),
+                    order: 0,
+                    score: 0,
+                    dictionary: "KANJIDIC".to_string(),
+                    value: NumOrStr::Num(11),
+                }],
+            )]),
+            definitions: vec!["cat".to_string(), "<feline>".to_string()],
+            frequencies: vec![],
+        }
+    }
+
+    #[test]
+    fn render() {
+        let entry = cat_entry();
+        let render = |mapping: AnkiKanjiFieldType| mapping.render(&entry);
+        assert_eq!(render(AnkiKanjiFieldType::Character("Kanji".into())), "猫");
+        assert_eq!(
+            render(AnkiKanjiFieldType::Meanings("Meaning".into())),
+            "<ol><li>cat</li><li>&lt;feline&gt;</li></ol>"
+        );
+        assert_eq!(render(AnkiKanjiFieldType::Onyomi("On".into())), "ビョウ");
+        assert_eq!(
+            render(AnkiKanjiFieldType::StrokeCount("Strokes".into())),
+            "11"
+        );
+        assert_eq!(
+            render(AnkiKanjiFieldType::Frequency("Frequency".into())),
+            ""
+        );
+    }
+
+    #[cfg(feature = "anki")]
+    #[test]
+    fn render_fields() {
+        let config = KanjiNoteConfig {
+            model_name: "Kanji".to_string(),
+            deck_name: "Kanji".to_string(),
+            tags: vec![],
+            field_mappings: vec![
+                AnkiKanjiFieldType::Character("Kanji".to_string()),
+                AnkiKanjiFieldType::Meanings("Meaning".to_string()),
+            ],
+        };
+        let fields = config.render_fields(&cat_entry());
+        assert_eq!(fields.keys().collect::<Vec<_>>(), ["Kanji", "Meaning"]);
+        assert_eq!(fields["Kanji"], "猫");
+        assert_eq!(config.character_field(), Some("Kanji"));
+    }
+
+    #[test]
+    fn auto_selection() {
+        let names = ["Basic".to_string(), "Japanese Kanji".to_string()];
+        assert_eq!(kanji_index(names.iter()), 1);
+        assert_eq!(kanji_index(names[..1].iter()), 0);
+        assert!(matches!(
+            auto_kanji_field_mappings(3).as_slice(),
+            [
+                KanjiFieldIndex::Character(0),
+                KanjiFieldIndex::Meanings(1),
+                KanjiFieldIndex::Onyomi(2)
+            ]
+        ));
+        assert_eq!(auto_kanji_field_mappings(10).len(), 6);
+    }
+}
@@ -0,0 +1,188 @@
+//! Audio and images for Anki notes, stored with AnkiConnect's `storeMediaFile`.
+//!
+//! Media files are named after a hash of their content (or url),
+//! so storing the same media twice reuses the file instead of adding a copy.
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "anki")]
+use crate::anki::core::{DisplayAnki, DisplayAnkiError};
+
+/// Where the data of an [AnkiMedia] comes from.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MediaSource {
+    Bytes(Vec<u8>),
+    /// Downloaded by Anki.
+    Url(String),
+}
+
+/// A media file to store in Anki's collection.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnkiMedia {
+    source: MediaSource,
+    /// The file extension without the dot, e.g. `"mp3"`.
+    extension: String,
+}
+
+impl AnkiMedia {
+    pub fn from_bytes(data: Vec<u8>, extension: &str) -> Self {
+        Self {
+            source: MediaSource::Bytes(data),
+            extension: extension.trim_start_matches('.').to_string(),
+        }
+    }
+
+    /// Media that Anki downloads from `url`.
+    ///
+    /// The extension is taken from the url's path, see [AnkiMedia::with_extension]
+    /// for urls without one.
+    pub fn from_url(url: &str) -> Self {
+        Self {
+            source: MediaSource::Url(url.to_string()),
+            extension: url_extension(url).unwrap_or_default().to_string(),
+        }
+    }
+
+    pub fn with_extension(mut self, extension: &str) -> Self {
+        self.extension = extension.trim_start_matches('.').to_string();
+        self
+    }
+
+    pub fn source(&self) -> &MediaSource {
+        &self.source
+    }
+
+    pub fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    /// The name the file is stored under, e.g. `yomichan_5d1a6c5d1bd2f3ab.mp3`.
+    ///
+    /// The same bytes (or url) always get the same name.
+    pub fn filename(&self) -> String {
+        let hash = match &self.source {
+            MediaSource::Bytes(data) => fnv1a(data),
+            MediaSource::Url(url) => fnv1a(url.as_bytes()),
+        };
+        match self.extension.is_empty() {
+            true => format!("yomichan_{hash:016x}"),
+            false => format!("yomichan_{hash:016x}.{}", self.extension),
+        }
+    }
+}
+
+/// The extension of the last path segment of `url`, ignoring the query and fragment.
+fn url_extension(url: &str) -> Option<&str> {
+    let path = url.split(['?', '#']).next()?;
+    let (_, scheme_rest) = path.split_once("://").unwrap_or(("", path));
+    let (_, segment) = scheme_rest.rsplit_once('/')?;
+    let (_, extension) = segment.rsplit_once('.')?;
+    (!extension.is_empty()).then_some(extension)
+}
+
+/// 64 bit FNV-1a; stable across platforms and releases, unlike [std::hash::DefaultHasher].
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+    })
+}
+
+/// Media for the audio and image fields of a note.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NoteMedia {
+    pub term_audio: Option<AnkiMedia>,
+    pub sentence_audio: Option<AnkiMedia>,
+    pub image: Option<AnkiMedia>,
+}
+
+impl NoteMedia {
+    /// The filenames [DisplayAnki::store_note_media] stores the media under,
+    /// without storing anything.
+    pub fn filenames(&self) -> StoredMedia {
+        StoredMedia {
+            term_audio: self.term_audio.as_ref().map(AnkiMedia::filename),
+            sentence_audio: self.sentence_audio.as_ref().map(AnkiMedia::filename),
+            image: self.image.as_ref().map(AnkiMedia::filename),
+        }
+    }
+
+    /// Every media file, in field order.
+    pub fn files(&self) -> Vec<AnkiMedia> {
+        [&self.term_audio, &self.sentence_audio, &self.image]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect()
+    }
+}
+
+/// The filenames [NoteMedia] was stored under, see [DisplayAnki::store_note_media].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredMedia {
+    pub term_audio: Option<String>,
+    pub sentence_audio: Option<String>,
+    pub image: Option<String>,
+}
+
+#[cfg(feature = "anki")]
+impl DisplayAnki {
+    /// Stores `media` in Anki's collection and returns its filename.
+    pub fn store_media(&self, media: &AnkiMedia) -> Result<String, DisplayAnkiError> {
+        let filename = media.filename();
+        let stored = self
+            .connect()?
+            .store_media_file(&filename, media.source())?;
+        Ok(stored)
+    }
+
+    /// Stores every file of `media`.
+    pub fn store_note_media(&self, media: &NoteMedia) -> Result<StoredMedia, DisplayAnkiError> {
+        let store = |media: &Option<AnkiMedia>| -> Result<Option<String>, DisplayAnkiError> {
+            media
+                .as_ref()
+                .map(|media| self.store_media(media))
+                .transpose()
+        };
+        Ok(StoredMedia {
+            term_audio: store(&media.term_audio)?,
+            sentence_audio: store(&media.sentence_audio)?,
+            image: store(&media.image)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod media_tests {
+    use super::*;
+
+    #[test]
+    fn deterministic_filenames() {
+        let a = AnkiMedia::from_bytes(vec![1, 2, 3], ".mp3");
+        let b = AnkiMedia::from_bytes(vec![1, 2, 3], "mp3");
+        let c = AnkiMedia::from_bytes(vec![1, 2, 4], "mp3");
+        assert_eq!(a.filename(), b.filename());
+        assert_ne!(a.filename(), c.filename());
+        assert!(a.filename().starts_with("yomichan_") && a.filename().ends_with(".mp3"));
+        // known FNV-1a value
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn url_extensions() {
+        let media = AnkiMedia::from_url("https://example.com/audio/食べる.ogg?v=2#t");
+        assert_eq!(media.extension(), "ogg");
+        assert_eq!(
+            AnkiMedia::from_url("https://example.com/tts").extension(),
+            ""
+        );
+        assert_eq!(AnkiMedia::from_url("https://example.com").extension(), "");
+        assert_eq!(
+            AnkiMedia::from_url("https://example.com/tts")
+                .with_extension("mp3")
+                .extension(),
+            "mp3"
+        );
+    }
+}
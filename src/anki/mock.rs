@@ -0,0 +1,726 @@
+//! An in-memory stand-in for AnkiConnect, so the Anki integration can be tested without Anki.
+//!
+//! Available in tests and with the `anki-mock` feature.
+//! Implements the actions this crate sends: `version`, `deckNames`, `deckNamesAndIds`,
+//! `createDeck`, `modelNames`, `modelNamesAndIds`, `modelFieldNames`, `addNote`, `addNotes`,
+//! `canAddNotes`, `canAddNotesWithErrorDetail`, `findNotes`, `notesInfo`, `updateNoteFields`,
+//! `deleteNotes` and `storeMediaFile`. `findNotes` understands field, `note:`, `deck:`, `nid:` and `tag:`
+//! searches, quoting, negation and the `*`/`_` wildcards.
+//!
+//! ```rust,no_run
+//! # use yomichan_rs::anki::mock::MockAnkiConnect;
+//! let anki = MockAnkiConnect::start()?;
+//! anki.add_model("Japanese", &["Word", "Reading", "Meaning"]);
+//! // point the profile's `AnkiOptions::server` at it
+//! let server = anki.server();
+//! // ... add notes through yomichan_rs ...
+//! assert_eq!(anki.notes().len(), 1);
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use indexmap::IndexMap;
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::anki::{
+    connect::{AnkiConnect, NewNote},
+    media::MediaSource,
+};
+
+/// A note stored by [MockAnkiConnect].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MockNote {
+    pub id: u64,
+    pub deck_name: String,
+    pub model_name: String,
+    /// Every field of the model, in order.
+    pub fields: IndexMap<String, String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug)]
+struct MockState {
+    decks: IndexMap<String, u64>,
+    /// Field names by model name.
+    models: IndexMap<String, Vec<String>>,
+    notes: IndexMap<u64, MockNote>,
+    media: IndexMap<String, MediaSource>,
+    next_id: u64,
+}
+
+/// A local AnkiConnect server with in-memory decks, models, notes and media.
+///
+/// Starts with the `Default` deck and the `Basic` model (`Front`, `Back`),
+/// and stops when dropped.
+pub struct MockAnkiConnect {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MockAnkiConnect {
+    /// Starts the server on a free port of `127.0.0.1`.
+    pub fn start() -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(MockState {
+            decks: IndexMap::from([("Default".to_string(), 1)]),
+            models: IndexMap::from([(
+                "Basic".to_string(),
+                vec!["Front".to_string(), "Back".to_string()],
+            )]),
+            notes: IndexMap::new(),
+            media: IndexMap::new(),
+            next_id: 1_700_000_000_000,
+        }));
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let state = state.clone();
+            let shutdown = shutdown.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if shutdown.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        // a broken connection only affects its own request
+                        let _ = handle_connection(stream, &state);
+                    }
+                }
+            })
+        };
+        Ok(Self {
+            addr,
+            state,
+            shutdown,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// The value to set [AnkiOptions](crate::settings::core::AnkiOptions)'s `server` to.
+    pub fn server(&self) -> String {
+        self.addr.to_string()
+    }
+
+    /// An [AnkiConnect] pointed at the server.
+    pub fn connect(&self) -> AnkiConnect {
+        AnkiConnect::from_server(&self.server())
+    }
+
+    pub fn add_deck(&self, name: &str) -> &Self {
+        self.state.lock().create_deck(name);
+        self
+    }
+
+    /// Adds (or replaces) the model `name` with `fields`, in order.
+    pub fn add_model(&self, name: &str, fields: &[&str]) -> &Self {
+        let fields = fields.iter().map(|field| field.to_string()).collect();
+        self.state.lock().models.insert(name.to_string(), fields);
+        self
+    }
+
+    pub fn remove_model(&self, name: &str) -> &Self {
+        self.state.lock().models.shift_remove(name);
+        self
+    }
+
+    pub fn decks(&self) -> Vec<String> {
+        self.state.lock().decks.keys().cloned().collect()
+    }
+
+    /// Every stored note, oldest first.
+    pub fn notes(&self) -> Vec<MockNote> {
+        self.state.lock().notes.values().cloned().collect()
+    }
+
+    /// Every stored media file by filename.
+    pub fn media(&self) -> IndexMap<String, MediaSource> {
+        self.state.lock().media.clone()
+    }
+}
+
+impl Drop for MockAnkiConnect {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // wakes up the blocking `accept`
+        let _ = TcpStream::connect(self.addr);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn handle_connection(mut stream: TcpStream, state: &Mutex<MockState>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    let mut raw = Vec::new();
+    let mut buf = [0u8; 8192];
+    let body_start = loop {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        raw.extend_from_slice(&buf[..n]);
+        if let Some(split) = raw.windows(4).position(|w| w == b"\r\n\r\n") {
+            break split + 4;
+        }
+    };
+    let head = String::from_utf8_lossy(&raw[..body_start]).to_string();
+    let content_length = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    while raw.len() < body_start + content_length {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        raw.extend_from_slice(&buf[..n]);
+    }
+
+    let response = match serde_json::from_slice::<Request>(&raw[body_start..]) {
+        Ok(request) => match state.lock().handle(&request.action, request.params) {
+            Ok(result) => json!({ "result": result, "error": null }),
+            Err(error) => json!({ "result": null, "error": error }),
+        },
+        Err(e) => json!({ "result": null, "error": format!("malformed request: {e}") }),
+    };
+    let body = response.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+#[derive(Deserialize)]
+struct Request {
+    action: String,
+    #[serde(default)]
+    params: Value,
+}
+
+fn param<T: serde::de::DeserializeOwned>(params: &Value, name: &str) -> Result<T, String> {
+    serde_json::from_value(params.get(name).cloned().unwrap_or(Value::Null))
+        .map_err(|e| format!("invalid parameter `{name}`: {e}"))
+}
+
+impl MockState {
+    fn handle(&mut self, action: &str, params: Value) -> Result<Value, String> {
+        let result = match action {
+            "version" => json!(6),
+            "deckNames" => json!(self.decks.keys().collect::<Vec<_>>()),
+            "deckNamesAndIds" => json!(self.decks),
+            "createDeck" => json!(self.create_deck(&param::<String>(&params, "deck")?)),
+            "modelNames" => json!(self.models.keys().collect::<Vec<_>>()),
+            "modelNamesAndIds" => {
+                let ids: IndexMap<_, _> = self.models.keys().zip(1_600_000_000_000u64..).collect();
+                json!(ids)
+            }
+            "modelFieldNames" => {
+                let model_name: String = param(&params, "modelName")?;
+                match self.models.get(&model_name) {
+                    Some(fields) => json!(fields),
+                    None => return Err(format!("model was not found: {model_name}")),
+                }
+            }
+            "addNote" => json!(self.add_note(param(&params, "note")?)?),
+            "addNotes" => {
+                let notes: Vec<NewNote> = param(&params, "notes")?;
+                let ids: Vec<Option<u64>> = notes
+                    .into_iter()
+                    .map(|note| self.add_note(note).ok())
+                    .collect();
+                json!(ids)
+            }
+            "canAddNotes" => {
+                let notes: Vec<NewNote> = param(&params, "notes")?;
+                let can_add: Vec<bool> = notes
+                    .iter()
+                    .map(|note| self.check_new_note(note).is_ok())
+                    .collect();
+                json!(can_add)
+            }
+            "canAddNotesWithErrorDetail" => {
+                let notes: Vec<NewNote> = param(&params, "notes")?;
+                let details: Vec<Value> = notes
+                    .iter()
+                    .map(|note| match self.check_new_note(note) {
+                        Ok(()) => json!({ "canAdd": true }),
+                        Err(error) => json!({ "canAdd": false, "error": error }),
+                    })
+                    .collect();
+                json!(details)
+            }
+            "findNotes" => {
+                let query: String = param(&params, "query")?;
+                let terms = parse_query(&query);
+                let ids: Vec<u64> = self
+                    .notes
+                    .values()
+                    .filter(|note| terms.iter().all(|term| term.matches(note)))
+                    .map(|note| note.id)
+                    .collect();
+                json!(ids)
+            }
+            "notesInfo" => {
+                let ids: Vec<u64> = param(&params, "notes")?;
+                let infos: Vec<Value> = ids
+                    .iter()
+                    .map(|id| match self.notes.get(id) {
+                        Some(note) => note_info(note),
+                        None => json!({}),
+                    })
+                    .collect();
+                json!(infos)
+            }
+            "updateNoteFields" => {
+                #[derive(Deserialize)]
+                struct Update {
+                    id: u64,
+                    fields: IndexMap<String, String>,
+                }
+                let update: Update = param(&params, "note")?;
+                let note = self
+                    .notes
+                    .get_mut(&update.id)
+                    .ok_or_else(|| format!("Note was not found: {}", update.id))?;
+                for (field_name, value) in update.fields {
+                    match note.fields.get_mut(&field_name) {
+                        Some(field) => *field = value,
+                        None => return Err(format!("field not found: {field_name}")),
+                    }
+                }
+                Value::Null
+            }
+            "deleteNotes" => {
+                let ids: Vec<u64> = param(&params, "notes")?;
+                for id in ids {
+                    self.notes.shift_remove(&id);
+                }
+                Value::Null
+            }
+            "storeMediaFile" => {
+                let filename: String = param(&params, "filename")?;
+                let source = match (params.get("data"), params.get("url")) {
+                    (Some(Value::String(data)), _) => {
+                        let data = BASE64
+                            .decode(data)
+                            .map_err(|e| format!("invalid base64 `data`: {e}"))?;
+                        MediaSource::Bytes(data)
+                    }
+                    (_, Some(Value::String(url))) => MediaSource::Url(url.clone()),
+                    _ => return Err("storeMediaFile needs `data` or `url`".to_string()),
+                };
+                self.media.insert(filename.clone(), source);
+                json!(filename)
+            }
+            _ => return Err(format!("unsupported action: {action}")),
+        };
+        Ok(result)
+    }
+
+    /// Creates the deck and, like Anki, its missing parents.
+    fn create_deck(&mut self, name: &str) -> u64 {
+        if let Some(id) = self.decks.get(name) {
+            return *id;
+        }
+        if let Some((parent, _)) = name.rsplit_once("::") {
+            self.create_deck(parent);
+        }
+        let id = self.next_id();
+        self.decks.insert(name.to_string(), id);
+        id
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// The errors AnkiConnect returns for a note that can't be added.
+    fn check_new_note(&self, note: &NewNote) -> Result<(), String> {
+        let Some(model_fields) = self.models.get(&note.model_name) else {
+            return Err(format!("model was not found: {}", note.model_name));
+        };
+        if !self.decks.contains_key(&note.deck_name) {
+            return Err(format!("deck was not found: {}", note.deck_name));
+        }
+        if let Some(field_name) = note.fields.keys().find(|f| !model_fields.contains(f)) {
+            return Err(format!("field not found: {field_name}"));
+        }
+        // like Anki, only the first field is compared
+        let Some(first_field) = model_fields.first() else {
+            return Err(format!("model has no fields: {}", note.model_name));
+        };
+        let first_value = note.fields.get(first_field).map(String::as_str);
+        let first_value = first_value.unwrap_or_default().trim();
+        if first_value.is_empty() {
+            return Err("cannot create note because it is empty".to_string());
+        }
+        let scope = &note.options.duplicate_scope_options;
+        let deck = match note.options.duplicate_scope.as_deref() {
+            Some("deck") => Some(scope.deck_name.as_deref().unwrap_or(&note.deck_name)),
+            _ => None,
+        };
+        let in_deck = |deck_name: &str| match deck {
+            None => true,
+            Some(deck) => {
+                deck_name == deck
+                    || (scope.check_children
+                        && deck_name.starts_with(deck)
+                        && deck_name[deck.len()..].starts_with("::"))
+            }
+        };
+        let duplicate = self.notes.values().any(|existing| {
+            (scope.check_all_models || existing.model_name == note.model_name)
+                && in_deck(&existing.deck_name)
+                && existing.fields.values().next().map(|v| v.trim()) == Some(first_value)
+        });
+        if duplicate && !note.options.allow_duplicate {
+            return Err("cannot create note because it is a duplicate".to_string());
+        }
+        Ok(())
+    }
+
+    fn add_note(&mut self, note: NewNote) -> Result<u64, String> {
+        self.check_new_note(&note)?;
+        let id = self.next_id();
+        let fields = self.models[&note.model_name]
+            .iter()
+            .map(|field_name| {
+                let value = note.fields.get(field_name).cloned().unwrap_or_default();
+                (field_name.clone(), value)
+            })
+            .collect();
+        self.notes.insert(
+            id,
+            MockNote {
+                id,
+                deck_name: note.deck_name,
+                model_name: note.model_name,
+                fields,
+                tags: note.tags,
+            },
+        );
+        Ok(id)
+    }
+}
+
+fn note_info(note: &MockNote) -> Value {
+    let fields: IndexMap<_, _> = note
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(order, (name, value))| (name, json!({ "value": value, "order": order })))
+        .collect();
+    json!({
+        "noteId": note.id,
+        "modelName": note.model_name,
+        "tags": note.tags,
+        "fields": fields,
+        "cards": [],
+    })
+}
+
+/// One term of an Anki search.
+#[derive(Debug, PartialEq)]
+struct SearchTerm {
+    negated: bool,
+    /// Still escaped, see [glob_match].
+    text: String,
+}
+
+impl SearchTerm {
+    fn matches(&self, note: &MockNote) -> bool {
+        self.matches_unnegated(note) != self.negated
+    }
+
+    fn matches_unnegated(&self, note: &MockNote) -> bool {
+        let Some((name, value)) = split_unescaped(&self.text, ':') else {
+            // bare text is searched in every field
+            let pattern = format!("*{}*", self.text);
+            return note.fields.values().any(|v| glob_match(&pattern, v));
+        };
+        match name.to_lowercase().as_str() {
+            "note" => glob_match(value, &note.model_name),
+            "deck" => {
+                // includes subdecks
+                let parts: Vec<&str> = note.deck_name.split("::").collect();
+                (1..=parts.len()).any(|n| glob_match(value, &parts[..n].join("::")))
+            }
+            "tag" => note.tags.iter().any(|tag| glob_match(value, tag)),
+            "nid" => value.split(',').any(|id| id.trim().parse() == Ok(note.id)),
+            _ => note
+                .fields
+                .iter()
+                .any(|(field_name, v)| glob_match(name, field_name) && glob_match(value, v)),
+        }
+    }
+}
+
+/// Splits a search into terms, removing quotes and keeping escapes.
+fn parse_query(query: &str) -> Vec<SearchTerm> {
+    let mut terms = Vec::new();
+    let mut chars = query.chars();
+    let mut text = String::new();
+    let mut negated = false;
+    let mut quoted = false;
+    let mut push = |text: &mut String, negated: &mut bool| {
+        if !text.is_empty() {
+            terms.push(SearchTerm {
+                negated: *negated,
+                text: std::mem::take(text),
+            });
+        }
+        *negated = false;
+    };
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                text.push(c);
+                text.extend(chars.next());
+            }
+            '"' => quoted = !quoted,
+            '-' if text.is_empty() && !quoted && !negated => negated = true,
+            c if c.is_whitespace() && !quoted => push(&mut text, &mut negated),
+            c => text.push(c),
+        }
+    }
+    push(&mut text, &mut negated);
+    terms
+}
+
+/// Splits `text` at the first `sep` that isn't escaped.
+fn split_unescaped(text: &str, sep: char) -> Option<(&str, &str)> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            c if c == sep && !escaped => return Some((&text[..i], &text[i + c.len_utf8()..])),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// Anki's case insensitive search matching: `*` matches anything, `_` one character,
+/// and `\` escapes the next character.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Token {
+        Char(char),
+        Any,
+        One,
+    }
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '\\' => Token::Char(chars.next().unwrap_or('\\')),
+            '*' => Token::Any,
+            '_' => Token::One,
+            c => Token::Char(c),
+        });
+    }
+    let text: Vec<char> = text.chars().collect();
+
+    // matched[j]: whether the tokens so far match text[..j]
+    let mut matched = vec![false; text.len() + 1];
+    matched[0] = true;
+    for token in tokens {
+        let mut next = vec![false; text.len() + 1];
+        for j in 0..=text.len() {
+            next[j] = match token {
+                Token::Any => matched[j] || (j > 0 && next[j - 1]),
+                Token::One => j > 0 && matched[j - 1],
+                Token::Char(c) => {
+                    j > 0 && matched[j - 1] && text[j - 1].to_lowercase().eq(c.to_lowercase())
+                }
+            };
+        }
+        matched = next;
+    }
+    matched[text.len()]
+}
+
+#[cfg(test)]
+mod mock_tests {
+    use super::*;
+    use crate::anki::connect::{
+        AnkiConnectError, CanAddNote, DuplicateScopeOptions, NewNoteOptions,
+    };
+
+    fn fields(pairs: &[(&str, &str)]) -> IndexMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn notes_roundtrip() {
+        let anki = MockAnkiConnect::start().unwrap();
+        anki.add_model("Japanese", &["Word", "Meaning"]);
+        let connect = anki.connect();
+        assert_eq!(connect.version().unwrap(), 6);
+        assert_eq!(connect.model_names().unwrap(), ["Basic", "Japanese"]);
+
+        connect.create_deck("Mining::Novels").unwrap();
+        let id = connect
+            .add_note(&NewNote {
+                deck_name: "Mining::Novels".to_string(),
+                model_name: "Japanese".to_string(),
+                fields: fields(&[("Word", "猫"), ("Meaning", "cat")]),
+                tags: vec!["yomichan".to_string()],
+                options: NewNoteOptions::default(),
+            })
+            .unwrap();
+        assert_eq!(
+            connect.find_notes("\"Word:猫\" \"deck:Mining\"").unwrap(),
+            [id]
+        );
+        assert!(connect
+            .find_notes("\"Word:猫\" -\"deck:Mining::*\"")
+            .unwrap()
+            .is_empty());
+        assert_eq!(connect.find_notes("note:japanese tag:yomi*").unwrap(), [id]);
+
+        connect
+            .update_note_fields(id, &fields(&[("Meaning", "cat (animal)")]))
+            .unwrap();
+        let info = connect.notes_info(&[id]).unwrap();
+        assert_eq!(info[0].field("Meaning"), Some("cat (animal)"));
+        assert!(matches!(
+            connect.update_note_fields(id, &fields(&[("Sentence", "")])),
+            Err(AnkiConnectError::Action { .. })
+        ));
+
+        connect.delete_notes(&[id]).unwrap();
+        assert!(anki.notes().is_empty());
+    }
+
+    #[test]
+    fn add_and_can_add_notes() {
+        let anki = MockAnkiConnect::start().unwrap();
+        let note = |front: &str| {
+            json!({ "deckName": "Default", "modelName": "Basic",
+            "fields": { "Front": front, "Back": "" } })
+        };
+        let connect = anki.connect();
+        let ids: Vec<Option<u64>> = connect
+            .invoke(
+                "addNotes",
+                json!({ "notes": [note("a"), note("a"), note("")] }),
+            )
+            .unwrap();
+        assert!(ids[0].is_some() && ids[1].is_none() && ids[2].is_none());
+        let can_add: Vec<bool> = connect
+            .invoke("canAddNotes", json!({ "notes": [note("a"), note("b")] }))
+            .unwrap();
+        assert_eq!(can_add, [false, true]);
+        assert!(connect.invoke::<Value>("guiBrowse", json!({})).is_err());
+    }
+
+    #[test]
+    fn duplicate_scopes() {
+        let anki = MockAnkiConnect::start().unwrap();
+        let connect = anki.connect();
+        connect.create_deck("Mining::Novels").unwrap();
+        let note = |deck: &str, options: NewNoteOptions| NewNote {
+            deck_name: deck.to_string(),
+            model_name: "Basic".to_string(),
+            fields: fields(&[("Front", "a"), ("Back", "")]),
+            tags: vec![],
+            options,
+        };
+        connect
+            .add_note(&note("Mining::Novels", NewNoteOptions::default()))
+            .unwrap();
+
+        let deck_scope = |deck_name: &str, check_children: bool| NewNoteOptions {
+            duplicate_scope: Some("deck".to_string()),
+            duplicate_scope_options: DuplicateScopeOptions {
+                deck_name: Some(deck_name.to_string()),
+                check_children,
+                check_all_models: false,
+            },
+            ..Default::default()
+        };
+        let checks = connect
+            .can_add_notes(&[
+                note("Default", NewNoteOptions::default()),
+                note("Default", deck_scope("Default", false)),
+                note("Mining", deck_scope("Mining", false)),
+                note("Mining", deck_scope("Mining", true)),
+            ])
+            .unwrap();
+        let duplicates: Vec<bool> = checks.iter().map(CanAddNote::is_duplicate).collect();
+        assert_eq!(duplicates, [true, false, false, true]);
+
+        let allow = NewNoteOptions {
+            allow_duplicate: true,
+            ..Default::default()
+        };
+        assert!(connect.add_note(&note("Default", allow)).is_ok());
+        assert_eq!(anki.notes().len(), 2);
+    }
+
+    #[test]
+    fn store_media() {
+        let anki = MockAnkiConnect::start().unwrap();
+        let connect = anki.connect();
+        let bytes = MediaSource::Bytes(vec![0, 1, 2, 250, 255]);
+        let url = MediaSource::Url("https://example.com/a.mp3".to_string());
+        connect.store_media_file("a.bin", &bytes).unwrap();
+        connect.store_media_file("a.mp3", &url).unwrap();
+        assert_eq!(anki.media()["a.bin"], bytes);
+        assert_eq!(anki.media()["a.mp3"], url);
+    }
+
+    #[test]
+    fn search_syntax() {
+        assert_eq!(
+            parse_query(r#"-"deck:a b::*" "Word:\"x\"" nid:1"#),
+            [
+                SearchTerm {
+                    negated: true,
+                    text: "deck:a b::*".to_string()
+                },
+                SearchTerm {
+                    negated: false,
+                    text: r#"Word:\"x\""#.to_string()
+                },
+                SearchTerm {
+                    negated: false,
+                    text: "nid:1".to_string()
+                },
+            ]
+        );
+        assert!(glob_match("ne*o", "Neko"));
+        assert!(glob_match(r"Japanese\_Vocab", "japanese_vocab"));
+        assert!(!glob_match(r"a\*", "ab"));
+        assert!(glob_match("_b", "ab") && !glob_match("_b", "b"));
+    }
+}
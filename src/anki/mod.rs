@@ -96,6 +96,31 @@
 //! )?;
 //! ```
 //!
+//! Any field can instead be filled with a template of [`FieldMarker`]s, which replaces
+//! the field's mapping. Unknown markers are rejected when the template is set.
+//!
+//! ```rust,no_run
+//! # use yomichan_rs::Yomichan;
+//! # let ycd = Yomichan::new("path/to/db.ycd").unwrap();
+//! # let anki = ycd.anki();
+//! anki.set_field_template("Front", "{furigana}")?;
+//! anki.set_field_template("Back", "{glossary-first}<br>{frequencies}")?;
+//! anki.set_field_template("Sentence", "{cloze-prefix}<b>{cloze-body}</b>{cloze-suffix}")?;
+//! ```
+//!
+//! Glossaries can be limited to some dictionaries, in a given order, and
+//! `{single-glossary-<dictionary>}` renders one dictionary on its own.
+//! The number of definitions and their labels are set by the profile's `glossary_*`
+//! [AnkiOptions](crate::settings::core::AnkiOptions).
+//!
+//! ```rust,no_run
+//! # use yomichan_rs::Yomichan;
+//! # let ycd = Yomichan::new("path/to/db.ycd").unwrap();
+//! # let anki = ycd.anki();
+//! anki.set_glossary_dictionaries(&["JMdict", "大辞林"])?;
+//! anki.set_field_template("Monolingual", "{single-glossary-大辞林}")?;
+//! ```
+//!
 //! ### 4. Action
 //! Once configured, you can build and add notes directly.
 //!
@@ -129,20 +154,86 @@
 //!         // Get the first entry for each recognized word
 //!         let entry = &res.dictionary_entries[0];
 //!
-//!         // .build_note_from_entry creates an anki_direct::Note object 
+//!         // .build_note_from_entry creates a NewNote
 //!         // but does not communicate with Anki yet.
 //!         let note = anki.build_note_from_entry(entry, Some(sentence))?;
 //!         pending_notes.push(note);
 //!     }
 //! }
 //!
-//! for note in pending_notes {
-//!     anki.add_entry(note, sentence.into())?;
+//! // later, add them all at once
+//! anki.connect()?.add_notes(&pending_notes)?;
+//!
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! ## Existing Notes
+//!
+//! Notes made from an entry can be found again, updated, or deleted.
+//!
+//! ```rust,no_run
+//! # use yomichan_rs::Yomichan;
+//! # let ycd = Yomichan::new("path/to/db.ycd").unwrap();
+//! # let anki = ycd.anki();
+//! # let segments = ycd.search("日本語").unwrap();
+//! # let entry = &segments[0].results.as_ref().unwrap().dictionary_entries[0];
+//! let note_ids = anki.find_entry_notes(entry)?;
+//! if let Some(&note_id) = note_ids.first() {
+//!     // re-mine the word with a better sentence
+//!     anki.update_note_fields_from_entry(note_id, entry, Some("日本語は難しい。"), &["Sentence"])?;
+//!     // and drop the older copies
+//!     anki.delete_notes(&note_ids[1..])?;
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! ## Kanji notes
+//!
+//! Kanji notes have their own model, deck and field mapping, see [`kanji`].
+//!
+//! ```rust,no_run
+//! # use yomichan_rs::Yomichan;
+//! # let ycd = Yomichan::new("path/to/db.ycd").unwrap();
+//! ycd.anki().configure_kanji_note_creation_auto()?;
+//! for kanji in ycd.find_kanji("日本語").unwrap() {
+//!     ycd.anki().add_kanji_entry(&kanji)?;
 //! }
+//! # Ok::<(), yomichan_rs::anki::DisplayAnkiError>(())
+//! ```
+//!
+//! ## Without AnkiConnect
 //!
+//! Notes can be exported with the same settings, as a TSV file for Anki's text import
+//! or as an `.apkg` package, see [`NoteExport`].
+//!
+//! ```rust,no_run
+//! # use yomichan_rs::Yomichan;
+//! # let ycd = Yomichan::new("path/to/db.ycd").unwrap();
+//! # let segments = ycd.search("日本語").unwrap();
+//! # let entry = &segments[0].results.as_ref().unwrap().dictionary_entries[0];
+//! let mut export = ycd.anki().note_export()?;
+//! export.add_entry(entry, None);
+//! export.write_apkg("mined.apkg")?;
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
 
+pub mod connect;
 pub mod core;
+#[cfg(feature = "anki")]
+pub mod duplicate;
+pub mod export;
+pub mod glossary;
+pub mod kanji;
+pub mod media;
+#[cfg(any(test, feature = "anki-mock"))]
+pub mod mock;
+pub mod template;
 
+pub use connect::{AnkiConnect, AnkiConnectError, NewNote, NoteInfo};
 pub use core::{DisplayAnki, DisplayAnkiError};
+#[cfg(feature = "anki")]
+pub use duplicate::EntryAddStatus;
+pub use export::{AnkiExportError, NoteExport};
+pub use glossary::GlossaryRenderer;
+pub use media::{AnkiMedia, NoteMedia};
+pub use template::{FieldMarker, FieldTemplate, FieldTemplateError};
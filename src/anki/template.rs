@@ -0,0 +1,720 @@
+//! Yomitan style field templates, e.g. `{furigana}<br>{glossary-first}`.
+//!
+//! A template is stored per Anki field in [AnkiOptions](crate::settings::core::AnkiOptions)
+//! and is parsed when it is created or deserialized,
+//! so unknown markers are reported when the template is configured instead of when a note is made.
+//!
+//! Besides the [FieldMarker]s, `{single-glossary-<dictionary>}` is replaced with the glossary
+//! of a single dictionary, given as its name, alias or
+//! [dictionary_slug](crate::anki::glossary::dictionary_slug).
+//!
+//! Marker values are HTML-escaped, with line breaks as `<br>`,
+//! except the glossaries and `{furigana}`, which are HTML already.
+
+use std::{fmt, str::FromStr};
+
+use indexmap::IndexSet;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    anki::{
+        glossary::{escape_html, GlossaryRenderer},
+        media::StoredMedia,
+    },
+    models::dictionary::TermDictionaryEntry,
+    scanner::core::Sentence,
+    settings::core::{
+        AnkiFrequencyFormat, AnkiTermFieldType, FrequencyAggregationMethod,
+        FrequencyAggregationOptions, FrequencyAggregationSource, SortFrequencyDictionaryOrder,
+    },
+    translator::frequency::aggregate_frequency,
+};
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum FieldTemplateError {
+    #[error("unknown marker `{{{marker}}}` in anki field template `{template}`")]
+    UnknownMarker { marker: String, template: String },
+}
+
+/// A `{marker}` that is replaced with data from the entry when a note is made.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FieldMarker {
+    /// The headword terms, e.g. `食べる`.
+    Expression,
+    /// The headword readings, e.g. `たべる`.
+    Reading,
+    /// The headwords as `<ruby>` furigana.
+    Furigana,
+    /// The headwords in Anki's `{{furigana:}}` syntax, e.g. `食[た]べる`.
+    FuriganaPlain,
+    /// Every glossary of every definition.
+    Glossary,
+    /// The glossaries of the first definition.
+    GlossaryFirst,
+    /// The sentence the term was found in.
+    Sentence,
+    /// The sentence before the term.
+    ClozePrefix,
+    /// The term as written in the sentence.
+    ClozeBody,
+    /// The sentence after the term.
+    ClozeSuffix,
+    /// Every frequency as `dictionary: value`.
+    Frequencies,
+    /// The lowest frequency rank of any dictionary.
+    FrequencyLowest,
+    /// The harmonic mean of the frequency ranks of every dictionary.
+    FrequencyHarmonicRank,
+    /// Every pitch accent in downstep and position notation, e.g. `はしꜜ [2]`.
+    PitchAccents,
+    /// The tags of the definitions.
+    Tags,
+    /// The dictionary of the first definition.
+    Dictionary,
+    /// The term audio as `[sound:file]`, see [NoteMedia](crate::anki::media::NoteMedia).
+    Audio,
+    /// The sentence audio as `[sound:file]`.
+    SentenceAudio,
+    /// The image as `<img src="file">`.
+    Image,
+}
+
+impl FieldMarker {
+    pub const ALL: [FieldMarker; 19] = [
+        FieldMarker::Expression,
+        FieldMarker::Reading,
+        FieldMarker::Furigana,
+        FieldMarker::FuriganaPlain,
+        FieldMarker::Glossary,
+        FieldMarker::GlossaryFirst,
+        FieldMarker::Sentence,
+        FieldMarker::ClozePrefix,
+        FieldMarker::ClozeBody,
+        FieldMarker::ClozeSuffix,
+        FieldMarker::Frequencies,
+        FieldMarker::FrequencyLowest,
+        FieldMarker::FrequencyHarmonicRank,
+        FieldMarker::PitchAccents,
+        FieldMarker::Tags,
+        FieldMarker::Dictionary,
+        FieldMarker::Audio,
+        FieldMarker::SentenceAudio,
+        FieldMarker::Image,
+    ];
+
+    /// The name between the braces, e.g. `"glossary-first"`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FieldMarker::Expression => "expression",
+            FieldMarker::Reading => "reading",
+            FieldMarker::Furigana => "furigana",
+            FieldMarker::FuriganaPlain => "furigana-plain",
+            FieldMarker::Glossary => "glossary",
+            FieldMarker::GlossaryFirst => "glossary-first",
+            FieldMarker::Sentence => "sentence",
+            FieldMarker::ClozePrefix => "cloze-prefix",
+            FieldMarker::ClozeBody => "cloze-body",
+            FieldMarker::ClozeSuffix => "cloze-suffix",
+            FieldMarker::Frequencies => "frequencies",
+            FieldMarker::FrequencyLowest => "frequency-lowest",
+            FieldMarker::FrequencyHarmonicRank => "frequency-harmonic-rank",
+            FieldMarker::PitchAccents => "pitch-accents",
+            FieldMarker::Tags => "tags",
+            FieldMarker::Dictionary => "dictionary",
+            FieldMarker::Audio => "audio",
+            FieldMarker::SentenceAudio => "sentence-audio",
+            FieldMarker::Image => "image",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        FieldMarker::ALL
+            .into_iter()
+            .find(|marker| marker.name() == name)
+    }
+
+    /// The value of this marker for the note being made.
+    pub fn render(&self, context: &NoteContext) -> String {
+        let entry = context.entry;
+        match self {
+            FieldMarker::Expression => {
+                join_unique(entry.headwords.iter().map(|hw| text_html(&hw.term)), " ")
+            }
+            FieldMarker::Reading => {
+                join_unique(entry.headwords.iter().map(|hw| text_html(&hw.reading)), " ")
+            }
+            FieldMarker::Furigana => entry
+                .headwords
+                .iter()
+                .map(|hw| hw.furigana_html())
+                .collect::<Vec<_>>()
+                .join(" "),
+            FieldMarker::FuriganaPlain => entry
+                .headwords
+                .iter()
+                .map(|hw| text_html(&hw.furigana_anki()))
+                .collect::<Vec<_>>()
+                .join(" "),
+            FieldMarker::Glossary => {
+                context.render_glossary(|g| g.render(&entry.definitions))
+            }
+            FieldMarker::GlossaryFirst => {
+                context.render_glossary(|g| g.render_first(&entry.definitions))
+            }
+            FieldMarker::Sentence => text_html(context.sentence.unwrap_or_default()),
+            FieldMarker::ClozePrefix => text_html(context.cloze().0),
+            FieldMarker::ClozeBody => text_html(context.cloze().1),
+            FieldMarker::ClozeSuffix => text_html(context.cloze().2),
+            FieldMarker::Frequencies => join_unique(
+                entry.frequencies.iter().map(|f| {
+                    let value = f
+                        .display_value
+                        .clone()
+                        .unwrap_or_else(|| f.frequency.to_string());
+                    text_html(&format!("{}: {value}", f.dictionary_alias))
+                }),
+                "<br>",
+            ),
+            FieldMarker::FrequencyLowest => {
+                entry_frequency(entry, FrequencyAggregationMethod::MinimumRank)
+            }
+            FieldMarker::FrequencyHarmonicRank => {
+                entry_frequency(entry, FrequencyAggregationMethod::HarmonicMean)
+            }
+            FieldMarker::PitchAccents => join_unique(
+                entry.pitch_accents().into_iter().map(|(_, info)| {
+                    text_html(&format!(
+                        "{} {}",
+                        info.downstep_notation(),
+                        info.position_notation()
+                    ))
+                }),
+                "<br>",
+            ),
+            FieldMarker::Tags => join_unique(
+                entry
+                    .definitions
+                    .iter()
+                    .flat_map(|def| &def.tags)
+                    .map(|tag| text_html(&tag.name)),
+                ", ",
+            ),
+            FieldMarker::Dictionary => entry
+                .definitions
+                .first()
+                .map(|def| text_html(&def.dictionary_alias))
+                .unwrap_or_default(),
+            FieldMarker::Audio => context
+                .media_file(|media| &media.term_audio)
+                .map(|file| format!("[sound:{}]", escape_html(file)))
+                .unwrap_or_default(),
+            FieldMarker::SentenceAudio => context
+                .media_file(|media| &media.sentence_audio)
+                .map(|file| format!("[sound:{}]", escape_html(file)))
+                .unwrap_or_default(),
+            FieldMarker::Image => context
+                .media_file(|media| &media.image)
+                .map(|file| format!("<img src=\"{}\">", escape_html(file)))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// The frequency ranks of every dictionary of `entry` combined with `method`,
+/// empty if the entry has no frequencies.
+fn entry_frequency(entry: &TermDictionaryEntry, method: FrequencyAggregationMethod) -> String {
+    let dictionaries: IndexSet<&str> = entry
+        .frequencies
+        .iter()
+        .map(|f| f.dictionary.as_str())
+        .collect();
+    let aggregation = FrequencyAggregationOptions {
+        method,
+        sources: dictionaries
+            .into_iter()
+            .map(|dictionary| {
+                FrequencyAggregationSource::new(dictionary, SortFrequencyDictionaryOrder::Rank)
+            })
+            .collect(),
+    };
+    aggregate_frequency(&aggregation, &entry.frequencies, None)
+        .map(|rank| rank.to_string())
+        .unwrap_or_default()
+}
+
+impl fmt::Display for FieldMarker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{{}}}", self.name())
+    }
+}
+
+/// `text` as HTML, with line breaks as `<br>`.
+fn text_html(text: &str) -> String {
+    escape_html(text).replace('\n', "<br>")
+}
+
+/// Joins the non-empty `values`, skipping duplicates.
+fn join_unique(values: impl Iterator<Item = String>, separator: &str) -> String {
+    values
+        .filter(|value| !value.is_empty())
+        .collect::<IndexSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum TemplatePart {
+    Text(String),
+    Marker(FieldMarker),
+    /// `{single-glossary-<dictionary>}`
+    SingleGlossary(String),
+}
+
+const SINGLE_GLOSSARY_PREFIX: &str = "single-glossary-";
+
+/// A parsed Anki field template.
+///
+/// `{name}` is a marker if `name` only consists of ascii letters, digits and `-`,
+/// or starts with `single-glossary-` and has no whitespace;
+/// every other brace (e.g. in inline css) is kept as text.
+///
+/// ```
+/// # use yomichan_rs::anki::template::FieldTemplate;
+/// let template: FieldTemplate = "{expression} ({reading})".parse().unwrap();
+/// assert_eq!(template.source(), "{expression} ({reading})");
+/// assert!("{definition}".parse::<FieldTemplate>().is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct FieldTemplate {
+    source: String,
+    parts: Vec<TemplatePart>,
+}
+
+impl FieldTemplate {
+    pub fn parse(source: &str) -> Result<Self, FieldTemplateError> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut rest = source;
+        while let Some(start) = rest.find('{') {
+            text.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            let name = after
+                .find('}')
+                .map(|end| &after[..end])
+                .filter(|name| is_marker_name(name));
+            let Some(name) = name else {
+                text.push('{');
+                rest = after;
+                continue;
+            };
+            let part = match name.strip_prefix(SINGLE_GLOSSARY_PREFIX) {
+                Some(dictionary) => TemplatePart::SingleGlossary(dictionary.to_string()),
+                None => FieldMarker::from_name(name).map(TemplatePart::Marker).ok_or_else(|| {
+                    FieldTemplateError::UnknownMarker {
+                        marker: name.to_string(),
+                        template: source.to_string(),
+                    }
+                })?,
+            };
+            if !text.is_empty() {
+                parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+            }
+            parts.push(part);
+            rest = &after[name.len() + 1..];
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            parts.push(TemplatePart::Text(text));
+        }
+        Ok(Self {
+            source: source.to_string(),
+            parts,
+        })
+    }
+
+    /// The template as it was written.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The markers used in the template, in order.
+    pub fn markers(&self) -> impl Iterator<Item = FieldMarker> + '_ {
+        self.parts.iter().filter_map(|part| match part {
+            TemplatePart::Marker(marker) => Some(*marker),
+            TemplatePart::Text(_) | TemplatePart::SingleGlossary(_) => None,
+        })
+    }
+
+    /// Replaces every marker with its value for the note being made.
+    pub fn render(&self, context: &NoteContext) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Text(text) => out.push_str(text),
+                TemplatePart::Marker(marker) => out.push_str(&marker.render(context)),
+                TemplatePart::SingleGlossary(dictionary) => {
+                    let definitions = &context.entry.definitions;
+                    out.push_str(
+                        &context.render_glossary(|g| g.render_dictionary(definitions, dictionary)),
+                    )
+                }
+            }
+        }
+        out
+    }
+}
+
+fn is_marker_name(name: &str) -> bool {
+    // dictionary names aren't limited to ascii
+    if let Some(dictionary) = name.strip_prefix(SINGLE_GLOSSARY_PREFIX) {
+        return !dictionary.is_empty()
+            && !dictionary.contains(|c: char| c.is_whitespace() || c == '{');
+    }
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+impl From<FieldMarker> for FieldTemplate {
+    fn from(marker: FieldMarker) -> Self {
+        Self {
+            source: marker.to_string(),
+            parts: vec![TemplatePart::Marker(marker)],
+        }
+    }
+}
+
+impl FromStr for FieldTemplate {
+    type Err = FieldTemplateError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<String> for FieldTemplate {
+    type Error = FieldTemplateError;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(&value)
+    }
+}
+
+impl From<FieldTemplate> for String {
+    fn from(template: FieldTemplate) -> Self {
+        template.source
+    }
+}
+
+impl fmt::Display for FieldTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// The data a [FieldTemplate] is rendered with.
+#[derive(Clone, Copy, Debug)]
+pub struct NoteContext<'a> {
+    pub entry: &'a TermDictionaryEntry,
+    /// The sentence the entry was found in.
+    pub sentence: Option<&'a str>,
+    /// The character offset in `sentence` of the text the entry was found for,
+    /// see [Sentence::offset].
+    pub sentence_offset: Option<usize>,
+    /// The media files of the note,
+    /// see [DisplayAnki::store_note_media](crate::anki::DisplayAnki::store_note_media).
+    pub media: Option<&'a StoredMedia>,
+    /// How `{glossary}`, `{glossary-first}` and `{single-glossary-<dictionary>}` are rendered,
+    /// the default renderer if `None`.
+    pub glossary: Option<&'a GlossaryRenderer>,
+}
+
+impl<'a> NoteContext<'a> {
+    pub fn new(entry: &'a TermDictionaryEntry, sentence: Option<&'a str>) -> Self {
+        Self {
+            entry,
+            sentence,
+            sentence_offset: None,
+            media: None,
+            glossary: None,
+        }
+    }
+
+    /// Uses the text of `sentence` and where in it the entry was found.
+    pub fn with_sentence(mut self, sentence: &'a Sentence) -> Self {
+        self.sentence = Some(&sentence.text);
+        self.sentence_offset = Some(sentence.offset);
+        self
+    }
+
+    pub fn with_media(mut self, media: &'a StoredMedia) -> Self {
+        self.media = Some(media);
+        self
+    }
+
+    pub fn with_glossary(mut self, glossary: &'a GlossaryRenderer) -> Self {
+        self.glossary = Some(glossary);
+        self
+    }
+
+    /// Renders with the context's [GlossaryRenderer], or the default one.
+    fn render_glossary(&self, render: impl Fn(&GlossaryRenderer) -> String) -> String {
+        match self.glossary {
+            Some(glossary) => render(glossary),
+            None => render(&GlossaryRenderer::default()),
+        }
+    }
+
+    fn media_file(&self, file: impl Fn(&StoredMedia) -> &Option<String>) -> Option<&'a str> {
+        self.media.and_then(|media| file(media).as_deref())
+    }
+
+    /// The sentence split around the text the entry was found for.
+    ///
+    /// With a [NoteContext::sentence_offset] the body is the source text of the entry at
+    /// that offset (see [split_cloze_at]). Without one, it is the first occurrence of the
+    /// source text in the sentence (see [split_cloze]).
+    pub fn cloze(&self) -> (&'a str, &'a str, &'a str) {
+        let Some(sentence) = self.sentence else {
+            return ("", "", "");
+        };
+        let mut sources: Vec<_> = self
+            .entry
+            .headwords
+            .iter()
+            .flat_map(|hw| &hw.sources)
+            .collect();
+        // primary sources first
+        sources.sort_by_key(|source| !source.is_primary);
+        let cloze = match self.sentence_offset {
+            Some(offset) => sources.into_iter().find_map(|source| {
+                split_cloze_at(sentence, offset, source.original_text.chars().count())
+            }),
+            None => sources
+                .into_iter()
+                .find_map(|source| split_cloze(sentence, &source.original_text)),
+        };
+        cloze.unwrap_or((sentence, "", ""))
+    }
+}
+
+/// Splits `sentence` into the text before, at, and after the `length` characters
+/// starting at the character `offset`.
+///
+/// `None` if `length` is 0 or the range is not in `sentence`.
+pub fn split_cloze_at(sentence: &str, offset: usize, length: usize) -> Option<(&str, &str, &str)> {
+    if length == 0 {
+        return None;
+    }
+    let mut boundaries = sentence
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(sentence.len()));
+    let start = boundaries.nth(offset)?;
+    let end = boundaries.nth(length - 1)?;
+    Some((&sentence[..start], &sentence[start..end], &sentence[end..]))
+}
+
+/// Splits `sentence` into the text before, at, and after the first `body`.
+///
+/// `None` if `body` is empty or not in `sentence`.
+pub fn split_cloze<'a>(sentence: &'a str, body: &str) -> Option<(&'a str, &'a str, &'a str)> {
+    if body.is_empty() {
+        return None;
+    }
+    let start = sentence.find(body)?;
+    let end = start + body.len();
+    Some((&sentence[..start], &sentence[start..end], &sentence[end..]))
+}
+
+impl AnkiTermFieldType {
+    /// The marker that fills this field when it has no template of its own.
+    pub fn default_marker(&self, frequency_format: AnkiFrequencyFormat) -> FieldMarker {
+        match self {
+            AnkiTermFieldType::Term(_) => FieldMarker::Expression,
+            AnkiTermFieldType::Reading(_) => FieldMarker::Reading,
+            AnkiTermFieldType::Sentence(_) => FieldMarker::Sentence,
+            AnkiTermFieldType::Definition(_) => FieldMarker::Glossary,
+            AnkiTermFieldType::Frequency(_) => frequency_format.marker(),
+            AnkiTermFieldType::TermAudio(_) => FieldMarker::Audio,
+            AnkiTermFieldType::SentenceAudio(_) => FieldMarker::SentenceAudio,
+            AnkiTermFieldType::Image(_) => FieldMarker::Image,
+        }
+    }
+}
+
+impl AnkiFrequencyFormat {
+    pub fn marker(&self) -> FieldMarker {
+        match self {
+            AnkiFrequencyFormat::All => FieldMarker::Frequencies,
+            AnkiFrequencyFormat::LowestRank => FieldMarker::FrequencyLowest,
+            AnkiFrequencyFormat::HarmonicMean => FieldMarker::FrequencyHarmonicRank,
+        }
+    }
+}
+
+#[cfg(test)]
+mod template_tests {
+    use super::*;
+    use crate::{
+        anki::core::NoteConfig,
+        settings::core::AnkiTermFieldType,
+        utils::test_utils::{TestYomichan, FIXTURE_DICTIONARY},
+    };
+    use indexmap::IndexMap;
+
+    #[test]
+    fn parse_markers() {
+        let template = FieldTemplate::parse("{furigana}<br>{glossary-first}").unwrap();
+        assert_eq!(
+            template.markers().collect::<Vec<_>>(),
+            [FieldMarker::Furigana, FieldMarker::GlossaryFirst]
+        );
+        for marker in FieldMarker::ALL {
+            assert_eq!(FieldMarker::from_name(marker.name()), Some(marker));
+        }
+    }
+
+    #[test]
+    fn braces_that_are_not_markers_are_text() {
+        let source = "<span style=\"a{color: red}\">{expression}</span> {} {";
+        let template = FieldTemplate::parse(source).unwrap();
+        assert_eq!(template.markers().count(), 1);
+        assert_eq!(
+            template.parts.last(),
+            Some(&TemplatePart::Text("</span> {} {".to_string()))
+        );
+    }
+
+    #[test]
+    fn unknown_marker() {
+        assert_eq!(
+            FieldTemplate::parse("{expression} {definition}"),
+            Err(FieldTemplateError::UnknownMarker {
+                marker: "definition".to_string(),
+                template: "{expression} {definition}".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn single_glossary() {
+        let template =
+            FieldTemplate::parse("{single-glossary-jmdict-english}<br>{大辞林}").unwrap();
+        assert_eq!(template.markers().count(), 0);
+        assert_eq!(
+            template.parts[0],
+            TemplatePart::SingleGlossary("jmdict-english".to_string())
+        );
+        assert_eq!(
+            FieldTemplate::parse("{single-glossary-大辞林}").unwrap().parts,
+            [TemplatePart::SingleGlossary("大辞林".to_string())]
+        );
+    }
+
+    #[test]
+    fn serde_validates() {
+        let template: FieldTemplate = serde_json::from_str("\"{reading}\"").unwrap();
+        assert_eq!(serde_json::to_string(&template).unwrap(), "\"{reading}\"");
+        assert!(serde_json::from_str::<FieldTemplate>("\"{nope}\"").is_err());
+    }
+
+    #[test]
+    fn cloze() {
+        assert_eq!(
+            split_cloze("昨日パンを食べた。", "食べた"),
+            Some(("昨日パンを", "食べた", "。"))
+        );
+        assert_eq!(split_cloze("昨日パンを食べた。", "飲んだ"), None);
+        assert_eq!(split_cloze("昨日パンを食べた。", ""), None);
+        assert_eq!(
+            split_cloze_at("昨日パンを食べた。", 5, 3),
+            Some(("昨日パンを", "食べた", "。"))
+        );
+        assert_eq!(
+            split_cloze_at("昨日パンを食べた。", 8, 1),
+            Some(("昨日パンを食べた", "。", ""))
+        );
+        assert_eq!(split_cloze_at("昨日パンを食べた。", 8, 2), None);
+        assert_eq!(split_cloze_at("昨日パンを食べた。", 5, 0), None);
+    }
+
+    /// The entry of 鳴く found for 鳴いた in the fixture dictionary.
+    fn naku(name: &str) -> TermDictionaryEntry {
+        let ycd = TestYomichan::new(name, &[FIXTURE_DICTIONARY]);
+        ycd.search_page("鳴いた", 0, None)
+            .unwrap()
+            .dictionary_entries
+            .into_iter()
+            .find(|entry| entry.headwords[0].term == "鳴く")
+            .unwrap()
+    }
+
+    fn config() -> NoteConfig {
+        NoteConfig {
+            model_name: "Japanese".to_string(),
+            deck_name: "Mining".to_string(),
+            tags: vec![],
+            field_mappings: vec![
+                AnkiTermFieldType::Term("Word".to_string()),
+                AnkiTermFieldType::Sentence("Sentence".to_string()),
+                AnkiTermFieldType::Definition("Meaning".to_string()),
+            ],
+            field_templates: IndexMap::from([
+                (
+                    "Cloze".to_string(),
+                    "{cloze-prefix}<b>{cloze-body}</b>{cloze-suffix}"
+                        .parse()
+                        .unwrap(),
+                ),
+                ("Furigana".to_string(), "{furigana}".parse().unwrap()),
+            ]),
+            frequency_format: Default::default(),
+            glossary: Default::default(),
+        }
+    }
+
+    #[test]
+    fn render_fields_cloze_at_sentence_offset() {
+        let entry = naku("render_fields_cloze");
+        let config = config();
+        // the entry was found for the second 鳴いた
+        let sentence = Sentence {
+            text: "犬が鳴いた。\n猫も鳴いた。".to_string(),
+            offset: 9,
+            cue: None,
+        };
+        let fields = config.render_fields(&NoteContext::new(&entry, None).with_sentence(&sentence));
+        assert_eq!(
+            fields.keys().collect::<Vec<_>>(),
+            ["Word", "Sentence", "Meaning", "Cloze", "Furigana"]
+        );
+        assert_eq!(fields["Word"], "鳴く");
+        assert_eq!(fields["Sentence"], "犬が鳴いた。<br>猫も鳴いた。");
+        assert_eq!(fields["Cloze"], "犬が鳴いた。<br>猫も<b>鳴いた</b>。");
+        assert!(fields["Meaning"].contains("to cry (of an animal)"));
+        assert!(fields["Furigana"].starts_with("<ruby>"));
+
+        // without an offset, the first occurrence is the cloze body
+        let fields = config.render_fields(&NoteContext::new(&entry, Some(&sentence.text)));
+        assert_eq!(fields["Cloze"], "犬が<b>鳴いた</b>。<br>猫も鳴いた。");
+    }
+
+    #[test]
+    fn render_fields_escapes_text() {
+        let entry = naku("render_fields_escapes");
+        let sentence = Sentence {
+            text: "<i>鳴いた</i> & \"ね\"".to_string(),
+            offset: 3,
+            cue: None,
+        };
+        let fields =
+            config().render_fields(&NoteContext::new(&entry, None).with_sentence(&sentence));
+        assert_eq!(
+            fields["Sentence"],
+            "&lt;i&gt;鳴いた&lt;/i&gt; &amp; &quot;ね&quot;"
+        );
+        assert_eq!(
+            fields["Cloze"],
+            "&lt;i&gt;<b>鳴いた</b>&lt;/i&gt; &amp; &quot;ね&quot;"
+        );
+    }
+}
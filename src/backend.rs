@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use crate::translator::types::FindTermsMatchType;
+use crate::translator::{core::FindTermsMode, types::FindTermsMatchType};
 use crate::{
     database::{DictionaryDatabaseError, DictionaryService, DictionarySummary},
     scanner::core::TextScanner,
@@ -28,9 +28,7 @@ impl Backend {
     pub fn new(db: Arc<dyn DictionaryService>) -> Result<Self, Box<DictionaryDatabaseError>> {
         let opts_blob = db.get_settings()?;
         let options = match opts_blob {
-            Some(blob) => native_model::decode::<YomichanOptions>(blob)
-                .map(|(t, _)| t)
-                .expect("Failed to decode options"),
+            Some(blob) => YomichanOptions::decode(blob).map_err(DictionaryDatabaseError::from)?,
             None => YomichanOptions::new(),
         };
         let backend = Self {
@@ -50,9 +48,8 @@ impl Backend {
             .get_settings()
             .map_err(|e| DisplayAnkiError::Custom(e.to_string()))?;
         let options: YomichanOptions = match opts_blob {
-            Some(blob) => native_model::decode::<YomichanOptions>(blob)
-                .map(|(t, _)| t)
-                .expect("Failed to decode options"),
+            Some(blob) => YomichanOptions::decode(blob)
+                .map_err(|e| DisplayAnkiError::Custom(format!("failed to decode options: {e}")))?,
             None => YomichanOptions::new(),
         };
         let options: Ptr<YomichanOptions> = options.into();
@@ -64,9 +61,27 @@ impl Backend {
             db: db.clone(),
             options: options.clone(),
         };
+        backend.refresh_dictionary_styles();
         Ok(backend)
     }
 
+    /// Reloads the installed dictionaries' styles used for Anki glossaries.
+    ///
+    /// Called at startup and after dictionaries are imported.
+    /// If the dictionary summaries cannot be read, the previous styles are kept.
+    #[cfg(feature = "anki")]
+    pub(crate) fn refresh_dictionary_styles(&self) {
+        match self.db.get_dictionary_summaries() {
+            Ok(summaries) => self.anki.read().set_dictionary_styles(
+                summaries
+                    .into_iter()
+                    .map(|summary| (summary.title, summary.styles))
+                    .collect(),
+            ),
+            Err(e) => tracing::warn!("failed to load dictionary styles: {e}"),
+        }
+    }
+
     /// The internal impl to write global options to the database.
     /// Takes an [Option<RwTransaction>] so rwtx's can be reused if necessary.
     ///
@@ -123,6 +138,17 @@ impl Yomichan {
         })
     }
 
+    /// Sets how the current profile groups search results, see [FindTermsMode].
+    ///
+    /// `Merge` groups by the profile's main dictionary.
+    /// Only updates the mode in memory.
+    /// To save it to the db, call [Self::update_options] after.
+    pub fn set_result_output_mode(&self, mode: FindTermsMode) -> ProfileResult<()> {
+        self.with_profile_mut(|profile| {
+            profile.options_mut().general.result_output_mode = mode;
+        })
+    }
+
     /// Deletes dictionaries from the database and options by name, in memory only.
     pub fn delete_dictionaries_by_names_in_memory(
         &self,
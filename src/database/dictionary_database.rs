@@ -1,4 +1,5 @@
 use crate::database::dictionary_importer::DictionarySummary;
+use crate::database::known_words::WordStatus;
 use crate::dictionary_importer::CHUNKS;
 use crate::translator::core::TagTargetItem;
 use yomichan_importer::dictionary_data::{TermMetaFreqDataMatchType, TermMetaModeType, TermMetaPitchData};
@@ -287,6 +288,8 @@ pub enum DictionaryDatabaseError {
         wrong: QueryRequestMatchType,
         expected: QueryRequestMatchType,
     },
+    #[error("failed to decode options: {0}")]
+    Options(#[from] native_model::Error),
 }
 
 impl From<rusqlite::Error> for DictionaryDatabaseError {
@@ -459,6 +462,30 @@ impl DictionaryDatabase {
                 data BLOB
             );
             CREATE INDEX IF NOT EXISTS idx_tags_name ON tags(name);
+
+            CREATE TABLE IF NOT EXISTS known_words (
+                term TEXT NOT NULL,
+                reading TEXT NOT NULL,
+                status TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL,
+                PRIMARY KEY (term, reading)
+            );
+            CREATE TABLE IF NOT EXISTS pending_notes (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                term TEXT NOT NULL,
+                model TEXT NOT NULL,
+                deck TEXT NOT NULL,
+                fields TEXT NOT NULL,
+                tags TEXT NOT NULL,
+                term_field TEXT,
+                media TEXT NOT NULL,
+                status TEXT NOT NULL,
+                error TEXT,
+                note_id INTEGER,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
         ",
         )
     }
@@ -887,6 +914,22 @@ impl crate::database::DictionaryService for DictionaryDatabase {
     ) -> Result<Vec<TermEntry>, Box<DictionaryDatabaseError>> {
         self.find_terms_bulk(term_list, dictionaries, match_type)
     }
+
+    fn find_word_status(
+        &self,
+        term: &str,
+        reading: &str,
+    ) -> Result<WordStatus, Box<DictionaryDatabaseError>> {
+        let statuses = self.find_word_statuses_bulk(&[(term.to_string(), reading.to_string())])?;
+        Ok(statuses.into_iter().next().unwrap_or_default())
+    }
+
+    fn find_word_statuses_bulk(
+        &self,
+        words: &[(String, String)],
+    ) -> Result<Vec<WordStatus>, Box<DictionaryDatabaseError>> {
+        self.find_word_statuses_bulk(words)
+    }
 }
 
 pub fn split_optional_string_field(field: Option<String>) -> Vec<String> {
@@ -58,6 +58,10 @@ impl Yomichan {
             )
             .expect("Failed to update options");
 
+        // new dictionaries' styles for anki glossaries
+        #[cfg(feature = "anki")]
+        self.backend.refresh_dictionary_styles();
+
         Ok(())
     }
 }
@@ -0,0 +1,296 @@
+use crate::database::dictionary_database::{
+    split_optional_string_field, DatabaseKanjiEntry, DatabaseMetaFrequency, DictionaryDatabase,
+    DictionaryDatabaseError, DictionarySet, GenericQueryRequest, QueryType,
+};
+use crate::dictionary_importer::CHUNKS;
+use crate::models::dictionary::{
+    DictionaryEntryType, KanjiDictionaryEntry, KanjiFrequency, KanjiStat, KanjiStatGroups, NumOrStr,
+};
+use crate::translator::core::Translator;
+use crate::Yomichan;
+
+use indexmap::{IndexMap, IndexSet};
+use native_model::decode;
+use yomichan_importer::dictionary_data::{GenericFreqData, TermMetaFreqDataMatchType};
+
+fn decode_blob<T: native_model::Model>(data: Vec<u8>) -> Result<T, rusqlite::Error> {
+    decode::<T>(data).map(|(t, _)| t).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Blob, Box::new(e))
+    })
+}
+
+impl DictionaryDatabase {
+    /// Returns the kanji bank entries of `characters` from `dictionaries`.
+    pub fn find_kanji_bulk(
+        &self,
+        characters: &[String],
+        dictionaries: &dyn DictionarySet,
+    ) -> Result<Vec<DatabaseKanjiEntry>, Box<DictionaryDatabaseError>> {
+        let mut results = Vec::new();
+        let conn = self.conn.lock();
+        for chunk in characters.chunks(CHUNKS) {
+            let placeholders = chunk.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+            let query = format!("SELECT data FROM kanji WHERE character IN ({placeholders})");
+            let mut stmt = conn.prepare(&query)?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(chunk), |row| {
+                decode_blob::<DatabaseKanjiEntry>(row.get(0)?)
+            })?;
+            for row in rows {
+                let entry = row?;
+                if dictionaries.has(entry.dictionary.as_deref().unwrap_or_default()) {
+                    results.push(entry);
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    /// Returns the kanji meta bank frequencies of `characters` from `dictionaries`.
+    pub fn find_kanji_meta_bulk(
+        &self,
+        characters: &[String],
+        dictionaries: &dyn DictionarySet,
+    ) -> Result<Vec<DatabaseMetaFrequency>, Box<DictionaryDatabaseError>> {
+        let mut results = Vec::new();
+        let conn = self.conn.lock();
+        for chunk in characters.chunks(CHUNKS) {
+            let placeholders = chunk.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+            let query = format!("SELECT data FROM kanji_meta WHERE character IN ({placeholders})");
+            let mut stmt = conn.prepare(&query)?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(chunk), |row| {
+                decode_blob::<DatabaseMetaFrequency>(row.get(0)?)
+            })?;
+            for row in rows {
+                let meta = row?;
+                if dictionaries.has(&meta.dictionary) {
+                    results.push(meta);
+                }
+            }
+        }
+        Ok(results)
+    }
+}
+
+impl Yomichan {
+    /// Looks up every kanji of `text`, in order and without repeats,
+    /// in the current profile's enabled dictionaries.
+    ///
+    /// Characters without a kanji bank entry are skipped.
+    /// Stats (e.g. `strokes`) are grouped by the category of their tag.
+    pub fn find_kanji(
+        &self,
+        text: &str,
+    ) -> Result<Vec<KanjiDictionaryEntry>, Box<DictionaryDatabaseError>> {
+        let characters: Vec<String> = text
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(String::from)
+            .collect::<IndexSet<_>>()
+            .into_iter()
+            .collect();
+        // enabled dictionary name -> index in the profile
+        let dictionaries: IndexMap<String, usize> = self
+            .with_profile(|profile| {
+                profile
+                    .dictionaries()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, options))| options.enabled)
+                    .map(|(index, (name, _))| (name.clone(), index))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let kanji = self.db.find_kanji_bulk(&characters, &dictionaries)?;
+        let meta = self.db.find_kanji_meta_bulk(&characters, &dictionaries)?;
+
+        let mut entries = Vec::new();
+        for character in &characters {
+            for entry in kanji.iter().filter(|entry| &entry.character == character) {
+                let dictionary = entry.dictionary.clone().unwrap_or_default();
+                let frequencies = meta
+                    .iter()
+                    .filter(|meta| &meta.freq_expression == character)
+                    .enumerate()
+                    .map(|(index, meta)| {
+                        kanji_frequency(index, meta, dictionaries.get(&meta.dictionary))
+                    })
+                    .collect();
+                entries.push(KanjiDictionaryEntry {
+                    entry_type: DictionaryEntryType::Kanji,
+                    character: character.clone(),
+                    onyomi: split_optional_string_field(entry.onyomi.clone()),
+                    kunyomi: split_optional_string_field(entry.kunyomi.clone()),
+                    tags: split_optional_string_field(entry.tags.clone())
+                        .into_iter()
+                        .map(|name| {
+                            let tag = self.find_tag(&name, &dictionary)?;
+                            Ok(Translator::_create_dictionary_tag(
+                                tag,
+                                name,
+                                dictionary.clone(),
+                            ))
+                        })
+                        .collect::<Result<_, Box<DictionaryDatabaseError>>>()?,
+                    stats: self.kanji_stats(entry, &dictionary)?,
+                    definitions: entry.meanings.clone(),
+                    frequencies,
+                    dictionary,
+                });
+            }
+        }
+        Ok(entries)
+    }
+
+    fn find_tag(
+        &self,
+        name: &str,
+        dictionary: &str,
+    ) -> Result<Option<crate::database::DatabaseTag>, Box<DictionaryDatabaseError>> {
+        let query = GenericQueryRequest {
+            query_type: QueryType::String(name.to_string()),
+            dictionary: dictionary.to_string(),
+        };
+        let tags = self.db.find_tag_meta_bulk(&[query])?;
+        Ok(tags.into_iter().next().flatten())
+    }
+
+    fn kanji_stats(
+        &self,
+        entry: &DatabaseKanjiEntry,
+        dictionary: &str,
+    ) -> Result<KanjiStatGroups, Box<DictionaryDatabaseError>> {
+        let mut groups = KanjiStatGroups::new();
+        for (name, value) in entry.stats.iter().flatten() {
+            let tag = self.find_tag(name, dictionary)?;
+            let tag = Translator::_create_dictionary_tag(tag, name.clone(), dictionary.to_string());
+            let value = match value.parse() {
+                Ok(num) => NumOrStr::Num(num),
+                Err(_) => NumOrStr::Str(value.clone()),
+            };
+            groups
+                .entry(tag.category.clone())
+                .or_default()
+                .push(KanjiStat {
+                    name: tag.name,
+                    category: tag.category,
+                    content: tag.content.join(", "),
+                    order: tag.order as u16,
+                    score: tag.score as u64,
+                    dictionary: dictionary.to_string(),
+                    value,
+                });
+        }
+        for stats in groups.values_mut() {
+            stats.sort_by_key(|stat| stat.order);
+        }
+        Ok(groups)
+    }
+}
+
+fn kanji_frequency(
+    index: usize,
+    meta: &DatabaseMetaFrequency,
+    dictionary_index: Option<&usize>,
+) -> KanjiFrequency {
+    let data = match &meta.data {
+        TermMetaFreqDataMatchType::WithReading(data) => data.frequency.clone(),
+        TermMetaFreqDataMatchType::Generic(data) => data.clone(),
+    };
+    let info = GenericFreqData::get_frequency_info(&data);
+    let dictionary_index = dictionary_index.copied().unwrap_or_default() as u16;
+    KanjiFrequency {
+        index: index as u64,
+        dictionary: meta.dictionary.clone(),
+        dictionary_index,
+        dictionary_priority: 0,
+        character: meta.freq_expression.clone(),
+        frequency: NumOrStr::Num(info.frequency),
+        display_value: info.display_value,
+        display_value_parsed: info.display_value_parsed,
+    }
+}
+
+#[cfg(test)]
+mod kanji_tests {
+    use super::*;
+    use crate::database::dictionary_database::DatabaseTag;
+    use crate::settings::core::DictionaryOptions;
+    use crate::utils::test_utils::TEST_PATHS;
+    use native_model::encode;
+    use rusqlite::params;
+    use yomichan_importer::dictionary_data::TermMetaModeType;
+
+    #[test]
+    fn find_kanji() {
+        let dir = TEST_PATHS.tests_dir.join("kanji_tmp");
+        let _ = std::fs::create_dir_all(&dir);
+        scopeguard::defer! {
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+        let ycd = Yomichan::new(&dir).unwrap();
+        ycd.with_profile_mut(|profile| {
+            let mut options = DictionaryOptions::new("KANJIDIC".to_string());
+            options.enabled = true;
+            profile
+                .dictionaries_mut()
+                .insert("KANJIDIC".to_string(), options);
+        })
+        .unwrap();
+
+        let kanji = DatabaseKanjiEntry {
+            character: "猫".to_string(),
+            onyomi: Some("ビョウ".to_string()),
+            kunyomi: Some("ねこ".to_string()),
+            tags: Some("jouyou".to_string()),
+            meanings: vec!["cat".to_string()],
+            stats: Some(IndexMap::from([("strokes".to_string(), "11".to_string())])),
+            dictionary: Some("KANJIDIC".to_string()),
+        };
+        let freq = DatabaseMetaFrequency {
+            id: "1".to_string(),
+            freq_expression: "猫".to_string(),
+            mode: TermMetaModeType::Freq,
+            data: TermMetaFreqDataMatchType::Generic(GenericFreqData::Integer(1702)),
+            dictionary: "KANJIDIC".to_string(),
+        };
+        let strokes = DatabaseTag {
+            id: "strokes".to_string(),
+            name: "strokes".to_string(),
+            category: "misc".to_string(),
+            order: 0,
+            notes: "Stroke count".to_string(),
+            score: 0,
+            dictionary: "KANJIDIC".to_string(),
+        };
+        {
+            let conn = ycd.db.conn.lock();
+            conn.execute(
+                "INSERT INTO kanji (character, dictionary, data) VALUES (?, ?, ?)",
+                params!["猫", "KANJIDIC", encode(&kanji).unwrap()],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO kanji_meta (character, dictionary, data) VALUES (?, ?, ?)",
+                params!["猫", "KANJIDIC", encode(&freq).unwrap()],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO tags (id, name, dictionary, data) VALUES (?, ?, ?, ?)",
+                params!["strokes", "strokes", "KANJIDIC", encode(&strokes).unwrap()],
+            )
+            .unwrap();
+        }
+
+        let entries = ycd.find_kanji("猫が猫を").unwrap();
+        assert_eq!(entries.len(), 1);
+        let cat = &entries[0];
+        assert_eq!(cat.onyomi, ["ビョウ"]);
+        assert_eq!(cat.kunyomi, ["ねこ"]);
+        assert_eq!(cat.definitions, ["cat"]);
+        assert_eq!(cat.stroke_count().as_deref(), Some("11"));
+        assert_eq!(cat.stats["misc"][0].content, "Stroke count");
+        assert_eq!(cat.tags[0].name, "jouyou");
+        assert_eq!(cat.frequencies[0].frequency, NumOrStr::Num(1702));
+    }
+}
@@ -0,0 +1,327 @@
+use crate::database::dictionary_database::{DictionaryDatabase, DictionaryDatabaseError};
+use crate::Yomichan;
+
+use chrono::prelude::*;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use std::str::FromStr;
+
+/// How well the user knows a word.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum WordStatus {
+    /// The word has never been marked (the status of any word missing from the table).
+    #[default]
+    Unknown,
+    Learning,
+    Known,
+    /// The word should not count towards comprehension (e.g., names, typos).
+    Ignored,
+}
+
+impl WordStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WordStatus::Unknown => "unknown",
+            WordStatus::Learning => "learning",
+            WordStatus::Known => "known",
+            WordStatus::Ignored => "ignored",
+        }
+    }
+}
+
+impl FromStr for WordStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unknown" => Ok(WordStatus::Unknown),
+            "learning" => Ok(WordStatus::Learning),
+            "known" => Ok(WordStatus::Known),
+            "ignored" => Ok(WordStatus::Ignored),
+            _ => Err(format!("unknown word status: {s}")),
+        }
+    }
+}
+
+/// A row of the `known_words` table.
+///
+/// An empty `reading` acts as a wildcard: it applies to every reading of `term`
+/// that doesn't have its own row (word lists often don't include readings).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownWord {
+    pub term: String,
+    pub reading: String,
+    pub status: WordStatus,
+    pub created_at: DateTime<Local>,
+    pub updated_at: DateTime<Local>,
+}
+
+pub(super) fn timestamp_to_local(secs: i64) -> DateTime<Local> {
+    DateTime::from_timestamp(secs, 0)
+        .unwrap_or_default()
+        .with_timezone(&Local)
+}
+
+fn parse_status(idx: usize, status: String) -> Result<WordStatus, rusqlite::Error> {
+    WordStatus::from_str(&status).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, e.into())
+    })
+}
+
+impl DictionaryDatabase {
+    /// Sets the status of every `(term, reading)` pair in a single transaction.
+    ///
+    /// Existing rows keep their `created_at` and get a new `updated_at`.
+    /// Returns the number of rows written.
+    pub fn set_word_statuses_bulk(
+        &self,
+        words: &[(String, String)],
+        status: WordStatus,
+    ) -> Result<usize, Box<DictionaryDatabaseError>> {
+        let now = Local::now().timestamp();
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO known_words (term, reading, status, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?4)
+                 ON CONFLICT(term, reading) DO UPDATE SET
+                    status = excluded.status,
+                    updated_at = excluded.updated_at",
+            )?;
+            for (term, reading) in words {
+                stmt.execute(params![term, reading, status.as_str(), now])?;
+            }
+        }
+        tx.commit()?;
+        Ok(words.len())
+    }
+
+    pub fn get_known_word(
+        &self,
+        term: &str,
+        reading: &str,
+    ) -> Result<Option<KnownWord>, Box<DictionaryDatabaseError>> {
+        let conn = self.conn.lock();
+        let word = conn
+            .query_row(
+                "SELECT term, reading, status, created_at, updated_at FROM known_words
+                 WHERE term = ? AND reading = ?",
+                [term, reading],
+                Self::row_to_known_word,
+            )
+            .optional()?;
+        Ok(word)
+    }
+
+    /// Returns all tracked words, optionally only those with `status`.
+    pub fn get_known_words(
+        &self,
+        status: Option<WordStatus>,
+    ) -> Result<Vec<KnownWord>, Box<DictionaryDatabaseError>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT term, reading, status, created_at, updated_at FROM known_words
+             WHERE ?1 IS NULL OR status = ?1
+             ORDER BY updated_at",
+        )?;
+        let words = stmt
+            .query_map([status.map(|s| s.as_str())], Self::row_to_known_word)?
+            .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+        Ok(words)
+    }
+
+    /// Looks up the status of each `(term, reading)` pair, falling back to the
+    /// term's wildcard (empty reading) row, then to [WordStatus::Unknown].
+    pub fn find_word_statuses_bulk(
+        &self,
+        words: &[(String, String)],
+    ) -> Result<Vec<WordStatus>, Box<DictionaryDatabaseError>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT status FROM known_words
+             WHERE term = ?1 AND (reading = ?2 OR reading = '')
+             ORDER BY reading = ?2 DESC
+             LIMIT 1",
+        )?;
+        let mut statuses = Vec::with_capacity(words.len());
+        for (term, reading) in words {
+            let status: Option<String> = stmt
+                .query_row([term, reading], |row| row.get(0))
+                .optional()?;
+            statuses.push(match status {
+                Some(status) => parse_status(0, status)?,
+                None => WordStatus::Unknown,
+            });
+        }
+        Ok(statuses)
+    }
+
+    pub fn remove_known_word(
+        &self,
+        term: &str,
+        reading: &str,
+    ) -> Result<bool, Box<DictionaryDatabaseError>> {
+        let conn = self.conn.lock();
+        let removed = conn.execute(
+            "DELETE FROM known_words WHERE term = ? AND reading = ?",
+            [term, reading],
+        )?;
+        Ok(removed > 0)
+    }
+
+    fn row_to_known_word(row: &rusqlite::Row) -> Result<KnownWord, rusqlite::Error> {
+        Ok(KnownWord {
+            term: row.get(0)?,
+            reading: row.get(1)?,
+            status: parse_status(2, row.get(2)?)?,
+            created_at: timestamp_to_local(row.get(3)?),
+            updated_at: timestamp_to_local(row.get(4)?),
+        })
+    }
+}
+
+impl Yomichan {
+    /// Marks a single word with `status`. Use an empty `reading` to match every reading.
+    pub fn set_word_status(
+        &self,
+        term: &str,
+        reading: &str,
+        status: WordStatus,
+    ) -> Result<(), Box<DictionaryDatabaseError>> {
+        self.db
+            .set_word_statuses_bulk(&[(term.to_string(), reading.to_string())], status)?;
+        Ok(())
+    }
+
+    /// Returns the status of a word, see [DictionaryDatabase::find_word_statuses_bulk].
+    pub fn word_status(
+        &self,
+        term: &str,
+        reading: &str,
+    ) -> Result<WordStatus, Box<DictionaryDatabaseError>> {
+        let statuses = self
+            .db
+            .find_word_statuses_bulk(&[(term.to_string(), reading.to_string())])?;
+        Ok(statuses.into_iter().next().unwrap_or_default())
+    }
+
+    pub fn known_words(
+        &self,
+        status: Option<WordStatus>,
+    ) -> Result<Vec<KnownWord>, Box<DictionaryDatabaseError>> {
+        self.db.get_known_words(status)
+    }
+
+    /// Marks every `(term, reading)` pair with `status`. Returns the number of words written.
+    pub fn import_known_words<I, T, R>(
+        &self,
+        words: I,
+        status: WordStatus,
+    ) -> Result<usize, Box<DictionaryDatabaseError>>
+    where
+        I: IntoIterator<Item = (T, R)>,
+        T: Into<String>,
+        R: Into<String>,
+    {
+        let words: Vec<(String, String)> = words
+            .into_iter()
+            .map(|(term, reading)| (term.into(), reading.into()))
+            .filter(|(term, _)| !term.trim().is_empty())
+            .collect();
+        self.db.set_word_statuses_bulk(&words, status)
+    }
+
+    /// Imports a plain text word list, one word per line.
+    ///
+    /// Each line is either `term` or `term<TAB>reading`. Blank lines and lines starting with `#`
+    /// are skipped. Returns the number of words written.
+    pub fn import_word_list(
+        &self,
+        list: &str,
+        status: WordStatus,
+    ) -> Result<usize, Box<DictionaryDatabaseError>> {
+        self.import_known_words(parse_word_list(list), status)
+    }
+
+    /// Imports the `Term` (and `Reading`, if mapped) field of every Anki note matching `query`,
+    /// e.g. `"deck:Japanese is:review"`. Returns the number of words written.
+    #[cfg(feature = "anki")]
+    pub fn import_known_words_from_anki(
+        &self,
+        query: &str,
+        status: WordStatus,
+    ) -> Result<usize, crate::anki::DisplayAnkiError> {
+        let words = self.backend.anki.read().term_field_values(query)?;
+        self.import_known_words(words, status).map_err(|e| {
+            crate::anki::DisplayAnkiError::Custom(format!("failed to store known words: {e}"))
+        })
+    }
+}
+
+fn parse_word_list(list: &str) -> impl Iterator<Item = (&str, &str)> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| match line.split_once('\t') {
+            Some((term, reading)) => (term.trim(), reading.trim()),
+            None => (line, ""),
+        })
+}
+
+#[cfg(test)]
+mod known_words_tests {
+    use super::*;
+    use crate::utils::test_utils::TEST_PATHS;
+
+    #[test]
+    fn word_list_parsing() {
+        let list = "# my words\n猫\tねこ\n\n  犬  \n";
+        let words: Vec<_> = parse_word_list(list).collect();
+        assert_eq!(words, [("猫", "ねこ"), ("犬", "")]);
+    }
+
+    #[test]
+    fn status_roundtrip() {
+        let dir = TEST_PATHS.tests_dir.join("known_words_tmp");
+        let _ = std::fs::create_dir_all(&dir);
+        scopeguard::defer! {
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+        let db = DictionaryDatabase::new(dir.join("known_words.ycd"));
+
+        let words = [
+            ("猫".to_string(), "ねこ".to_string()),
+            ("犬".to_string(), String::new()),
+        ];
+        db.set_word_statuses_bulk(&words, WordStatus::Learning)
+            .unwrap();
+        db.set_word_statuses_bulk(&words[..1], WordStatus::Known)
+            .unwrap();
+
+        let statuses = db
+            .find_word_statuses_bulk(&[
+                ("猫".into(), "ねこ".into()),
+                ("犬".into(), "いぬ".into()),
+                ("鳥".into(), "とり".into()),
+            ])
+            .unwrap();
+        assert_eq!(
+            statuses,
+            [WordStatus::Known, WordStatus::Learning, WordStatus::Unknown]
+        );
+
+        let cat = db.get_known_word("猫", "ねこ").unwrap().unwrap();
+        assert!(cat.updated_at >= cat.created_at);
+        assert_eq!(
+            db.get_known_words(Some(WordStatus::Learning))
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(db.remove_known_word("犬", "").unwrap());
+    }
+}
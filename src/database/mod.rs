@@ -1,5 +1,8 @@
 pub mod dictionary_database;
 pub mod dictionary_importer;
+pub mod kanji;
+pub mod known_words;
+pub mod note_queue;
 
 pub use dictionary_database::{
     DatabaseKanjiEntry, DatabaseKanjiMeta, DatabaseMetaFrequency, DatabaseMetaMatchType,
@@ -8,6 +11,8 @@ pub use dictionary_database::{
     QueryRequestMatchType, QueryType, TermExactQueryRequest,
 };
 pub use dictionary_importer::DictionarySummary;
+pub use known_words::{KnownWord, WordStatus};
+pub use note_queue::{AddOrQueued, NewPendingNote, PendingNote, PendingNoteStatus};
 
 pub trait DictionaryService: Send + Sync {
     fn get_settings(&self) -> Result<Option<Vec<u8>>, Box<DictionaryDatabaseError>>;
@@ -38,4 +43,24 @@ pub trait DictionaryService: Send + Sync {
         dictionaries: &dyn crate::database::dictionary_database::DictionarySet,
         match_type: yomichan_importer::dictionary_database::TermSourceMatchType,
     ) -> Result<Vec<yomichan_importer::dictionary_database::TermEntry>, Box<DictionaryDatabaseError>>;
+    /// The [WordStatus] of a `(term, reading)` pair.
+    ///
+    /// Services without known words return [WordStatus::Unknown] for every word.
+    fn find_word_status(
+        &self,
+        _term: &str,
+        _reading: &str,
+    ) -> Result<WordStatus, Box<DictionaryDatabaseError>> {
+        Ok(WordStatus::Unknown)
+    }
+    /// Same as [DictionaryService::find_word_status], for every pair of `words`.
+    fn find_word_statuses_bulk(
+        &self,
+        words: &[(String, String)],
+    ) -> Result<Vec<WordStatus>, Box<DictionaryDatabaseError>> {
+        words
+            .iter()
+            .map(|(term, reading)| self.find_word_status(term, reading))
+            .collect()
+    }
 }
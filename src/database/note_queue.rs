@@ -0,0 +1,548 @@
+//! The `pending_notes` table: Anki notes built while AnkiConnect was unreachable,
+//! waiting to be sent with [Yomichan::flush_pending_notes](crate::Yomichan::flush_pending_notes).
+
+use crate::{
+    anki::media::AnkiMedia,
+    database::{
+        dictionary_database::{DictionaryDatabase, DictionaryDatabaseError},
+        known_words::timestamp_to_local,
+    },
+};
+#[cfg(feature = "anki")]
+use crate::{
+    anki::{
+        connect::NewNote, core::DisplayAnkiError, duplicate::find_duplicates, media::NoteMedia,
+        template::NoteContext,
+    },
+    settings::core::AnkiDuplicateBehavior,
+    TermDictionaryEntry, Yomichan,
+};
+
+use chrono::prelude::*;
+use indexmap::IndexMap;
+use rusqlite::{params, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use std::str::FromStr;
+
+/// Where a queued note is at.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PendingNoteStatus {
+    /// Waiting to be sent.
+    #[default]
+    Pending,
+    /// Added to Anki, or used to overwrite a duplicate.
+    Added,
+    /// Not added, since a duplicate was added meanwhile and the profile prevents duplicates.
+    Duplicate,
+    /// Anki rejected the note, see [PendingNote::error].
+    Failed,
+}
+
+impl PendingNoteStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PendingNoteStatus::Pending => "pending",
+            PendingNoteStatus::Added => "added",
+            PendingNoteStatus::Duplicate => "duplicate",
+            PendingNoteStatus::Failed => "failed",
+        }
+    }
+}
+
+impl FromStr for PendingNoteStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(PendingNoteStatus::Pending),
+            "added" => Ok(PendingNoteStatus::Added),
+            "duplicate" => Ok(PendingNoteStatus::Duplicate),
+            "failed" => Ok(PendingNoteStatus::Failed),
+            _ => Err(format!("unknown pending note status: {s}")),
+        }
+    }
+}
+
+/// A fully built note, as it would have been sent to Anki.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NewPendingNote {
+    /// The entry's headwords, for display.
+    pub term: String,
+    pub model_name: String,
+    pub deck_name: String,
+    pub fields: IndexMap<String, String>,
+    pub tags: Vec<String>,
+    /// The field `Term` was mapped to, `None` if no field was.
+    pub term_field: Option<String>,
+    /// Media the fields refer to, stored before the note is added.
+    pub media: Vec<AnkiMedia>,
+}
+
+/// A row of the `pending_notes` table.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PendingNote {
+    pub id: i64,
+    pub note: NewPendingNote,
+    pub status: PendingNoteStatus,
+    /// Why the note failed, or the ids of the duplicates.
+    pub error: Option<String>,
+    /// The id of the Anki note that was added or overwritten.
+    pub note_id: Option<u64>,
+    pub created_at: DateTime<Local>,
+    pub updated_at: DateTime<Local>,
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(idx: usize, json: String) -> rusqlite::Result<T> {
+    serde_json::from_str(&json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, e.into())
+    })
+}
+
+fn to_json<T: Serialize>(value: &T) -> rusqlite::Result<String> {
+    serde_json::to_string(value).map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))
+}
+
+impl DictionaryDatabase {
+    /// Adds `note` to the queue as [PendingNoteStatus::Pending] and returns its id.
+    pub fn queue_pending_note(
+        &self,
+        note: &NewPendingNote,
+    ) -> Result<i64, Box<DictionaryDatabaseError>> {
+        let now = Local::now().timestamp();
+        let conn = self.conn.lock();
+        conn.execute(
+            "INSERT INTO pending_notes
+                (term, model, deck, fields, tags, term_field, media, status, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?9)",
+            params![
+                note.term,
+                note.model_name,
+                note.deck_name,
+                to_json(&note.fields)?,
+                to_json(&note.tags)?,
+                note.term_field,
+                to_json(&note.media)?,
+                PendingNoteStatus::Pending.as_str(),
+                now,
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn get_pending_note(
+        &self,
+        id: i64,
+    ) -> Result<Option<PendingNote>, Box<DictionaryDatabaseError>> {
+        let conn = self.conn.lock();
+        let note = conn
+            .query_row(
+                "SELECT id, term, model, deck, fields, tags, term_field, media,
+                        status, error, note_id, created_at, updated_at
+                 FROM pending_notes WHERE id = ?",
+                [id],
+                Self::row_to_pending_note,
+            )
+            .optional()?;
+        Ok(note)
+    }
+
+    /// Returns the queued notes in the order they were queued,
+    /// optionally only those with `status`.
+    pub fn get_pending_notes(
+        &self,
+        status: Option<PendingNoteStatus>,
+    ) -> Result<Vec<PendingNote>, Box<DictionaryDatabaseError>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT id, term, model, deck, fields, tags, term_field, media,
+                    status, error, note_id, created_at, updated_at
+             FROM pending_notes
+             WHERE ?1 IS NULL OR status = ?1
+             ORDER BY id",
+        )?;
+        let notes = stmt
+            .query_map([status.map(|s| s.as_str())], Self::row_to_pending_note)?
+            .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+        Ok(notes)
+    }
+
+    pub fn set_pending_note_status(
+        &self,
+        id: i64,
+        status: PendingNoteStatus,
+        error: Option<&str>,
+        note_id: Option<u64>,
+    ) -> Result<(), Box<DictionaryDatabaseError>> {
+        let now = Local::now().timestamp();
+        let conn = self.conn.lock();
+        conn.execute(
+            "UPDATE pending_notes SET status = ?2, error = ?3, note_id = ?4, updated_at = ?5
+             WHERE id = ?1",
+            params![id, status.as_str(), error, note_id.map(|id| id as i64), now],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_pending_note(&self, id: i64) -> Result<bool, Box<DictionaryDatabaseError>> {
+        let conn = self.conn.lock();
+        let removed = conn.execute("DELETE FROM pending_notes WHERE id = ?", [id])?;
+        Ok(removed > 0)
+    }
+
+    /// Removes every note that was [PendingNoteStatus::Added]. Returns the number removed.
+    pub fn clear_added_pending_notes(&self) -> Result<usize, Box<DictionaryDatabaseError>> {
+        let conn = self.conn.lock();
+        let removed = conn.execute(
+            "DELETE FROM pending_notes WHERE status = ?",
+            [PendingNoteStatus::Added.as_str()],
+        )?;
+        Ok(removed)
+    }
+
+    fn row_to_pending_note(row: &rusqlite::Row) -> Result<PendingNote, rusqlite::Error> {
+        let status: String = row.get(8)?;
+        let status = PendingNoteStatus::from_str(&status).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(8, rusqlite::types::Type::Text, e.into())
+        })?;
+        Ok(PendingNote {
+            id: row.get(0)?,
+            note: NewPendingNote {
+                term: row.get(1)?,
+                model_name: row.get(2)?,
+                deck_name: row.get(3)?,
+                fields: from_json(4, row.get(4)?)?,
+                tags: from_json(5, row.get(5)?)?,
+                term_field: row.get(6)?,
+                media: from_json(7, row.get(7)?)?,
+            },
+            status,
+            error: row.get(9)?,
+            note_id: row.get::<_, Option<i64>>(10)?.map(|id| id as u64),
+            created_at: timestamp_to_local(row.get(11)?),
+            updated_at: timestamp_to_local(row.get(12)?),
+        })
+    }
+}
+
+/// What [Yomichan::add_entry_or_queue] did with an entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AddOrQueued {
+    /// Added to Anki, with the ids [DisplayAnki::add_entry](crate::anki::DisplayAnki::add_entry)
+    /// returned.
+    Added(Vec<usize>),
+    /// AnkiConnect was unreachable, the note was queued with this id.
+    Queued(i64),
+}
+
+#[cfg(feature = "anki")]
+impl Yomichan {
+    /// Adds `entry` to Anki like
+    /// [DisplayAnki::add_entry_with_media](crate::anki::DisplayAnki::add_entry_with_media),
+    /// or, if AnkiConnect is unreachable and the profile's `queue_when_offline` is set,
+    /// queues the fully built note in the database to be sent by [Self::flush_pending_notes].
+    ///
+    /// ```rust,no_run
+    /// # use yomichan_rs::{Yomichan, anki::NoteMedia, database::note_queue::AddOrQueued};
+    /// # let ycd = Yomichan::new("path/to/db.ycd").unwrap();
+    /// # let segments = ycd.search("日本語").unwrap();
+    /// # let entry = &segments[0].results.as_ref().unwrap().dictionary_entries[0];
+    /// let media = NoteMedia::default();
+    /// if let AddOrQueued::Queued(_) = ycd.add_entry_or_queue(entry, None, &media)? {
+    ///     // later, once Anki is running again
+    ///     ycd.flush_pending_notes()?;
+    /// }
+    /// # Ok::<(), yomichan_rs::anki::DisplayAnkiError>(())
+    /// ```
+    pub fn add_entry_or_queue(
+        &self,
+        entry: &TermDictionaryEntry,
+        sentence: Option<&str>,
+        media: &NoteMedia,
+    ) -> Result<AddOrQueued, DisplayAnkiError> {
+        self.add_entry_or_queue_with_tags(entry, sentence, media, &[])
+    }
+
+    /// Same as [Self::add_entry_or_queue], but tags the note with `extra_tags`
+    /// on top of the profile's configured tags,
+    /// see [DisplayAnki::add_entry_with_tags](crate::anki::DisplayAnki::add_entry_with_tags).
+    pub fn add_entry_or_queue_with_tags(
+        &self,
+        entry: &TermDictionaryEntry,
+        sentence: Option<&str>,
+        media: &NoteMedia,
+        extra_tags: &[String],
+    ) -> Result<AddOrQueued, DisplayAnkiError> {
+        let anki = self.backend.anki.read();
+        if !anki.with_anki_options(|opts| *opts.queue_when_offline())? {
+            return anki
+                .add_entry_with_tags(entry, sentence, media, extra_tags)
+                .map(AddOrQueued::Added);
+        }
+        let result = match anki.connect()?.version() {
+            Ok(_) => anki.add_entry_with_tags(entry, sentence, media, extra_tags),
+            Err(e) => Err(e.into()),
+        };
+        match result {
+            Err(e) if e.is_unreachable() => {
+                let config = anki.note_config()?;
+                let filenames = media.filenames();
+                let context = NoteContext::new(entry, sentence).with_media(&filenames);
+                let note = NewPendingNote {
+                    term: entry.get_headword_text_joined(),
+                    model_name: config.model_name.clone(),
+                    deck_name: config.deck_name.clone(),
+                    fields: config.render_fields(&context),
+                    tags: config.tags.iter().chain(extra_tags).cloned().collect(),
+                    term_field: config.term_field().map(str::to_string),
+                    media: media.files(),
+                };
+                let id = self.db.queue_pending_note(&note).map_err(queue_error)?;
+                Ok(AddOrQueued::Queued(id))
+            }
+            result => result.map(AddOrQueued::Added),
+        }
+    }
+
+    /// Sends every [PendingNoteStatus::Pending] note to Anki, oldest first,
+    /// and returns them with their new status.
+    ///
+    /// Notes are checked against the current profile and collection, since both may have
+    /// changed since they were queued:
+    /// - a note whose model no longer exists is [PendingNoteStatus::Failed]
+    /// - a missing deck is created
+    /// - fields the model no longer has are dropped
+    /// - duplicates are handled by the current `duplicate_behavior`:
+    ///   `Prevent` marks the note [PendingNoteStatus::Duplicate],
+    ///   `Overwrite` updates the first duplicate (keeping `overwrite_keep_fields`)
+    ///
+    /// Stops early, leaving the rest pending, if AnkiConnect becomes unreachable.
+    pub fn flush_pending_notes(&self) -> Result<Vec<PendingNote>, DisplayAnkiError> {
+        let pending = self
+            .db
+            .get_pending_notes(Some(PendingNoteStatus::Pending))
+            .map_err(queue_error)?;
+        if pending.is_empty() {
+            return Ok(pending);
+        }
+
+        let anki = self.backend.anki.read();
+        let connect = anki.connect()?;
+        let (model_names, mut deck_names) = match (connect.model_names(), connect.deck_names()) {
+            (Ok(models), Ok(decks)) => (models, decks),
+            (Err(e), _) | (_, Err(e)) if e.is_unreachable() => return Ok(vec![]),
+            (Err(e), _) | (_, Err(e)) => return Err(e.into()),
+        };
+        let options = anki.duplicate_options()?;
+        let keep_fields = anki.with_anki_options(|opts| opts.overwrite_keep_fields().clone())?;
+        let mut model_fields: IndexMap<String, Vec<String>> = IndexMap::new();
+
+        let mut flushed = Vec::with_capacity(pending.len());
+        for pending_note in pending {
+            let mut note = pending_note.note;
+            let result = (|| -> Result<_, DisplayAnkiError> {
+                if !model_names.contains(&note.model_name) {
+                    let reason = format!("model '{}' no longer exists", note.model_name);
+                    return Ok((PendingNoteStatus::Failed, Some(reason), None));
+                }
+                if !deck_names.contains(&note.deck_name) {
+                    connect.create_deck(&note.deck_name)?;
+                    deck_names.push(note.deck_name.clone());
+                }
+                if !model_fields.contains_key(&note.model_name) {
+                    let fields = connect.model_field_names(&note.model_name)?;
+                    model_fields.insert(note.model_name.clone(), fields);
+                }
+                let fields = &model_fields[&note.model_name];
+                note.fields
+                    .retain(|field_name, _| fields.contains(field_name));
+
+                let new_note = NewNote {
+                    deck_name: note.deck_name.clone(),
+                    model_name: note.model_name.clone(),
+                    fields: note.fields.clone(),
+                    tags: note.tags.clone(),
+                    options: options.note_options(&note.deck_name),
+                };
+                let duplicate_ids = find_duplicates(&connect, options, &new_note)?;
+                let overwrite_id = match (options.behavior, &duplicate_ids) {
+                    (AnkiDuplicateBehavior::Allow, _) | (_, None) => None,
+                    (AnkiDuplicateBehavior::Overwrite, Some(ids)) if !ids.is_empty() => {
+                        Some(ids[0])
+                    }
+                    (_, Some(ids)) => {
+                        let reason = format!("duplicate of anki notes {ids:?}");
+                        return Ok((PendingNoteStatus::Duplicate, Some(reason), None));
+                    }
+                };
+
+                for media in &note.media {
+                    anki.store_media(media)?;
+                }
+                if let Some(note_id) = overwrite_id {
+                    let mut fields = note.fields.clone();
+                    fields.retain(|field_name, value| {
+                        !value.is_empty() && !keep_fields.contains(field_name)
+                    });
+                    if !fields.is_empty() {
+                        connect.update_note_fields(note_id, &fields)?;
+                    }
+                    return Ok((PendingNoteStatus::Added, None, Some(note_id)));
+                }
+                let note_id = connect.add_note(&new_note)?;
+                Ok((PendingNoteStatus::Added, None, Some(note_id)))
+            })();
+
+            let (status, error, note_id) = match result {
+                Ok(outcome) => outcome,
+                Err(e) if e.is_unreachable() => break,
+                Err(e) => (PendingNoteStatus::Failed, Some(e.to_string()), None),
+            };
+            self.db
+                .set_pending_note_status(pending_note.id, status, error.as_deref(), note_id)
+                .map_err(queue_error)?;
+            if let Some(flushed_note) = self
+                .db
+                .get_pending_note(pending_note.id)
+                .map_err(queue_error)?
+            {
+                flushed.push(flushed_note);
+            }
+        }
+        Ok(flushed)
+    }
+
+    /// The queued notes, optionally only those with `status`, oldest first.
+    pub fn pending_notes(
+        &self,
+        status: Option<PendingNoteStatus>,
+    ) -> Result<Vec<PendingNote>, Box<DictionaryDatabaseError>> {
+        self.db.get_pending_notes(status)
+    }
+
+    /// Removes a queued note, e.g. a [PendingNoteStatus::Failed] one the user gave up on.
+    pub fn remove_pending_note(&self, id: i64) -> Result<bool, Box<DictionaryDatabaseError>> {
+        self.db.remove_pending_note(id)
+    }
+}
+
+#[cfg(feature = "anki")]
+fn queue_error(e: Box<DictionaryDatabaseError>) -> DisplayAnkiError {
+    DisplayAnkiError::Custom(format!("failed to access the note queue: {e}"))
+}
+
+#[cfg(test)]
+mod note_queue_tests {
+    use super::*;
+    use crate::utils::test_utils::TEST_PATHS;
+
+    #[test]
+    fn queue_roundtrip() {
+        let dir = TEST_PATHS.tests_dir.join("note_queue_tmp");
+        let _ = std::fs::create_dir_all(&dir);
+        scopeguard::defer! {
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+        let db = DictionaryDatabase::new(dir.join("note_queue.ycd"));
+
+        let note = NewPendingNote {
+            term: "猫".to_string(),
+            model_name: "Japanese".to_string(),
+            deck_name: "Mining".to_string(),
+            fields: IndexMap::from([
+                ("Word".to_string(), "猫".to_string()),
+                ("Audio".to_string(), "[sound:yomichan_1.mp3]".to_string()),
+            ]),
+            tags: vec!["yomichan".to_string()],
+            term_field: Some("Word".to_string()),
+            media: vec![AnkiMedia::from_url("https://example.com/猫.mp3")],
+        };
+        let first = db.queue_pending_note(&note).unwrap();
+        let second = db.queue_pending_note(&note).unwrap();
+        assert_ne!(first, second);
+
+        let queued = db.get_pending_note(first).unwrap().unwrap();
+        assert_eq!(queued.note, note);
+        assert_eq!(queued.status, PendingNoteStatus::Pending);
+
+        db.set_pending_note_status(first, PendingNoteStatus::Added, None, Some(42))
+            .unwrap();
+        let added = db.get_pending_note(first).unwrap().unwrap();
+        assert_eq!(added.note_id, Some(42));
+        assert_eq!(
+            db.get_pending_notes(Some(PendingNoteStatus::Pending))
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(db.clear_added_pending_notes().unwrap(), 1);
+        assert!(db.remove_pending_note(second).unwrap());
+        assert!(db.get_pending_notes(None).unwrap().is_empty());
+    }
+
+    #[cfg(feature = "anki")]
+    #[test]
+    fn flush_against_mock_anki() {
+        use crate::{anki::mock::MockAnkiConnect, Yomichan};
+
+        let dir = TEST_PATHS.tests_dir.join("note_queue_flush_tmp");
+        let _ = std::fs::create_dir_all(&dir);
+        scopeguard::defer! {
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+        let ycd = Yomichan::new(&dir).unwrap();
+        let set_server = |server: String| {
+            ycd.with_profile_mut(|profile| {
+                profile.anki_options_mut().set_server(server);
+            })
+            .unwrap();
+        };
+        let note = |model_name: &str, word: &str| NewPendingNote {
+            term: word.to_string(),
+            model_name: model_name.to_string(),
+            deck_name: "Mining".to_string(),
+            fields: IndexMap::from([
+                ("Word".to_string(), word.to_string()),
+                ("Removed".to_string(), "dropped".to_string()),
+            ]),
+            tags: vec![],
+            term_field: Some("Word".to_string()),
+            media: vec![AnkiMedia::from_bytes(vec![1, 2, 3], "mp3")],
+        };
+        ycd.db.queue_pending_note(&note("Japanese", "猫")).unwrap();
+        ycd.db.queue_pending_note(&note("Japanese", "猫")).unwrap();
+        ycd.db.queue_pending_note(&note("Gone", "犬")).unwrap();
+
+        // nothing is sent while anki is unreachable
+        let offline = MockAnkiConnect::start().unwrap().server();
+        set_server(offline);
+        assert!(ycd.flush_pending_notes().unwrap().is_empty());
+
+        let anki = MockAnkiConnect::start().unwrap();
+        anki.add_model("Japanese", &["Word", "Meaning"]);
+        set_server(anki.server());
+        let statuses: Vec<_> = ycd
+            .flush_pending_notes()
+            .unwrap()
+            .into_iter()
+            .map(|note| note.status)
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                PendingNoteStatus::Added,
+                PendingNoteStatus::Duplicate,
+                PendingNoteStatus::Failed
+            ]
+        );
+        let notes = anki.notes();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].deck_name, "Mining");
+        assert_eq!(notes[0].fields["Word"], "猫");
+        assert_eq!(anki.media().len(), 1);
+        assert!(ycd
+            .pending_notes(Some(PendingNoteStatus::Pending))
+            .unwrap()
+            .is_empty());
+    }
+}
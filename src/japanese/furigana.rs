@@ -0,0 +1,358 @@
+//! Splits a headword into base text / reading pairs for ruby text and Anki's furigana syntax.
+
+use derive_more::{Deref, From};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    japanese::kana::{is_kana, katakana_char_to_hiragana},
+    models::dictionary::{TermDictionaryEntry, TermHeadword},
+};
+
+/// A piece of a headword.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FuriganaSegment {
+    /// The written text, e.g. `"食"` or `"べる"`.
+    pub text: String,
+    /// The reading of `text`; `None` if `text` is already kana (e.g. okurigana).
+    pub reading: Option<String>,
+}
+
+impl FuriganaSegment {
+    pub fn new(text: impl Into<String>, reading: Option<String>) -> Self {
+        Self {
+            text: text.into(),
+            reading,
+        }
+    }
+
+    fn plain(text: impl Into<String>) -> Self {
+        Self::new(text, None)
+    }
+}
+
+/// A headword split into [FuriganaSegment]s.
+///
+/// ```
+/// # use yomichan_rs::japanese::furigana::Furigana;
+/// let furigana = Furigana::distribute("食べ物", "たべもの");
+/// assert_eq!(furigana.to_anki(), "食[た]べ 物[もの]");
+/// assert_eq!(
+///     furigana.to_html(),
+///     "<ruby>食<rt>た</rt></ruby>べ<ruby>物<rt>もの</rt></ruby>"
+/// );
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, Deref, From)]
+pub struct Furigana(Vec<FuriganaSegment>);
+
+impl Furigana {
+    /// Aligns `reading` to the kanji of `term`.
+    ///
+    /// Kana in `term` are matched against `reading` (hiragana and katakana are treated as equal),
+    /// and the kana between them are assigned to the surrounding kanji runs.
+    /// If the alignment is impossible or ambiguous, the shared kana prefix and suffix
+    /// are split off and the remaining text gets the remaining reading.
+    pub fn distribute(term: &str, reading: &str) -> Self {
+        if reading.is_empty() || reading == term {
+            return Self(vec![FuriganaSegment::plain(term)]);
+        }
+        let groups = group_by_kana(term);
+        let reading: Vec<char> = reading.chars().collect();
+        if let Some(segments) = segmentize(&reading, &groups) {
+            return Self(segments);
+        }
+        Self(distribute_fallback(term, &reading))
+    }
+
+    pub fn segments(&self) -> &[FuriganaSegment] {
+        &self.0
+    }
+
+    pub fn into_segments(self) -> Vec<FuriganaSegment> {
+        self.0
+    }
+
+    /// `<ruby>` markup, e.g. `<ruby>漢字<rt>かんじ</rt></ruby>`.
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        for segment in &self.0 {
+            match &segment.reading {
+                Some(reading) => {
+                    html.push_str("<ruby>");
+                    html.push_str(&escape_html(&segment.text));
+                    html.push_str("<rt>");
+                    html.push_str(&escape_html(reading));
+                    html.push_str("</rt></ruby>");
+                }
+                None => html.push_str(&escape_html(&segment.text)),
+            }
+        }
+        html
+    }
+
+    /// Anki's `{{furigana:}}` syntax, e.g. `漢字[かんじ]`.
+    ///
+    /// A space is put in front of every segment with a reading (except at the start),
+    /// since Anki treats everything up to the previous space as the base text.
+    pub fn to_anki(&self) -> String {
+        let mut out = String::new();
+        for segment in &self.0 {
+            match &segment.reading {
+                Some(reading) => {
+                    if !out.is_empty() {
+                        out.push(' ');
+                    }
+                    out.push_str(&segment.text);
+                    out.push('[');
+                    out.push_str(reading);
+                    out.push(']');
+                }
+                None => out.push_str(&segment.text),
+            }
+        }
+        out
+    }
+
+    /// The written form, without readings.
+    pub fn text(&self) -> String {
+        self.0.iter().map(|s| s.text.as_str()).collect()
+    }
+}
+
+impl TermHeadword {
+    /// Splits this headword's term into [FuriganaSegment]s using its reading.
+    pub fn furigana(&self) -> Furigana {
+        Furigana::distribute(&self.term, &self.reading)
+    }
+
+    pub fn furigana_html(&self) -> String {
+        self.furigana().to_html()
+    }
+
+    pub fn furigana_anki(&self) -> String {
+        self.furigana().to_anki()
+    }
+}
+
+impl TermDictionaryEntry {
+    /// Furigana of the primary (first) headword, `None` if the entry has no headwords.
+    pub fn furigana(&self) -> Option<Furigana> {
+        self.headwords.first().map(TermHeadword::furigana)
+    }
+
+    /// Furigana of every headword, in headword order.
+    pub fn headwords_furigana(&self) -> Vec<Furigana> {
+        self.headwords.iter().map(TermHeadword::furigana).collect()
+    }
+}
+
+/// A run of either kana or non-kana characters of a term.
+struct TextGroup {
+    text: String,
+    /// Hiragana form of a kana group, `None` for non-kana groups.
+    normalized: Option<Vec<char>>,
+    char_len: usize,
+}
+
+fn group_by_kana(term: &str) -> Vec<TextGroup> {
+    let mut groups: Vec<TextGroup> = Vec::new();
+    for c in term.chars() {
+        let kana = is_kana(c);
+        match groups.last_mut() {
+            Some(group) if group.normalized.is_some() == kana => {
+                group.text.push(c);
+                group.char_len += 1;
+                if let Some(normalized) = group.normalized.as_mut() {
+                    normalized.push(katakana_char_to_hiragana(c));
+                }
+            }
+            _ => groups.push(TextGroup {
+                text: c.to_string(),
+                normalized: kana.then(|| vec![katakana_char_to_hiragana(c)]),
+                char_len: 1,
+            }),
+        }
+    }
+    groups
+}
+
+fn starts_with_normalized(reading: &[char], prefix: &[char]) -> bool {
+    reading.len() >= prefix.len()
+        && reading
+            .iter()
+            .zip(prefix)
+            .all(|(r, p)| katakana_char_to_hiragana(*r) == *p)
+}
+
+/// Returns `None` if `reading` can't be distributed over `groups` unambiguously.
+fn segmentize(reading: &[char], groups: &[TextGroup]) -> Option<Vec<FuriganaSegment>> {
+    let Some((group, rest)) = groups.split_first() else {
+        return reading.is_empty().then(Vec::new);
+    };
+    if let Some(normalized) = &group.normalized {
+        if !starts_with_normalized(reading, normalized) {
+            return None;
+        }
+        let mut segments = segmentize(&reading[group.char_len..], rest)?;
+        segments.insert(0, FuriganaSegment::plain(group.text.clone()));
+        return Some(segments);
+    }
+
+    // every kanji reads as at least one kana
+    let mut result = None;
+    for i in (group.char_len..=reading.len()).rev() {
+        if let Some(mut segments) = segmentize(&reading[i..], rest) {
+            if result.is_some() {
+                return None;
+            }
+            let group_reading: String = reading[..i].iter().collect();
+            segments.insert(
+                0,
+                FuriganaSegment::new(group.text.clone(), Some(group_reading)),
+            );
+            result = Some(segments);
+        }
+        if rest.is_empty() {
+            break;
+        }
+    }
+    result
+}
+
+/// Splits off the kana prefix and suffix shared by `term` and `reading`,
+/// and gives the rest of the term the rest of the reading.
+fn distribute_fallback(term: &str, reading: &[char]) -> Vec<FuriganaSegment> {
+    let term: Vec<char> = term.chars().collect();
+    let eq = |a: char, b: char| {
+        is_kana(a) && katakana_char_to_hiragana(a) == katakana_char_to_hiragana(b)
+    };
+
+    let max = term.len().min(reading.len());
+    let prefix = (0..max).take_while(|&i| eq(term[i], reading[i])).count();
+    let suffix = (0..max - prefix)
+        .take_while(|&i| eq(term[term.len() - 1 - i], reading[reading.len() - 1 - i]))
+        .count();
+
+    let collect = |chars: &[char]| chars.iter().collect::<String>();
+    let middle_term = &term[prefix..term.len() - suffix];
+    let middle_reading = &reading[prefix..reading.len() - suffix];
+
+    let mut segments = Vec::with_capacity(3);
+    if prefix > 0 {
+        segments.push(FuriganaSegment::plain(collect(&term[..prefix])));
+    }
+    match (middle_term.is_empty(), middle_reading.is_empty()) {
+        (true, _) => {}
+        (false, true) => segments.push(FuriganaSegment::plain(collect(middle_term))),
+        (false, false) => segments.push(FuriganaSegment::new(
+            collect(middle_term),
+            Some(collect(middle_reading)),
+        )),
+    }
+    if suffix > 0 {
+        segments.push(FuriganaSegment::plain(collect(
+            &term[term.len() - suffix..],
+        )));
+    }
+    segments
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod furigana_tests {
+    use super::*;
+
+    fn pairs(term: &str, reading: &str) -> Vec<(String, Option<String>)> {
+        Furigana::distribute(term, reading)
+            .into_segments()
+            .into_iter()
+            .map(|s| (s.text, s.reading))
+            .collect()
+    }
+
+    fn seg(text: &str, reading: Option<&str>) -> (String, Option<String>) {
+        (text.to_string(), reading.map(str::to_string))
+    }
+
+    #[test]
+    fn whole_kanji() {
+        assert_eq!(
+            pairs("日本語", "にほんご"),
+            [seg("日本語", Some("にほんご"))]
+        );
+    }
+
+    #[test]
+    fn okurigana() {
+        assert_eq!(
+            pairs("食べる", "たべる"),
+            [seg("食", Some("た")), seg("べる", None)]
+        );
+        assert_eq!(
+            pairs("お茶", "おちゃ"),
+            [seg("お", None), seg("茶", Some("ちゃ"))]
+        );
+        assert_eq!(
+            pairs("取り扱い", "とりあつかい"),
+            [
+                seg("取", Some("と")),
+                seg("り", None),
+                seg("扱", Some("あつか")),
+                seg("い", None)
+            ]
+        );
+    }
+
+    #[test]
+    fn kana_only() {
+        assert_eq!(pairs("ひらがな", "ひらがな"), [seg("ひらがな", None)]);
+        assert_eq!(pairs("ひらがな", ""), [seg("ひらがな", None)]);
+        // katakana term with a hiragana reading
+        assert_eq!(pairs("パン", "ぱん"), [seg("パン", None)]);
+    }
+
+    #[test]
+    fn mixed_katakana() {
+        assert_eq!(
+            pairs("アメリカ人", "あめりかじん"),
+            [seg("アメリカ", None), seg("人", Some("じん"))]
+        );
+    }
+
+    #[test]
+    fn unmatched_falls_back() {
+        // `ヶ` is read as `か`, so the kana of the term don't appear in the reading
+        assert_eq!(
+            pairs("一ヶ月", "いっかげつ"),
+            [seg("一ヶ月", Some("いっかげつ"))]
+        );
+        assert_eq!(
+            pairs("ケ月だ", "かげつだ"),
+            [seg("ケ月", Some("かげつ")), seg("だ", None)]
+        );
+    }
+
+    #[test]
+    fn output_formats() {
+        let furigana = Furigana::distribute("お茶", "おちゃ");
+        assert_eq!(furigana.to_anki(), "お 茶[ちゃ]");
+        assert_eq!(furigana.to_html(), "お<ruby>茶<rt>ちゃ</rt></ruby>");
+        assert_eq!(furigana.text(), "お茶");
+        assert_eq!(
+            Furigana::distribute("漢字", "かんじ").to_anki(),
+            "漢字[かんじ]"
+        );
+    }
+}
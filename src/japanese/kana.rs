@@ -0,0 +1,87 @@
+//! Character classification and conversion helpers for kana.
+
+const HIRAGANA_START: u32 = 0x3041;
+const HIRAGANA_END: u32 = 0x3096;
+const KATAKANA_START: u32 = 0x30A1;
+const KATAKANA_END: u32 = 0x30FA;
+/// Offset between a katakana character and its hiragana counterpart.
+const KATAKANA_HIRAGANA_OFFSET: u32 = 0x60;
+/// The last katakana (ヶ) that has a hiragana counterpart.
+const KATAKANA_CONVERTIBLE_END: u32 = 0x30F6;
+
+/// `ー`
+pub const PROLONGED_SOUND_MARK: char = 'ー';
+
+pub fn is_hiragana(c: char) -> bool {
+    matches!(c as u32, HIRAGANA_START..=HIRAGANA_END | 0x309D..=0x309E)
+}
+
+pub fn is_katakana(c: char) -> bool {
+    matches!(c as u32, KATAKANA_START..=KATAKANA_END | 0x30FC..=0x30FE | 0x31F0..=0x31FF)
+}
+
+/// Hiragana, katakana or the prolonged sound mark `ー`.
+pub fn is_kana(c: char) -> bool {
+    is_hiragana(c) || is_katakana(c)
+}
+
+/// CJK ideographs, including `々` and `〆`.
+pub fn is_kanji(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x4E00..=0x9FFF | 0x3400..=0x4DBF | 0xF900..=0xFAFF | 0x20000..=0x2FA1F | 0x3005 | 0x3006
+    )
+}
+
+pub fn is_kana_str(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(is_kana)
+}
+
+/// Converts a single katakana character to hiragana, leaving anything else untouched.
+pub fn katakana_char_to_hiragana(c: char) -> char {
+    let code = c as u32;
+    if (KATAKANA_START..=KATAKANA_CONVERTIBLE_END).contains(&code) {
+        return char::from_u32(code - KATAKANA_HIRAGANA_OFFSET).unwrap_or(c);
+    }
+    c
+}
+
+/// Converts a single hiragana character to katakana, leaving anything else untouched.
+pub fn hiragana_char_to_katakana(c: char) -> char {
+    let code = c as u32;
+    if (HIRAGANA_START..=HIRAGANA_END).contains(&code) {
+        return char::from_u32(code + KATAKANA_HIRAGANA_OFFSET).unwrap_or(c);
+    }
+    c
+}
+
+pub fn katakana_to_hiragana(text: &str) -> String {
+    text.chars().map(katakana_char_to_hiragana).collect()
+}
+
+pub fn hiragana_to_katakana(text: &str) -> String {
+    text.chars().map(hiragana_char_to_katakana).collect()
+}
+
+#[cfg(test)]
+mod kana_tests {
+    use super::*;
+
+    #[test]
+    fn classify() {
+        assert!(is_hiragana('あ'));
+        assert!(is_katakana('ア'));
+        assert!(is_kana('ー'));
+        assert!(!is_kana('漢'));
+        assert!(is_kanji('漢'));
+        assert!(is_kanji('々'));
+        assert!(!is_kanji('a'));
+    }
+
+    #[test]
+    fn convert() {
+        assert_eq!(katakana_to_hiragana("カタカナー"), "かたかなー");
+        assert_eq!(hiragana_to_katakana("ひらがな"), "ヒラガナ");
+        assert_eq!(katakana_to_hiragana("ヴ"), "ゔ");
+    }
+}
@@ -0,0 +1,10 @@
+//! Japanese specific text utilities.
+
+pub mod furigana;
+pub mod kana;
+pub mod pitch;
+pub mod romaji;
+
+pub use furigana::{Furigana, FuriganaSegment};
+pub use pitch::{PitchAccentInfo, PitchPattern};
+pub use romaji::{kana_to_romaji, romaji_to_kana, RomajiSystem};
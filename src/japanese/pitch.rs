@@ -0,0 +1,371 @@
+//! Pitch accent analysis: morae, downstep positions, pattern names and rendering.
+//!
+//! Downstep positions follow the Yomitan dictionary format: `0` is heiban (no downstep),
+//! and `n` means the pitch drops after the `n`th mora. Nasal and devoiced positions are
+//! 1-based mora indices.
+
+use serde::{Deserialize, Serialize};
+use yomichan_importer::dictionary_database::{PitchAccent, Pronunciation};
+
+use crate::{
+    models::dictionary::{TermDictionaryEntry, TermPronunciation},
+    settings::core::GeneralOptions,
+};
+
+/// Small kana that combine with the preceding kana into a single mora.
+const SMALL_KANA: &str = "ぁぃぅぇぉゃゅょゎァィゥェォャュョヮㇰㇱㇲㇳㇴㇵㇶㇷㇸㇹㇺㇻㇼㇽㇾㇿ";
+
+/// Splits a kana reading into morae, e.g. `"きょう"` into `["きょ", "う"]`.
+///
+/// `っ`, `ん` and `ー` count as morae of their own.
+pub fn split_morae(reading: &str) -> Vec<String> {
+    let mut morae: Vec<String> = Vec::new();
+    for c in reading.chars() {
+        match morae.last_mut() {
+            Some(last) if SMALL_KANA.contains(c) => last.push(c),
+            _ => morae.push(c.to_string()),
+        }
+    }
+    morae
+}
+
+/// The name of a pitch accent pattern.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PitchPattern {
+    /// 平板: low-high, no downstep (even on a following particle).
+    Heiban,
+    /// 頭高: high on the first mora only.
+    Atamadaka,
+    /// 中高: drops in the middle of the word.
+    Nakadaka,
+    /// 尾高: drops after the last mora, i.e. on a following particle.
+    Odaka,
+}
+
+impl PitchPattern {
+    /// Classifies a downstep position for a word of `mora_count` morae.
+    pub fn classify(position: usize, mora_count: usize) -> Self {
+        match position {
+            0 => PitchPattern::Heiban,
+            1 => PitchPattern::Atamadaka,
+            p if p >= mora_count => PitchPattern::Odaka,
+            _ => PitchPattern::Nakadaka,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PitchPattern::Heiban => "heiban",
+            PitchPattern::Atamadaka => "atamadaka",
+            PitchPattern::Nakadaka => "nakadaka",
+            PitchPattern::Odaka => "odaka",
+        }
+    }
+
+    pub fn as_japanese(&self) -> &'static str {
+        match self {
+            PitchPattern::Heiban => "平板",
+            PitchPattern::Atamadaka => "頭高",
+            PitchPattern::Nakadaka => "中高",
+            PitchPattern::Odaka => "尾高",
+        }
+    }
+}
+
+/// Whether the mora at `index` (0-based) is high for the downstep `position`.
+///
+/// `index == mora_count` is the following particle.
+pub fn is_mora_pitch_high(index: usize, position: usize) -> bool {
+    match position {
+        0 => index > 0,
+        1 => index < 1,
+        _ => index > 0 && index < position,
+    }
+}
+
+/// A pitch accent of a single reading, ready to be displayed.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PitchAccentInfo {
+    pub reading: String,
+    pub morae: Vec<String>,
+    /// The downstep position, `0` for heiban.
+    pub position: usize,
+    /// 1-based indices of morae pronounced nasally (e.g. `が` as `か゚`).
+    pub nasal_positions: Vec<usize>,
+    /// 1-based indices of devoiced morae.
+    pub devoice_positions: Vec<usize>,
+    pub pattern: PitchPattern,
+}
+
+impl PitchAccentInfo {
+    pub fn new(reading: &str, position: usize) -> Self {
+        let morae = split_morae(reading);
+        let pattern = PitchPattern::classify(position, morae.len());
+        Self {
+            reading: reading.to_string(),
+            morae,
+            position,
+            nasal_positions: Vec::new(),
+            devoice_positions: Vec::new(),
+            pattern,
+        }
+    }
+
+    pub fn from_pitch_accent(reading: &str, pitch: &PitchAccent) -> Self {
+        let mut info = Self::new(reading, pitch.position as usize);
+        info.nasal_positions = pitch.nasal_positions.iter().map(|p| *p as usize).collect();
+        info.devoice_positions = pitch
+            .devoice_positions
+            .iter()
+            .map(|p| *p as usize)
+            .collect();
+        info
+    }
+
+    pub fn mora_count(&self) -> usize {
+        self.morae.len()
+    }
+
+    /// High/low pitch of every mora, followed by the pitch of a following particle.
+    pub fn pitches(&self) -> Vec<bool> {
+        (0..=self.morae.len())
+            .map(|i| is_mora_pitch_high(i, self.position))
+            .collect()
+    }
+
+    /// The mora as it should be displayed, with nasal morae written with `゜`.
+    fn display_mora(&self, index: usize) -> String {
+        let mora = &self.morae[index];
+        if !self.nasal_positions.contains(&(index + 1)) {
+            return mora.clone();
+        }
+        let mut chars = mora.chars();
+        match chars.next() {
+            Some(first) => {
+                let mut nasal = String::new();
+                nasal.push(remove_dakuten(first));
+                nasal.push('\u{309A}');
+                nasal.extend(chars);
+                nasal
+            }
+            None => mora.clone(),
+        }
+    }
+
+    /// The reading with `ꜜ` after the mora the pitch drops after, e.g. `はしꜜ`.
+    /// Heiban readings have no marker.
+    pub fn downstep_notation(&self) -> String {
+        let mut text = String::new();
+        for i in 0..self.morae.len() {
+            text.push_str(&self.display_mora(i));
+            if i + 1 == self.position {
+                text.push('ꜜ');
+            }
+        }
+        text
+    }
+
+    /// The downstep position in brackets, e.g. `[2]`.
+    pub fn position_notation(&self) -> String {
+        format!("[{}]", self.position)
+    }
+
+    /// The reading as `<span>`s, with an overline class on high morae and a marker class
+    /// on the mora before the downstep.
+    pub fn to_html(&self) -> String {
+        let pitches = self.pitches();
+        let mut html = String::from(r#"<span class="pitch-accent-characters">"#);
+        for i in 0..self.morae.len() {
+            let mut classes = vec![match pitches[i] {
+                true => "pitch-high",
+                false => "pitch-low",
+            }];
+            if pitches[i] && !pitches[i + 1] {
+                classes.push("pitch-downstep");
+            }
+            if self.devoice_positions.contains(&(i + 1)) {
+                classes.push("pitch-devoice");
+            }
+            if self.nasal_positions.contains(&(i + 1)) {
+                classes.push("pitch-nasal");
+            }
+            html.push_str(&format!(
+                r#"<span class="{}">{}</span>"#,
+                classes.join(" "),
+                self.display_mora(i)
+            ));
+        }
+        html.push_str("</span>");
+        html
+    }
+
+    /// An svg line graph of the pitch, with a hollow dot for the following particle.
+    pub fn to_svg(&self) -> String {
+        const STEP: usize = 50;
+        const HIGH_Y: usize = 25;
+        const LOW_Y: usize = 75;
+        let pitches = self.pitches();
+        let width = STEP * pitches.len();
+        let points: Vec<(usize, usize)> = pitches
+            .iter()
+            .enumerate()
+            .map(|(i, high)| (STEP / 2 + i * STEP, if *high { HIGH_Y } else { LOW_Y }))
+            .collect();
+
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" class="pitch-accent-graph" viewBox="0 0 {width} 100" width="{width}" height="100">"#
+        );
+        let path: Vec<String> = points.iter().map(|(x, y)| format!("{x},{y}")).collect();
+        svg.push_str(&format!(
+            r#"<polyline class="pitch-accent-graph-line" points="{}" fill="none" stroke="currentColor" stroke-width="5"/>"#,
+            path.join(" ")
+        ));
+        let (particle, word) = points
+            .split_last()
+            .expect("[unexpected] pitches is never empty");
+        for (x, y) in word {
+            svg.push_str(&format!(
+                r#"<circle class="pitch-accent-graph-dot" cx="{x}" cy="{y}" r="15" fill="currentColor"/>"#
+            ));
+        }
+        let (x, y) = particle;
+        svg.push_str(&format!(
+            r#"<circle class="pitch-accent-graph-dot-particle" cx="{x}" cy="{y}" r="15" fill="none" stroke="currentColor" stroke-width="5"/>"#
+        ));
+        svg.push_str("</svg>");
+        svg
+    }
+
+    /// Renders the notations enabled in `general`
+    /// (`show_pitch_accent_downstep_notation`, `show_pitch_accent_position_notation`,
+    /// `show_pitch_accent_graph`), joined by spaces.
+    pub fn render(&self, general: &GeneralOptions) -> String {
+        let mut parts = Vec::new();
+        if general.show_pitch_accent_downstep_notation {
+            parts.push(self.to_html());
+        }
+        if general.show_pitch_accent_position_notation {
+            parts.push(self.position_notation());
+        }
+        if general.show_pitch_accent_graph {
+            parts.push(self.to_svg());
+        }
+        parts.join(" ")
+    }
+}
+
+impl TermPronunciation {
+    /// The pitch accents of this pronunciation for `reading` (the reading of the headword at
+    /// `headword_index`). Phonetic transcriptions are skipped.
+    pub fn pitch_accents(&self, reading: &str) -> Vec<PitchAccentInfo> {
+        self.pronunciations
+            .iter()
+            .filter_map(|p| match p {
+                Pronunciation::PitchAccent(pitch) => {
+                    Some(PitchAccentInfo::from_pitch_accent(reading, pitch))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl TermDictionaryEntry {
+    /// Every pitch accent of the entry, paired with the index of the headword it belongs to.
+    pub fn pitch_accents(&self) -> Vec<(usize, PitchAccentInfo)> {
+        let mut accents = Vec::new();
+        for pronunciation in &self.pronunciations {
+            let Some(headword) = self.headwords.get(pronunciation.headword_index) else {
+                continue;
+            };
+            for info in pronunciation.pitch_accents(&headword.reading) {
+                accents.push((pronunciation.headword_index, info));
+            }
+        }
+        accents
+    }
+}
+
+/// `が` -> `か`, for the voiced rows whose unvoiced kana is one code point lower.
+fn remove_dakuten(c: char) -> char {
+    const VOICED: &str =
+        "がぎぐげござじずぜぞだぢづでどばびぶべぼガギグゲゴザジズゼゾダヂヅデドバビブベボ";
+    if VOICED.contains(c) {
+        return char::from_u32(c as u32 - 1).unwrap_or(c);
+    }
+    c
+}
+
+#[cfg(test)]
+mod pitch_tests {
+    use super::*;
+
+    #[test]
+    fn morae() {
+        assert_eq!(split_morae("きょう"), ["きょ", "う"]);
+        assert_eq!(split_morae("がっこう"), ["が", "っ", "こ", "う"]);
+        assert_eq!(split_morae("ティーシャツ"), ["ティ", "ー", "シャ", "ツ"]);
+    }
+
+    #[test]
+    fn patterns() {
+        assert_eq!(
+            PitchAccentInfo::new("さくら", 0).pattern,
+            PitchPattern::Heiban
+        );
+        assert_eq!(
+            PitchAccentInfo::new("いのち", 1).pattern,
+            PitchPattern::Atamadaka
+        );
+        assert_eq!(
+            PitchAccentInfo::new("こころ", 2).pattern,
+            PitchPattern::Nakadaka
+        );
+        assert_eq!(PitchAccentInfo::new("はし", 2).pattern, PitchPattern::Odaka);
+    }
+
+    #[test]
+    fn pitches() {
+        // low-high-high, particle high
+        assert_eq!(
+            PitchAccentInfo::new("さくら", 0).pitches(),
+            [false, true, true, true]
+        );
+        // low-high, particle low
+        assert_eq!(
+            PitchAccentInfo::new("はし", 2).pitches(),
+            [false, true, false]
+        );
+        assert_eq!(
+            PitchAccentInfo::new("いのち", 1).pitches(),
+            [true, false, false, false]
+        );
+    }
+
+    #[test]
+    fn notation() {
+        let mut info = PitchAccentInfo::new("かがみ", 3);
+        assert_eq!(info.downstep_notation(), "かがみꜜ");
+        assert_eq!(info.position_notation(), "[3]");
+        info.nasal_positions = vec![2];
+        assert_eq!(info.downstep_notation(), "かか\u{309A}みꜜ");
+        assert_eq!(
+            PitchAccentInfo::new("さくら", 0).downstep_notation(),
+            "さくら"
+        );
+    }
+
+    #[test]
+    fn html_and_svg() {
+        let mut info = PitchAccentInfo::new("はし", 1);
+        info.devoice_positions = vec![2];
+        assert_eq!(
+            info.to_html(),
+            r#"<span class="pitch-accent-characters"><span class="pitch-high pitch-downstep">は</span><span class="pitch-low pitch-devoice">し</span></span>"#
+        );
+        let svg = info.to_svg();
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<circle").count(), 3);
+        assert!(svg.contains(r#"points="25,25 75,75 125,75""#));
+    }
+}
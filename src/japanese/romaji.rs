@@ -0,0 +1,371 @@
+//! Kana <-> romaji conversion.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    japanese::kana::{is_kana, katakana_char_to_hiragana, PROLONGED_SOUND_MARK},
+    Yomichan,
+};
+
+/// The romanization system used by [kana_to_romaji].
+///
+/// Both use IME spellings where the system would give two kana the same romaji,
+/// so every romanization converts back to the same kana with [romaji_to_kana]:
+/// `ぢ` -> `di`, `づ` -> `du`, `を` -> `wo`, `でぃ` -> `dhi`, and small kana with an `x`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RomajiSystem {
+    /// `し` -> `shi`, `ち` -> `chi`, `つ` -> `tsu`, `ふ` -> `fu`, `じゃ` -> `ja`.
+    #[default]
+    Hepburn,
+    /// `し` -> `si`, `ち` -> `ti`, `つ` -> `tu`, `ふ` -> `hu`, `じゃ` -> `zya`.
+    Kunrei,
+}
+
+/// `(hiragana, hepburn, kunrei)`
+#[rustfmt::skip]
+const KANA_ROMAJI: &[(&str, &str, &str)] = &[
+    ("あ", "a", "a"), ("い", "i", "i"), ("う", "u", "u"), ("え", "e", "e"), ("お", "o", "o"),
+    ("か", "ka", "ka"), ("き", "ki", "ki"), ("く", "ku", "ku"), ("け", "ke", "ke"), ("こ", "ko", "ko"),
+    ("さ", "sa", "sa"), ("し", "shi", "si"), ("す", "su", "su"), ("せ", "se", "se"), ("そ", "so", "so"),
+    ("た", "ta", "ta"), ("ち", "chi", "ti"), ("つ", "tsu", "tu"), ("て", "te", "te"), ("と", "to", "to"),
+    ("な", "na", "na"), ("に", "ni", "ni"), ("ぬ", "nu", "nu"), ("ね", "ne", "ne"), ("の", "no", "no"),
+    ("は", "ha", "ha"), ("ひ", "hi", "hi"), ("ふ", "fu", "hu"), ("へ", "he", "he"), ("ほ", "ho", "ho"),
+    ("ま", "ma", "ma"), ("み", "mi", "mi"), ("む", "mu", "mu"), ("め", "me", "me"), ("も", "mo", "mo"),
+    ("や", "ya", "ya"), ("ゆ", "yu", "yu"), ("よ", "yo", "yo"),
+    ("ら", "ra", "ra"), ("り", "ri", "ri"), ("る", "ru", "ru"), ("れ", "re", "re"), ("ろ", "ro", "ro"),
+    ("わ", "wa", "wa"), ("ゐ", "wyi", "wyi"), ("ゑ", "wye", "wye"), ("を", "wo", "wo"),
+    ("が", "ga", "ga"), ("ぎ", "gi", "gi"), ("ぐ", "gu", "gu"), ("げ", "ge", "ge"), ("ご", "go", "go"),
+    ("ざ", "za", "za"), ("じ", "ji", "zi"), ("ず", "zu", "zu"), ("ぜ", "ze", "ze"), ("ぞ", "zo", "zo"),
+    ("だ", "da", "da"), ("ぢ", "di", "di"), ("づ", "du", "du"), ("で", "de", "de"), ("ど", "do", "do"),
+    ("ば", "ba", "ba"), ("び", "bi", "bi"), ("ぶ", "bu", "bu"), ("べ", "be", "be"), ("ぼ", "bo", "bo"),
+    ("ぱ", "pa", "pa"), ("ぴ", "pi", "pi"), ("ぷ", "pu", "pu"), ("ぺ", "pe", "pe"), ("ぽ", "po", "po"),
+    ("ゔ", "vu", "vu"),
+    ("きゃ", "kya", "kya"), ("きゅ", "kyu", "kyu"), ("きょ", "kyo", "kyo"),
+    ("しゃ", "sha", "sya"), ("しゅ", "shu", "syu"), ("しょ", "sho", "syo"), ("しぇ", "she", "sye"),
+    ("ちゃ", "cha", "tya"), ("ちゅ", "chu", "tyu"), ("ちょ", "cho", "tyo"), ("ちぇ", "che", "tye"),
+    ("にゃ", "nya", "nya"), ("にゅ", "nyu", "nyu"), ("にょ", "nyo", "nyo"),
+    ("ひゃ", "hya", "hya"), ("ひゅ", "hyu", "hyu"), ("ひょ", "hyo", "hyo"),
+    ("みゃ", "mya", "mya"), ("みゅ", "myu", "myu"), ("みょ", "myo", "myo"),
+    ("りゃ", "rya", "rya"), ("りゅ", "ryu", "ryu"), ("りょ", "ryo", "ryo"),
+    ("ぎゃ", "gya", "gya"), ("ぎゅ", "gyu", "gyu"), ("ぎょ", "gyo", "gyo"),
+    ("じゃ", "ja", "zya"), ("じゅ", "ju", "zyu"), ("じょ", "jo", "zyo"), ("じぇ", "je", "zye"),
+    ("ぢゃ", "dya", "dya"), ("ぢゅ", "dyu", "dyu"), ("ぢょ", "dyo", "dyo"),
+    ("びゃ", "bya", "bya"), ("びゅ", "byu", "byu"), ("びょ", "byo", "byo"),
+    ("ぴゃ", "pya", "pya"), ("ぴゅ", "pyu", "pyu"), ("ぴょ", "pyo", "pyo"),
+    ("ふぁ", "fa", "fa"), ("ふぃ", "fi", "fi"), ("ふぇ", "fe", "fe"), ("ふぉ", "fo", "fo"),
+    ("てぃ", "thi", "thi"), ("でぃ", "dhi", "dhi"), ("とぅ", "twu", "twu"), ("どぅ", "dwu", "dwu"),
+    ("うぃ", "wi", "wi"), ("うぇ", "we", "we"), ("うぉ", "who", "who"),
+    ("ゔぁ", "va", "va"), ("ゔぃ", "vi", "vi"), ("ゔぇ", "ve", "ve"), ("ゔぉ", "vo", "vo"),
+    ("つぁ", "tsa", "tsa"), ("つぃ", "tsi", "tsi"), ("つぇ", "tse", "tse"), ("つぉ", "tso", "tso"),
+    ("ぁ", "xa", "xa"), ("ぃ", "xi", "xi"), ("ぅ", "xu", "xu"), ("ぇ", "xe", "xe"), ("ぉ", "xo", "xo"),
+    ("ゃ", "xya", "xya"), ("ゅ", "xyu", "xyu"), ("ょ", "xyo", "xyo"), ("ゎ", "xwa", "xwa"),
+];
+
+/// Romaji spellings only accepted as input by [romaji_to_kana],
+/// none of which [kana_to_romaji] writes.
+#[rustfmt::skip]
+const ROMAJI_INPUT_ALIASES: &[(&str, &str)] = &[
+    ("jya", "じゃ"), ("jyu", "じゅ"), ("jyo", "じょ"), ("cya", "ちゃ"), ("cyu", "ちゅ"),
+    ("cyo", "ちょ"), ("dzu", "づ"),
+    ("la", "ぁ"), ("li", "ぃ"), ("lu", "ぅ"), ("le", "ぇ"), ("lo", "ぉ"),
+    ("lya", "ゃ"), ("lyu", "ゅ"), ("lyo", "ょ"), ("xtu", "っ"), ("xtsu", "っ"),
+];
+
+fn kana_romaji(kana: &str, system: RomajiSystem) -> Option<&'static str> {
+    KANA_ROMAJI
+        .iter()
+        .find(|(k, _, _)| *k == kana)
+        .map(|(_, hepburn, kunrei)| match system {
+            RomajiSystem::Hepburn => *hepburn,
+            RomajiSystem::Kunrei => *kunrei,
+        })
+}
+
+fn is_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'i' | 'u' | 'e' | 'o')
+}
+
+/// Converts hiragana and katakana to romaji. Anything that isn't kana is kept as is.
+///
+/// - `っ` doubles the following consonant (`tch` for `っち` in Hepburn).
+/// - `ー` repeats the previous vowel.
+/// - `ん` before a vowel or `y` is written `n'`.
+///
+/// ```
+/// # use yomichan_rs::japanese::romaji::{kana_to_romaji, RomajiSystem};
+/// assert_eq!(kana_to_romaji("きっぷ", RomajiSystem::Hepburn), "kippu");
+/// assert_eq!(kana_to_romaji("ラーメン", RomajiSystem::Hepburn), "raamen");
+/// assert_eq!(kana_to_romaji("きんえん", RomajiSystem::Hepburn), "kin'en");
+/// assert_eq!(kana_to_romaji("しんぶん", RomajiSystem::Kunrei), "sinbun");
+/// ```
+pub fn kana_to_romaji(text: &str, system: RomajiSystem) -> String {
+    let chars: Vec<char> = text.chars().map(katakana_char_to_hiragana).collect();
+    let mut out = String::with_capacity(text.len());
+    let mut sokuon = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == 'っ' {
+            sokuon = true;
+            i += 1;
+            continue;
+        }
+        if c == 'ん' {
+            out.push('n');
+            let next = next_syllable(&chars[i + 1..], system)
+                .and_then(|(romaji, _)| romaji.chars().next());
+            if next.is_some_and(|n| is_vowel(n) || n == 'y') {
+                out.push('\'');
+            }
+            i += 1;
+            continue;
+        }
+        if c == PROLONGED_SOUND_MARK {
+            if let Some(vowel) = out.chars().next_back().filter(|v| is_vowel(*v)) {
+                out.push(vowel);
+            } else {
+                out.push('-');
+            }
+            i += 1;
+            continue;
+        }
+        match next_syllable(&chars[i..], system) {
+            Some((romaji, len)) => {
+                if sokuon {
+                    match (system, romaji.starts_with("ch")) {
+                        (RomajiSystem::Hepburn, true) => out.push('t'),
+                        _ => out.extend(romaji.chars().next().filter(|c| !is_vowel(*c))),
+                    }
+                }
+                out.push_str(romaji);
+                i += len;
+            }
+            None => {
+                out.push(chars[i]);
+                i += 1;
+            }
+        }
+        sokuon = false;
+    }
+    out
+}
+
+/// The romaji of the (possibly two character) syllable at the start of `chars`.
+fn next_syllable(chars: &[char], system: RomajiSystem) -> Option<(&'static str, usize)> {
+    if chars.len() >= 2 {
+        let pair: String = chars[..2].iter().collect();
+        if let Some(romaji) = kana_romaji(&pair, system) {
+            return Some((romaji, 2));
+        }
+    }
+    let single = chars.first()?.to_string();
+    kana_romaji(&single, system).map(|romaji| (romaji, 1))
+}
+
+/// Converts romaji (Hepburn, Kunrei and common IME spellings) to hiragana.
+/// Characters that can't be converted are kept as is.
+///
+/// ```
+/// # use yomichan_rs::japanese::romaji::romaji_to_kana;
+/// assert_eq!(romaji_to_kana("konnichiha"), "こんにちは");
+/// assert_eq!(romaji_to_kana("kippu"), "きっぷ");
+/// assert_eq!(romaji_to_kana("kin'en"), "きんえん");
+/// assert_eq!(romaji_to_kana("ra-men"), "らーめん");
+/// ```
+pub fn romaji_to_kana(text: &str) -> String {
+    let chars: Vec<char> = text.to_lowercase().chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c == '-' {
+            out.push(PROLONGED_SOUND_MARK);
+            i += 1;
+            continue;
+        }
+        if c == 'n' {
+            let after = chars.get(i + 2).copied();
+            match next {
+                Some('\'') => {
+                    out.push('ん');
+                    i += 2;
+                    continue;
+                }
+                // `nn` before a vowel is `ん` + `n?`, e.g. "konnichiha"
+                Some('n') if !after.is_some_and(|a| is_vowel(a) || a == 'y') => {
+                    out.push('ん');
+                    i += 2;
+                    continue;
+                }
+                Some(n) if is_vowel(n) || n == 'y' => {}
+                _ => {
+                    out.push('ん');
+                    i += 1;
+                    continue;
+                }
+            }
+        }
+        let is_consonant = c.is_ascii_alphabetic() && !is_vowel(c);
+        if is_consonant && (next == Some(c) || (c == 't' && next == Some('c'))) {
+            out.push('っ');
+            i += 1;
+            continue;
+        }
+        match romaji_syllable(&chars[i..]) {
+            Some((kana, len)) => {
+                out.push_str(kana);
+                i += len;
+            }
+            None => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Converts romaji the way an IME does while typing: like [romaji_to_kana],
+/// but a trailing `n` stays unconverted, since it may still become `な`, `にゃ`, etc.
+///
+/// ```
+/// # use yomichan_rs::japanese::romaji::romaji_to_kana_ime;
+/// assert_eq!(romaji_to_kana_ime("kon"), "こn");
+/// assert_eq!(romaji_to_kana_ime("konn"), "こん");
+/// assert_eq!(romaji_to_kana_ime("taber"), "たべr");
+/// ```
+pub fn romaji_to_kana_ime(text: &str) -> String {
+    let lowercase = text.to_lowercase();
+    match lowercase.strip_suffix('n') {
+        Some(rest) if !rest.ends_with('n') => {
+            let mut kana = romaji_to_kana(rest);
+            kana.push('n');
+            kana
+        }
+        _ => romaji_to_kana(&lowercase),
+    }
+}
+
+/// The longest romaji syllable at the start of `chars`, as kana.
+fn romaji_syllable(chars: &[char]) -> Option<(&'static str, usize)> {
+    (1..=chars.len().min(4)).rev().find_map(|len| {
+        let candidate: String = chars[..len].iter().collect();
+        ROMAJI_INPUT_ALIASES
+            .iter()
+            .find(|(romaji, _)| *romaji == candidate)
+            .map(|(_, kana)| *kana)
+            .or_else(|| {
+                KANA_ROMAJI
+                    .iter()
+                    .find(|(_, hepburn, kunrei)| *hepburn == candidate || *kunrei == candidate)
+                    .map(|(kana, _, _)| *kana)
+            })
+            .map(|kana| (kana, len))
+    })
+}
+
+/// Whether `text` looks like romaji input: ascii letters (plus `'` and `-`) only.
+pub fn is_romaji(text: &str) -> bool {
+    !text.is_empty()
+        && text.chars().any(|c| c.is_ascii_alphabetic())
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphabetic() || matches!(c, '\'' | '-' | ' '))
+}
+
+/// Whether `text` contains any kana.
+pub fn contains_kana(text: &str) -> bool {
+    text.chars().any(is_kana)
+}
+
+impl Yomichan {
+    /// Live conversion for a search box: converts romaji to kana while the user types
+    /// (see [romaji_to_kana_ime]) if `general.enable_wanakana` is set and the profile
+    /// language is Japanese. Otherwise `text` is returned unchanged.
+    pub fn convert_search_input(&self, text: &str) -> String {
+        let enabled = self
+            .with_profile(|p| {
+                let general = &p.options().general;
+                general.enable_wanakana && general.language == "ja"
+            })
+            .unwrap_or(false);
+        match enabled && is_romaji(text) {
+            true => romaji_to_kana_ime(text),
+            false => text.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod romaji_tests {
+    use super::*;
+
+    #[test]
+    fn hepburn() {
+        let r = |t| kana_to_romaji(t, RomajiSystem::Hepburn);
+        assert_eq!(r("しんぶん"), "shinbun");
+        assert_eq!(r("ちゃ"), "cha");
+        assert_eq!(r("まっちゃ"), "matcha");
+        assert_eq!(r("がっこう"), "gakkou");
+        assert_eq!(r("ほんや"), "hon'ya");
+        assert_eq!(r("コーヒー"), "koohii");
+        assert_eq!(r("ジュース"), "juusu");
+        assert_eq!(r("ふじさん、"), "fujisan、");
+    }
+
+    #[test]
+    fn kunrei() {
+        let r = |t| kana_to_romaji(t, RomajiSystem::Kunrei);
+        assert_eq!(r("ふじさん"), "huzisan");
+        assert_eq!(r("まっちゃ"), "mattya");
+        assert_eq!(r("つづく"), "tuduku");
+    }
+
+    #[test]
+    fn to_kana() {
+        assert_eq!(romaji_to_kana("taberu"), "たべる");
+        assert_eq!(romaji_to_kana("shinbun"), "しんぶん");
+        assert_eq!(romaji_to_kana("sinbun"), "しんぶん");
+        assert_eq!(romaji_to_kana("hon'ya"), "ほんや");
+        assert_eq!(romaji_to_kana("matcha"), "まっちゃ");
+        assert_eq!(romaji_to_kana("Gakkou"), "がっこう");
+        assert_eq!(romaji_to_kana("onna"), "おんな");
+        assert_eq!(romaji_to_kana("kanji"), "かんじ");
+        assert_eq!(romaji_to_kana("wo"), "を");
+    }
+
+    #[test]
+    fn ime() {
+        assert_eq!(romaji_to_kana_ime("n"), "n");
+        assert_eq!(romaji_to_kana_ime("kan"), "かn");
+        assert_eq!(romaji_to_kana_ime("kanji"), "かんじ");
+        assert_eq!(romaji_to_kana_ime("hon'"), "ほん");
+    }
+
+    #[test]
+    fn roundtrip() {
+        for (kana, hepburn, kunrei) in KANA_ROMAJI {
+            assert_eq!(romaji_to_kana(hepburn), *kana, "{hepburn}");
+            assert_eq!(romaji_to_kana(kunrei), *kana, "{kunrei}");
+        }
+        let words = [
+            "きょうと",
+            "ちょっと",
+            "しゅくだい",
+            "ぎゅうにゅう",
+            "でぃすく",
+            "はなぢ",
+            "つづく",
+            "てぃっしゅ",
+            "ほんをよむ",
+            "うぉっか",
+        ];
+        for system in [RomajiSystem::Hepburn, RomajiSystem::Kunrei] {
+            for word in words {
+                let romaji = kana_to_romaji(word, system);
+                assert_eq!(romaji_to_kana(&romaji), word, "{romaji}");
+            }
+        }
+    }
+}
@@ -11,9 +11,11 @@ pub mod anki;
 pub mod audio;
 mod backend;
 pub mod database;
+pub mod japanese;
 pub mod models;
 pub mod scanner;
 pub mod settings;
+pub mod subtitles;
 pub mod translator;
 pub mod utils;
 
@@ -26,7 +28,7 @@ use std::sync::Arc;
 pub use crate::database::dictionary_importer;
 pub use crate::database::DictionaryService;
 pub use crate::models::dictionary::{
-    TermDefinition, TermDictionaryEntry, TermFrequency, TermPronunciation,
+    KanjiDictionaryEntry, TermDefinition, TermDictionaryEntry, TermFrequency, TermPronunciation,
 };
 pub use crate::scanner::core::{TermSearchResults, TermSearchResultsSegment, TextScanner};
 #[cfg(feature = "anki")]
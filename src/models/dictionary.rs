@@ -31,6 +31,15 @@ pub enum NumOrStr {
     Str(String),
 }
 
+impl std::fmt::Display for NumOrStr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NumOrStr::Num(num) => write!(f, "{num}"),
+            NumOrStr::Str(str) => f.write_str(str),
+        }
+    }
+}
+
 // /// Helper enum to match [TermMetaPitchAccent] data more accurately.
 // #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 // pub enum VecNumOrNum {
@@ -93,31 +102,31 @@ pub enum DictionaryEntryType {
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct KanjiStat {
     /// The name of the stat.
-    name: String,
+    pub name: String,
     /// The category of the stat.
-    category: String,
+    pub category: String,
     /// A description of the stat.
-    content: String,
+    pub content: String,
     /// A number indicating the sorting order of the stat.
-    order: u16,
+    pub order: u16,
     /// A score value for the stat.
-    score: u64,
+    pub score: u64,
     /// The name of the dictionary that the stat originated from.
-    dictionary: String,
+    pub dictionary: String,
     /// A value for the stat.
-    value: NumOrStr,
+    pub value: NumOrStr,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct KanjiFrequency {
-    index: u64,
-    dictionary: String,
-    dictionary_index: u16,
-    dictionary_priority: u16,
-    character: String,
-    frequency: NumOrStr,
-    display_value: Option<String>,
-    display_value_parsed: bool,
+    pub index: u64,
+    pub dictionary: String,
+    pub dictionary_index: u16,
+    pub dictionary_priority: u16,
+    pub character: String,
+    pub frequency: NumOrStr,
+    pub display_value: Option<String>,
+    pub display_value_parsed: bool,
 }
 
 /// An object with groups of stats about a kanji character.
@@ -128,23 +137,35 @@ pub type KanjiStatGroups = IndexMap<String, Vec<KanjiStat>>;
 pub struct KanjiDictionaryEntry {
     /// The type of the entry.
     /// Should be `"kanji"` in the json.
-    entry_type: DictionaryEntryType,
+    pub entry_type: DictionaryEntryType,
     /// The kanji character that was looked up.
-    character: String,
+    pub character: String,
     /// The name of the dictionary that the information originated from.
-    dictionary: String,
+    pub dictionary: String,
     /// Onyomi readings for the kanji character.
-    onyomi: Vec<String>,
+    pub onyomi: Vec<String>,
     /// Kunyomi readings for the kanji character.
-    kunyomi: Vec<String>,
+    pub kunyomi: Vec<String>,
     /// Tags for the kanji character.
-    tags: Vec<DictionaryTag>,
+    pub tags: Vec<DictionaryTag>,
     /// An object containing stats about the kanji character.
-    stats: KanjiStatGroups,
+    pub stats: KanjiStatGroups,
     /// Definitions for the kanji character.
-    definitions: Vec<String>,
+    pub definitions: Vec<String>,
     /// Frequency information for the kanji character.
-    frequencies: Vec<KanjiFrequency>,
+    pub frequencies: Vec<KanjiFrequency>,
+}
+
+impl KanjiDictionaryEntry {
+    /// The stat named `name` in any group, e.g. `"strokes"`.
+    pub fn stat(&self, name: &str) -> Option<&KanjiStat> {
+        self.stats.values().flatten().find(|stat| stat.name == name)
+    }
+
+    /// The `strokes` stat of kanji dictionaries like KANJIDIC.
+    pub fn stroke_count(&self) -> Option<String> {
+        self.stat("strokes").map(|stat| stat.value.to_string())
+    }
 }
 
 // #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -318,6 +339,10 @@ pub struct TermDictionaryEntry {
     pub score: i128,
     /// A sorting value based on the term's overall frequency.
     pub frequency_order: i128,
+    /// The combined rank of the term across the profile's `general.frequency_aggregation`
+    /// dictionaries (lower is more common).
+    /// - `None` if aggregation is disabled or none of the dictionaries list the term.
+    pub aggregate_frequency: Option<i128>,
     /// The user-defined alias of the dictionary this entry belongs to.
     pub dictionary_alias: String,
     /// The index of the source dictionary in the user's configured list.
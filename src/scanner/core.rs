@@ -6,10 +6,12 @@ use yomichan_importer::dictionary_database::TermSourceMatchType;
 
 use crate::{
     backend::FindTermsDetails,
-    database::DictionaryService,
+    database::{DictionaryService, WordStatus},
     // these do not exist in importer
     models::dictionary::{TermDictionaryEntry, TermSource},
+    scanner::{reading::annotate_readings, vocabulary::lemma_of},
     settings::core::ProfileOptions,
+    subtitles::SentenceCue,
     translator::core::{FindTermsMode, FindTermsResult, Translator},
     Yomichan,
 };
@@ -67,7 +69,46 @@ impl Yomichan {
         let profile = profile.read();
         let opts = profile.options();
         let res = self.backend.scanner.search_sentence(text, opts)?;
-        Some(SentenceParser::parse(res))
+        let mut segments = SentenceParser::parse(res);
+        self.backend.scanner.annotate_word_statuses(&mut segments);
+        annotate_readings(&mut segments, &opts.parsing);
+        Some(segments)
+    }
+
+    /// Looks up the terms at the start of `text` and returns one page of the sorted results.
+    ///
+    /// `offset` entries are skipped and at most `limit` entries are returned
+    /// (`None` falls back to the profile's `general.max_results`).
+    /// [FindTermsResult::total_results] holds the number of entries across all pages.
+    ///
+    /// Returns `None` if the current user profile cannot be accessed.
+    pub fn search_page(
+        &self,
+        text: &str,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Option<FindTermsResult> {
+        let profile = self.backend.get_current_profile().ok()?;
+        let profile = profile.read();
+        Some(
+            self.backend
+                .scanner
+                .find_terms_page(text, profile.options(), offset, limit),
+        )
+    }
+}
+
+/// The profile's `general.result_output_mode`.
+///
+/// `Merge` needs a main dictionary to group by, without one `Group` is used instead
+/// (and the fallback is logged at debug level, since this runs on every lookup).
+pub(crate) fn find_terms_mode(options: &ProfileOptions) -> FindTermsMode {
+    match options.general.result_output_mode {
+        FindTermsMode::Merge if options.general.main_dictionary.is_empty() => {
+            tracing::debug!("result output mode is Merge without a main dictionary, using Group");
+            FindTermsMode::Group
+        }
+        mode => mode,
     }
 }
 
@@ -84,9 +125,17 @@ pub struct TermSearchResultsSegment {
     /// - `Some(Arc<TermSearchResults>)` if the `text` was found in the dictionary.
     /// - `None` if the `text` is a passthrough (unrecognized) part of the original string.
     pub results: Option<Arc<TermSearchResults>>,
+    /// The user's [WordStatus] for the best (first) entry of `results`.
+    /// - `None` for passthrough segments.
+    pub word_status: Option<WordStatus>,
+    /// The reading of `text`, rendered in the profile's `parsing.reading_mode`.
+    /// - `None` if the mode is `None` or the text has no reading.
+    ///
+    /// See [TermSearchResultsSegment::reading_in].
+    pub reading: Option<String>,
 }
 
-struct SentenceParser {}
+pub(crate) struct SentenceParser {}
 impl SentenceParser {
     /// Parses the flat list of dictionary entries from `TermSearchResults`
     /// into a structured `Vec<TermSearchResultsSegment>` using a "longest match" algorithm.
@@ -103,6 +152,8 @@ impl SentenceParser {
             return vec![TermSearchResultsSegment {
                 text: results.sentence.text,
                 results: None,
+                word_status: None,
+                reading: None,
             }];
         }
 
@@ -165,6 +216,8 @@ impl SentenceParser {
                 parsed_sentence.push(TermSearchResultsSegment {
                     text: found_key.clone(),
                     results: Some(Arc::new(segment_results)),
+                    word_status: None,
+                    reading: None,
                 });
 
                 current_pos += found_key.len();
@@ -173,6 +226,8 @@ impl SentenceParser {
                 parsed_sentence.push(TermSearchResultsSegment {
                     text: char_str.clone(),
                     results: None,
+                    word_status: None,
+                    reading: None,
                 });
                 current_pos += char_str.len();
             }
@@ -196,10 +251,12 @@ impl SentenceParser {
 ///
 /// * text the full unchanged string looked up
 /// * offset: The character offset of the original search text within the full sentence text.
+/// * cue: The subtitle cue the sentence came from, if it was mined from subtitles.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Sentence {
     pub text: String,
     pub offset: usize,
+    pub cue: Option<SentenceCue>,
 }
 
 /// The final, structured result of a term search, containing all dictionary
@@ -222,6 +279,9 @@ pub struct TermSearchResults {
     pub sentence: Sentence,
 }
 
+/// Characters that end a sentence when scanning for sentence boundaries.
+pub(crate) const SENTENCE_TERMINATORS: &str = ".!?。？！\n";
+
 #[derive(thiserror::Error, Debug)]
 pub enum BuildNoteError {
     #[error("current profile has no anki deck selected")]
@@ -244,7 +304,7 @@ pub struct TextScanner {
 
     /// Whether a newline character should terminate a sentence.
     /// Corresponds to `_sentenceTerminateAtNewlines` in JS.
-    sentence_terminate_at_newlines: bool,
+    pub(crate) sentence_terminate_at_newlines: bool,
 
     /// The match type to use for finding terms (e.g., exact or prefix).
     /// Corresponds to `_matchTypePrefix` in JS.
@@ -266,6 +326,10 @@ impl TextScanner {
         }
     }
 
+    pub(crate) fn translator(&self) -> &Translator {
+        &self.translator
+    }
+
     /// Scans an entire sentence to find all possible dictionary terms within it.
     ///
     /// This method implements the core logic for the full-sentence analysis view.
@@ -331,10 +395,39 @@ impl TextScanner {
             sentence: Sentence {
                 text: sentence_text.to_string(),
                 offset: 0,
+                cue: None,
             },
         })
     }
 
+    /// Sets [TermSearchResultsSegment::word_status] for every segment with results,
+    /// looking up the primary headword of its best entry in the known words table.
+    ///
+    /// Segments are left untouched if the lookup fails.
+    pub fn annotate_word_statuses(&self, segments: &mut [TermSearchResultsSegment]) {
+        let (indices, words): (Vec<usize>, Vec<(String, String)>) = segments
+            .iter()
+            .enumerate()
+            .filter_map(|(i, seg)| {
+                let entry = seg.results.as_ref()?.dictionary_entries.first()?;
+                Some((i, lemma_of(entry)?))
+            })
+            .unzip();
+        if words.is_empty() {
+            return;
+        }
+        let statuses = match self.translator.db.find_word_statuses_bulk(&words) {
+            Ok(statuses) => statuses,
+            Err(e) => {
+                tracing::warn!("failed to look up word statuses: {e}");
+                return;
+            }
+        };
+        for (i, status) in indices.into_iter().zip(statuses) {
+            segments[i].word_status = Some(status);
+        }
+    }
+
     // --- Public API Method ---
 
     /// The main entry point for scanning text.
@@ -413,6 +506,26 @@ impl TextScanner {
         &full_text[start_byte..end_byte]
     }
 
+    /// Finds the dictionary entries for `text`, skipping the first `offset` sorted entries
+    /// and keeping at most `limit` (or `general.max_results` if `None`).
+    pub fn find_terms_page(
+        &self,
+        text: &str,
+        options: &ProfileOptions,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> FindTermsResult {
+        let mode = find_terms_mode(options);
+        let details = FindTermsDetails::default();
+        let mut find_terms_options =
+            Translator::_get_translator_find_terms_options(mode, &details, options);
+        find_terms_options.offset = offset;
+        if limit.is_some() {
+            find_terms_options.max_results = limit;
+        }
+        self.translator.find_terms(mode, text, &find_terms_options)
+    }
+
     /// Calls the core translator to find dictionary entries.
     fn find_term_dictionary_entries(
         &self,
@@ -430,16 +543,15 @@ impl TextScanner {
         // Note: If more scanner-specific overrides are needed in the future (like deinflection toggles),
         // you would set them on `details` here.
 
-        // 3. use `FindTermsMode::Group` as a sensible default for this kind of scanning.
-        //    This is because most users would want frequencies and pronunciations to be included.
-        const MODE: FindTermsMode = FindTermsMode::Group;
+        // 3. use the profile's output mode
+        let mode = find_terms_mode(options);
 
         let find_terms_options =
-            Translator::_get_translator_find_terms_options(MODE, &details, options);
+            Translator::_get_translator_find_terms_options(mode, &details, options);
 
         let find_result = self
             .translator
-            .find_terms(MODE, search_text, &find_terms_options);
+            .find_terms(mode, search_text, &find_terms_options);
 
         // 6. Check if any dictionary entries were found and return an Option.
         if find_result.dictionary_entries.is_empty() {
@@ -491,7 +603,7 @@ impl TextScanner {
             }
 
             // Check for terminators. In JS, this is `terminatorMap.get(c)`.
-            if ".!?。？！\n".contains(c) && self.sentence_terminate_at_newlines {
+            if SENTENCE_TERMINATORS.contains(c) && self.sentence_terminate_at_newlines {
                 // The sentence starts *after* the terminator.
                 // We use the character index `i` and add 1 to get the start.
                 sentence_start_char = full_text[..i].chars().count() + 1;
@@ -511,7 +623,7 @@ impl TextScanner {
                 break;
             }
 
-            if ".!?。？！\n".contains(c) && self.sentence_terminate_at_newlines {
+            if SENTENCE_TERMINATORS.contains(c) && self.sentence_terminate_at_newlines {
                 // The sentence ends *at* the terminator (inclusive).
                 sentence_end_char = full_text[..i].chars().count() + 1;
                 break;
@@ -553,6 +665,7 @@ impl TextScanner {
         Sentence {
             text: trimmed_sentence.to_string(),
             offset,
+            cue: None,
         }
     }
 }
@@ -610,6 +723,83 @@ mod textscanner {
     }
 }
 
+#[cfg(test)]
+mod output_mode_tests {
+    use crate::{
+        translator::core::FindTermsMode,
+        utils::test_utils::{TestYomichan, FIXTURE_DICTIONARY},
+    };
+
+    /// The terms of every headword of every entry found for `text` in `mode`.
+    fn headwords(ycd: &TestYomichan, mode: FindTermsMode, text: &str) -> Vec<Vec<String>> {
+        ycd.set_result_output_mode(mode).unwrap();
+        ycd.search_page(text, 0, None)
+            .unwrap()
+            .dictionary_entries
+            .iter()
+            .map(|entry| entry.headwords.iter().map(|hw| hw.term.clone()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn merge_groups_by_sequence() {
+        let ycd = TestYomichan::new("output_mode", &[FIXTURE_DICTIONARY]);
+        ycd.with_profile_mut(|profile| {
+            profile.options_mut().general.main_dictionary = FIXTURE_DICTIONARY.to_string();
+        })
+        .unwrap();
+
+        // 晩餐 and 晩さん share a sequence, so only Merge finds 晩さん
+        assert_eq!(headwords(&ycd, FindTermsMode::Group, "晩餐"), [["晩餐"]]);
+        assert_eq!(headwords(&ycd, FindTermsMode::Split, "晩餐"), [["晩餐"]]);
+        let mut merged = headwords(&ycd, FindTermsMode::Merge, "晩餐");
+        assert_eq!(merged.len(), 1);
+        merged[0].sort();
+        assert_eq!(merged[0], ["晩さん", "晩餐"]);
+        // entries of other sequences stay apart
+        assert_eq!(headwords(&ycd, FindTermsMode::Merge, "猫"), [["猫"]]);
+    }
+
+    #[test]
+    fn merge_adds_secondary_searches() {
+        const SECONDARY: &str = "fixture-ja-secondary";
+        let ycd = TestYomichan::new("output_mode_secondary", &[FIXTURE_DICTIONARY, SECONDARY]);
+        let allow_secondary_searches = |allow: bool| {
+            ycd.with_profile_mut(|profile| {
+                profile.options_mut().general.main_dictionary = FIXTURE_DICTIONARY.to_string();
+                profile.dictionaries_mut()[SECONDARY].allow_secondary_searches = allow;
+            })
+            .unwrap();
+        };
+        let merged_dictionaries = || {
+            ycd.set_result_output_mode(FindTermsMode::Merge).unwrap();
+            let result = ycd.search_page("晩餐", 0, None).unwrap();
+            let mut dictionaries: Vec<String> = result.dictionary_entries[0]
+                .definitions
+                .iter()
+                .map(|definition| definition.dictionary.clone())
+                .collect();
+            dictionaries.dedup();
+            dictionaries
+        };
+
+        allow_secondary_searches(false);
+        assert_eq!(merged_dictionaries(), [FIXTURE_DICTIONARY]);
+        // 晩さん is only found through the main dictionary's sequence
+        allow_secondary_searches(true);
+        assert!(merged_dictionaries().iter().any(|d| d == SECONDARY));
+    }
+
+    #[test]
+    fn merge_without_main_dictionary_groups() {
+        let mut options = crate::settings::core::ProfileOptions::default();
+        options.general.result_output_mode = FindTermsMode::Merge;
+        assert_eq!(super::find_terms_mode(&options), FindTermsMode::Group);
+        options.general.main_dictionary = "JMdict".to_string();
+        assert_eq!(super::find_terms_mode(&options), FindTermsMode::Merge);
+    }
+}
+
 #[cfg(test)]
 mod dbtests {
     use crate::{utils::test_utils, Yomichan};
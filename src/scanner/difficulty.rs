@@ -0,0 +1,418 @@
+use indexmap::IndexSet;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::WordStatus,
+    models::dictionary::TermDictionaryEntry,
+    scanner::{stream::DocumentSegment, vocabulary::lemma_of},
+    settings::core::{
+        FrequencyAggregationMethod, FrequencyAggregationOptions, FrequencyAggregationSource,
+        SortFrequencyDictionaryOrder,
+    },
+    translator::{
+        frequency::{aggregate_frequency, profile_frequency_aggregation},
+        types::FindTermsSortOrder,
+    },
+    Yomichan,
+};
+
+/// Where [`Yomichan::analyze_difficulty_with`] gets the user's known words from.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum KnownWordsSource {
+    /// Don't compute unknown-word ratios or i+1 sentences.
+    #[default]
+    None,
+    /// Use the persistent known words table (see [`Yomichan::set_word_status`]).
+    /// `Known` and `Ignored` words count as known.
+    Table,
+    /// A list of known dictionary forms (terms).
+    List(IndexSet<String>),
+}
+
+/// Options for [`Yomichan::analyze_difficulty_with`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DifficultyOptions {
+    /// Rank thresholds to report coverage for (e.g., "top 1000 words cover 82% of tokens").
+    pub top_n: Vec<i128>,
+    pub known_words: KnownWordsSource,
+    /// Ranks with this dictionary alone (in the profile's `sort_frequency_dictionary_order`)
+    /// instead of the profile's frequency settings.
+    pub frequency_dictionary: Option<String>,
+}
+
+impl Default for DifficultyOptions {
+    fn default() -> Self {
+        Self {
+            top_n: vec![1_000, 2_000, 5_000, 10_000],
+            known_words: KnownWordsSource::None,
+            frequency_dictionary: None,
+        }
+    }
+}
+
+/// Share of tokens whose frequency rank is within `top_n`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FrequencyCoverage {
+    pub top_n: i128,
+    /// `0.0..=1.0`
+    pub ratio: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SentenceDifficulty {
+    pub index: usize,
+    pub text: String,
+    pub token_count: usize,
+    /// `None` if no known words source was supplied.
+    pub unknown_count: Option<usize>,
+    /// Exactly one unknown word: ideal material for learning it in context.
+    pub is_i_plus_one: bool,
+}
+
+/// The result of [`Yomichan::analyze_difficulty`].
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DifficultyReport {
+    /// Number of recognized word occurrences.
+    pub token_count: usize,
+    /// Number of distinct (term, reading) dictionary forms.
+    pub unique_lemma_count: usize,
+    /// The frequency dictionaries ranks were aggregated from.
+    pub frequency_dictionaries: Vec<String>,
+    /// Coverage for each requested `top_n`. Empty if there are no frequency dictionaries.
+    pub coverage: Vec<FrequencyCoverage>,
+    /// Share of tokens without any frequency value.
+    pub unranked_ratio: f64,
+    /// Share of tokens that are unknown, `None` if no known words source was supplied.
+    pub unknown_ratio: Option<f64>,
+    /// Average number of inflection rules applied to reach the dictionary form.
+    pub average_inflection_chain_length: f64,
+    pub sentences: Vec<SentenceDifficulty>,
+}
+
+impl DifficultyReport {
+    pub fn i_plus_one_sentences(&self) -> impl Iterator<Item = &SentenceDifficulty> {
+        self.sentences.iter().filter(|s| s.is_i_plus_one)
+    }
+}
+
+impl Yomichan {
+    /// Grades `text` using [`DifficultyOptions::default`].
+    ///
+    /// Returns `None` if the current user profile cannot be accessed.
+    pub fn analyze_difficulty(&self, text: &str) -> Option<DifficultyReport> {
+        self.analyze_difficulty_with(text, &DifficultyOptions::default())
+    }
+
+    /// Grades `text` by frequency coverage, unknown words and inflection complexity.
+    ///
+    /// Ranks come from the profile's frequency settings, like the ranks of
+    /// [`Yomichan::extract_vocabulary`] (see [profile_frequency_aggregation]),
+    /// or from [`DifficultyOptions::frequency_dictionary`].
+    pub fn analyze_difficulty_with(
+        &self,
+        text: &str,
+        opts: &DifficultyOptions,
+    ) -> Option<DifficultyReport> {
+        let frequency_aggregation = self
+            .with_profile(|p| {
+                let general = &p.options().general;
+                match &opts.frequency_dictionary {
+                    Some(dictionary) => {
+                        let mode = match general.sort_frequency_dictionary_order {
+                            FindTermsSortOrder::Ascending => SortFrequencyDictionaryOrder::Rank,
+                            FindTermsSortOrder::Descending => {
+                                SortFrequencyDictionaryOrder::Occurance
+                            }
+                        };
+                        FrequencyAggregationOptions {
+                            method: FrequencyAggregationMethod::MinimumRank,
+                            sources: vec![FrequencyAggregationSource::new(dictionary, mode)],
+                        }
+                    }
+                    None => profile_frequency_aggregation(general),
+                }
+            })
+            .ok()?;
+        let mut analyzer = DifficultyAnalyzer {
+            opts,
+            frequency_aggregation: &frequency_aggregation,
+            state: DifficultyState::default(),
+        };
+        for segment in self.search_document(text)? {
+            analyzer.push(&segment);
+        }
+        Some(analyzer.finish())
+    }
+}
+
+#[derive(Default)]
+struct DifficultyState {
+    token_count: usize,
+    lemmas: IndexSet<(String, String)>,
+    covered: Vec<usize>,
+    unranked: usize,
+    unknown: usize,
+    counted_for_unknown: usize,
+    inflection_rules: usize,
+    sentences: Vec<SentenceDifficulty>,
+}
+
+struct DifficultyAnalyzer<'a> {
+    opts: &'a DifficultyOptions,
+    frequency_aggregation: &'a FrequencyAggregationOptions,
+    state: DifficultyState,
+}
+
+impl DifficultyAnalyzer<'_> {
+    fn push(&mut self, segment: &DocumentSegment) {
+        let has_known_words = self.opts.known_words != KnownWordsSource::None;
+        let state = &mut self.state;
+        if state.sentences.len() <= segment.sentence_index {
+            state.sentences.push(SentenceDifficulty {
+                index: segment.sentence_index,
+                text: String::new(),
+                token_count: 0,
+                unknown_count: has_known_words.then_some(0),
+                is_i_plus_one: false,
+            });
+        }
+        let sentence = state
+            .sentences
+            .last_mut()
+            .expect("[unexpected] sentence was just pushed");
+        sentence.text.push_str(&segment.segment.text);
+
+        let Some(entry) = segment
+            .segment
+            .results
+            .as_ref()
+            .and_then(|r| r.dictionary_entries.first())
+        else {
+            return;
+        };
+        let Some(lemma) = lemma_of(entry) else {
+            return;
+        };
+        state.token_count += 1;
+        sentence.token_count += 1;
+        state.inflection_rules += shortest_inflection_chain(entry);
+
+        match aggregate_frequency(self.frequency_aggregation, &entry.frequencies, Some(0)) {
+            Some(rank) => {
+                state.covered.resize(self.opts.top_n.len(), 0);
+                for (covered, top_n) in state.covered.iter_mut().zip(&self.opts.top_n) {
+                    if rank <= *top_n {
+                        *covered += 1;
+                    }
+                }
+            }
+            None => state.unranked += 1,
+        }
+
+        let unknown = match &self.opts.known_words {
+            KnownWordsSource::None => None,
+            KnownWordsSource::Table => match segment.segment.word_status {
+                Some(WordStatus::Ignored) => None,
+                Some(WordStatus::Known) => Some(false),
+                _ => Some(true),
+            },
+            KnownWordsSource::List(words) => Some(!words.contains(&lemma.0)),
+        };
+        if let Some(unknown) = unknown {
+            state.counted_for_unknown += 1;
+            if unknown {
+                state.unknown += 1;
+                if let Some(count) = sentence.unknown_count.as_mut() {
+                    *count += 1;
+                }
+            }
+        }
+        state.lemmas.insert(lemma);
+    }
+
+    fn finish(self) -> DifficultyReport {
+        let DifficultyState {
+            token_count,
+            lemmas,
+            covered,
+            unranked,
+            unknown,
+            counted_for_unknown,
+            inflection_rules,
+            mut sentences,
+        } = self.state;
+        let ratio = |n: usize, total: usize| match total {
+            0 => 0.0,
+            total => n as f64 / total as f64,
+        };
+        for sentence in &mut sentences {
+            sentence.text = sentence.text.trim().to_string();
+            sentence.is_i_plus_one = sentence.unknown_count == Some(1);
+        }
+        sentences.retain(|s| !s.text.is_empty());
+        let frequency_dictionaries: Vec<String> = self
+            .frequency_aggregation
+            .sources
+            .iter()
+            .map(|source| source.dictionary.clone())
+            .collect();
+        let coverage = match frequency_dictionaries.is_empty() {
+            false => self
+                .opts
+                .top_n
+                .iter()
+                .enumerate()
+                .map(|(i, top_n)| FrequencyCoverage {
+                    top_n: *top_n,
+                    ratio: ratio(covered.get(i).copied().unwrap_or(0), token_count),
+                })
+                .collect(),
+            true => Vec::new(),
+        };
+        DifficultyReport {
+            token_count,
+            unique_lemma_count: lemmas.len(),
+            frequency_dictionaries,
+            coverage,
+            unranked_ratio: ratio(unranked, token_count),
+            unknown_ratio: (self.opts.known_words != KnownWordsSource::None)
+                .then(|| ratio(unknown, counted_for_unknown)),
+            average_inflection_chain_length: ratio(inflection_rules, token_count),
+            sentences,
+        }
+    }
+}
+
+fn shortest_inflection_chain(entry: &TermDictionaryEntry) -> usize {
+    entry
+        .inflection_rule_chain_candidates
+        .iter()
+        .map(|candidate| candidate.inflection_rules.len())
+        .min()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod difficulty_tests {
+    use super::*;
+    use crate::utils::test_utils::{TestYomichan, FIXTURE_DICTIONARY};
+
+    /// Ranks of 猫, 犬 and 鳴く.
+    const RANK: &str = "fixture-ja-rank";
+    /// Occurrence counts of 猫, 犬 and 食べる.
+    const OCCURRENCE: &str = "fixture-ja-occurrence";
+    const TEXT: &str = "猫が鳴いた。犬は食べる。";
+
+    fn coverage(report: &DifficultyReport) -> Vec<(i128, f64)> {
+        report.coverage.iter().map(|c| (c.top_n, c.ratio)).collect()
+    }
+
+    #[test]
+    fn analyze_difficulty() {
+        let ycd = TestYomichan::new("difficulty", &[FIXTURE_DICTIONARY]);
+        let known = IndexSet::from(["猫".to_string()]);
+        let opts = DifficultyOptions {
+            known_words: KnownWordsSource::List(known),
+            ..Default::default()
+        };
+        let report = ycd.analyze_difficulty_with(TEXT, &opts).unwrap();
+        assert_eq!(report.token_count, 4);
+        assert_eq!(report.unique_lemma_count, 4);
+        // the fixture has no frequencies
+        assert_eq!(report.unranked_ratio, 1.0);
+        assert!(report.frequency_dictionaries.is_empty());
+        assert!(report.coverage.is_empty());
+        // 鳴く, 犬 and 食べる
+        assert_eq!(report.unknown_ratio, Some(0.75));
+        // 鳴いた is one rule away from 鳴く
+        assert_eq!(report.average_inflection_chain_length, 0.25);
+
+        let sentences: Vec<_> = report
+            .sentences
+            .iter()
+            .map(|s| (s.text.as_str(), s.token_count, s.unknown_count))
+            .collect();
+        assert_eq!(
+            sentences,
+            [("猫が鳴いた。", 2, Some(1)), ("犬は食べる。", 2, Some(2))]
+        );
+        let i_plus_one: Vec<_> = report.i_plus_one_sentences().map(|s| s.index).collect();
+        assert_eq!(i_plus_one, [0]);
+    }
+
+    #[test]
+    fn sort_frequency_dictionary_coverage() {
+        let ycd = TestYomichan::new("difficulty_rank", &[FIXTURE_DICTIONARY, RANK]);
+        ycd.with_profile_mut(|profile| {
+            profile.options_mut().general.sort_frequency_dictionary = Some(RANK.to_string());
+        })
+        .unwrap();
+        let opts = DifficultyOptions {
+            top_n: vec![1_000, 2_000, 5_000],
+            ..Default::default()
+        };
+
+        let report = ycd.analyze_difficulty_with(TEXT, &opts).unwrap();
+        assert_eq!(report.frequency_dictionaries, [RANK]);
+        // 猫 500, 鳴く 3000, 犬 1500, and 食べる is unranked
+        assert_eq!(report.unranked_ratio, 0.25);
+        assert_eq!(
+            coverage(&report),
+            [(1_000, 0.25), (2_000, 0.5), (5_000, 0.75)]
+        );
+    }
+
+    #[test]
+    fn aggregated_frequency_coverage() {
+        let ycd = TestYomichan::new(
+            "difficulty_aggregation",
+            &[FIXTURE_DICTIONARY, RANK, OCCURRENCE],
+        );
+        ycd.with_profile_mut(|profile| {
+            profile.options_mut().general.frequency_aggregation = FrequencyAggregationOptions {
+                method: FrequencyAggregationMethod::MinimumRank,
+                sources: vec![
+                    FrequencyAggregationSource::new(RANK, SortFrequencyDictionaryOrder::Rank),
+                    FrequencyAggregationSource::new(
+                        OCCURRENCE,
+                        SortFrequencyDictionaryOrder::Occurance,
+                    ),
+                ],
+            };
+        })
+        .unwrap();
+        let opts = DifficultyOptions {
+            top_n: vec![1_000, 2_000, 5_000],
+            ..Default::default()
+        };
+
+        let report = ycd.analyze_difficulty_with(TEXT, &opts).unwrap();
+        // 猫 100, 鳴く 3000, 犬 1500 (its occurrence count of 100 is not a rank), 食べる 500
+        assert_eq!(report.unranked_ratio, 0.0);
+        assert_eq!(
+            coverage(&report),
+            [(1_000, 0.5), (2_000, 0.75), (5_000, 1.0)]
+        );
+
+        // only the occurrence dictionary, counts are read as occurrences
+        ycd.with_profile_mut(|profile| {
+            profile
+                .options_mut()
+                .general
+                .sort_frequency_dictionary_order = FindTermsSortOrder::Descending;
+        })
+        .unwrap();
+        let opts = DifficultyOptions {
+            frequency_dictionary: Some(OCCURRENCE.to_string()),
+            ..opts
+        };
+        let report = ycd.analyze_difficulty_with(TEXT, &opts).unwrap();
+        assert_eq!(report.frequency_dictionaries, [OCCURRENCE]);
+        // 猫 100, 食べる 500 and 犬 10000, 鳴く is unranked
+        assert_eq!(report.unranked_ratio, 0.25);
+        assert_eq!(
+            coverage(&report),
+            [(1_000, 0.5), (2_000, 0.5), (5_000, 0.5)]
+        );
+    }
+}
@@ -1 +1,5 @@
 pub mod core;
+pub mod difficulty;
+pub mod reading;
+pub mod stream;
+pub mod vocabulary;
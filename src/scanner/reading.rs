@@ -0,0 +1,188 @@
+use crate::{
+    japanese::{
+        kana::{
+            hiragana_to_katakana, is_kana, is_kana_str, katakana_char_to_hiragana,
+            katakana_to_hiragana,
+        },
+        romaji::{contains_kana, kana_to_romaji, RomajiSystem},
+    },
+    scanner::core::TermSearchResultsSegment,
+    settings::core::{ParsingOptions, ParsingReadingMode},
+    Yomichan,
+};
+
+impl TermSearchResultsSegment {
+    /// The kana reading of the segment's text as written, including its inflection
+    /// (e.g. `のんだ` for `飲んだ`, not the dictionary form `のむ`).
+    ///
+    /// - Passthrough segments containing kana are their own reading.
+    /// - `None` if the text has no kana reading (e.g. punctuation or non-Japanese text).
+    pub fn surface_reading(&self) -> Option<String> {
+        if is_kana_str(&self.text) {
+            return Some(self.text.clone());
+        }
+        let Some(results) = &self.results else {
+            return contains_kana(&self.text).then(|| self.text.clone());
+        };
+        let headword = results.dictionary_entries.first()?.headwords.first()?;
+        if headword.reading.is_empty() || !headword.reading.chars().all(is_kana) {
+            return None;
+        }
+        Some(
+            inflected_reading(&self.text, &headword.term, &headword.reading)
+                .unwrap_or_else(|| headword.reading.clone()),
+        )
+    }
+
+    /// Renders the reading of the segment in `mode`.
+    ///
+    /// [ParsingReadingMode::DictionaryReading] is the reading of the dictionary form,
+    /// all other modes use [TermSearchResultsSegment::surface_reading].
+    pub fn reading_in(&self, mode: ParsingReadingMode, system: RomajiSystem) -> Option<String> {
+        match mode {
+            ParsingReadingMode::None => None,
+            ParsingReadingMode::DictionaryReading => {
+                let entry = self.results.as_ref()?.dictionary_entries.first()?;
+                let reading = &entry.headwords.first()?.reading;
+                (!reading.is_empty()).then(|| reading.clone())
+            }
+            ParsingReadingMode::Hiragana => Some(katakana_to_hiragana(&self.surface_reading()?)),
+            ParsingReadingMode::Katakana => Some(hiragana_to_katakana(&self.surface_reading()?)),
+            ParsingReadingMode::Romaji => Some(kana_to_romaji(&self.surface_reading()?, system)),
+        }
+    }
+}
+
+/// Sets [TermSearchResultsSegment::reading] of every segment according to
+/// `ParsingOptions::reading_mode` (romaji uses Hepburn).
+pub(crate) fn annotate_readings(
+    segments: &mut [TermSearchResultsSegment],
+    options: &ParsingOptions,
+) {
+    for segment in segments {
+        segment.reading = segment.reading_in(options.reading_mode, RomajiSystem::Hepburn);
+    }
+}
+
+/// Joins the segments' readings (or their text, where there is no reading) into a line,
+/// e.g. for romaji subtitles.
+///
+/// With `term_spacing`, words are separated by a space.
+pub fn join_readings(segments: &[TermSearchResultsSegment], term_spacing: bool) -> String {
+    const OPENERS: &str = "「『（(【[“‘";
+    let mut line = String::new();
+    for segment in segments {
+        let needs_space = term_spacing
+            && segment.results.is_some()
+            && line
+                .chars()
+                .next_back()
+                .is_some_and(|last| !last.is_whitespace() && !OPENERS.contains(last));
+        if needs_space {
+            line.push(' ');
+        }
+        line.push_str(segment.reading.as_deref().unwrap_or(&segment.text));
+    }
+    line
+}
+
+impl Yomichan {
+    /// Searches `text` and joins the readings of its segments into a single line, rendered in
+    /// the profile's `parsing.reading_mode` and spaced according to `parsing.term_spacing`.
+    ///
+    /// Returns `None` if the current user profile cannot be accessed.
+    pub fn search_reading_line(&self, text: &str) -> Option<String> {
+        let term_spacing = self
+            .with_profile(|p| p.options().parsing.term_spacing)
+            .ok()?;
+        let segments = self.search(text)?;
+        Some(join_readings(&segments, term_spacing))
+    }
+}
+
+/// Replaces the kana ending of the dictionary form in `reading` with the ending of `surface`.
+///
+/// Returns `None` if `surface` and `term` don't share a stem followed by kana only.
+fn inflected_reading(surface: &str, term: &str, reading: &str) -> Option<String> {
+    let surface: Vec<char> = surface.chars().collect();
+    let term: Vec<char> = term.chars().collect();
+    let stem = surface
+        .iter()
+        .zip(&term)
+        .take_while(|(s, t)| s == t)
+        .count();
+    let term_suffix = &term[stem..];
+    let surface_suffix = &surface[stem..];
+    if !term_suffix.iter().all(|c| is_kana(*c)) || !surface_suffix.iter().all(|c| is_kana(*c)) {
+        return None;
+    }
+    let reading: Vec<char> = reading.chars().collect();
+    let reading_stem_len = reading.len().checked_sub(term_suffix.len())?;
+    let ends_with_suffix = reading[reading_stem_len..]
+        .iter()
+        .zip(term_suffix)
+        .all(|(r, t)| katakana_char_to_hiragana(*r) == katakana_char_to_hiragana(*t));
+    if !ends_with_suffix || (reading_stem_len == 0 && stem > 0) {
+        return None;
+    }
+    let mut inflected: String = reading[..reading_stem_len].iter().collect();
+    inflected.extend(surface_suffix);
+    Some(inflected)
+}
+
+#[cfg(test)]
+mod reading_tests {
+    use super::*;
+
+    #[test]
+    fn inflected() {
+        assert_eq!(
+            inflected_reading("飲んだ", "飲む", "のむ").as_deref(),
+            Some("のんだ")
+        );
+        assert_eq!(
+            inflected_reading("食べられない", "食べる", "たべる").as_deref(),
+            Some("たべられない")
+        );
+        assert_eq!(
+            inflected_reading("日本語", "日本語", "にほんご").as_deref(),
+            Some("にほんご")
+        );
+        // different stems
+        assert_eq!(inflected_reading("呑んだ", "飲む", "のむ"), None);
+    }
+
+    #[test]
+    fn passthrough_readings() {
+        let segment = |text: &str| TermSearchResultsSegment {
+            text: text.to_string(),
+            results: None,
+            word_status: None,
+            reading: None,
+        };
+        assert_eq!(
+            segment("ねこ").reading_in(ParsingReadingMode::Katakana, RomajiSystem::Hepburn),
+            Some("ネコ".to_string())
+        );
+        assert_eq!(
+            segment("、").reading_in(ParsingReadingMode::Romaji, RomajiSystem::Hepburn),
+            None
+        );
+        assert_eq!(
+            segment("ねこ").reading_in(ParsingReadingMode::None, RomajiSystem::Hepburn),
+            None
+        );
+    }
+
+    #[test]
+    fn join() {
+        let segment = |text: &str, reading: Option<&str>| TermSearchResultsSegment {
+            text: text.to_string(),
+            results: None,
+            word_status: None,
+            reading: reading.map(str::to_string),
+        };
+        let segments = [segment("猫", Some("neko")), segment("、", None)];
+        assert_eq!(join_readings(&segments, false), "neko、");
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::VecDeque;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{
+    scanner::{
+        core::{SentenceParser, TermSearchResultsSegment, TextScanner, SENTENCE_TERMINATORS},
+        reading::annotate_readings,
+    },
+    settings::core::ProfileOptions,
+    Yomichan,
+};
+
+/// Closing brackets and quotes that stay attached to the sentence they close.
+const SENTENCE_CLOSERS: &str = "」』）)]】〉》\"'”’";
+
+impl Yomichan {
+    /// Lazily scans a long document (e.g., a book chapter) sentence by sentence.
+    ///
+    /// Unlike [`Yomichan::search`], nothing is materialized up front: the text is split into
+    /// sentences, and each sentence is only scanned once the returned iterator reaches it.
+    /// Only one batch of sentences is held in memory at a time.
+    ///
+    /// The profile options are snapshotted when this is called, so changing the profile
+    /// while the iterator is alive does not affect the remaining sentences.
+    ///
+    /// # Returns
+    /// - `Some(DocumentScanner)` yielding [`DocumentSegment`]s with offsets into `text`.
+    /// - `None` if the current user profile cannot be accessed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use yomichan_rs::Yomichan;
+    /// let ycd = Yomichan::new("path/to/db").unwrap();
+    /// let book = std::fs::read_to_string("book.txt").unwrap();
+    ///
+    /// for seg in ycd.search_document(&book).unwrap().with_batch_size(32) {
+    ///     if seg.segment.results.is_some() {
+    ///         println!("{} @ {}", seg.segment.text, seg.char_offset);
+    ///     }
+    /// }
+    /// ```
+    pub fn search_document<'a>(&'a self, text: &'a str) -> Option<DocumentScanner<'a>> {
+        let profile = self.backend.get_current_profile().ok()?;
+        let options = profile.read().options().clone();
+        Some(self.backend.scanner.scan_document(text, options))
+    }
+}
+
+impl TextScanner {
+    /// Creates a lazy [`DocumentScanner`] over `text` using the given options.
+    pub fn scan_document<'a>(
+        &'a self,
+        text: &'a str,
+        options: ProfileOptions,
+    ) -> DocumentScanner<'a> {
+        DocumentScanner {
+            scanner: self,
+            sentences: SentenceSplitter::new(text, self.sentence_terminate_at_newlines),
+            options,
+            batch_size: 1,
+            sentence_index: 0,
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+/// A single sentence-sized slice of a larger document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SentenceSpan<'a> {
+    /// The sentence text, including its terminator and any leading whitespace.
+    pub text: &'a str,
+    /// Byte offset of `text` within the document.
+    pub byte_offset: usize,
+    /// Character offset of `text` within the document.
+    pub char_offset: usize,
+}
+
+/// Splits a document into contiguous [`SentenceSpan`]s.
+///
+/// Spans cover the whole input, so concatenating every span's text yields the original document.
+/// A sentence ends after a run of terminators (see `extract_sentence`) and any closing quotes.
+#[derive(Debug, Clone)]
+pub struct SentenceSplitter<'a> {
+    text: &'a str,
+    byte_pos: usize,
+    char_pos: usize,
+    terminate_at_newlines: bool,
+}
+
+impl<'a> SentenceSplitter<'a> {
+    pub fn new(text: &'a str, terminate_at_newlines: bool) -> Self {
+        Self {
+            text,
+            byte_pos: 0,
+            char_pos: 0,
+            terminate_at_newlines,
+        }
+    }
+
+    fn is_terminator(&self, c: char) -> bool {
+        if c == '\n' {
+            return self.terminate_at_newlines;
+        }
+        SENTENCE_TERMINATORS.contains(c)
+    }
+}
+
+impl<'a> Iterator for SentenceSplitter<'a> {
+    type Item = SentenceSpan<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.text[self.byte_pos..];
+        if rest.is_empty() {
+            return None;
+        }
+
+        let mut chars = rest.char_indices().peekable();
+        let mut end = rest.len();
+        let mut char_count = 0;
+        let mut terminated = false;
+        while let Some((i, c)) = chars.next() {
+            char_count += 1;
+            if self.is_terminator(c) {
+                terminated = true;
+            }
+            if !terminated {
+                continue;
+            }
+            // swallow the rest of the terminator run (e.g. "?!" or "。」")
+            let run_continues = chars.peek().is_some_and(|&(_, next)| {
+                self.is_terminator(next) || SENTENCE_CLOSERS.contains(next)
+            });
+            if !run_continues {
+                end = i + c.len_utf8();
+                break;
+            }
+        }
+
+        let span = SentenceSpan {
+            text: &rest[..end],
+            byte_offset: self.byte_pos,
+            char_offset: self.char_pos,
+        };
+        self.byte_pos += end;
+        self.char_pos += char_count;
+        Some(span)
+    }
+}
+
+/// A [`TermSearchResultsSegment`] positioned within the scanned document.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DocumentSegment {
+    /// Index of the sentence this segment belongs to.
+    pub sentence_index: usize,
+    /// Byte offset of the segment within the document.
+    pub byte_offset: usize,
+    /// Character offset of the segment within the document.
+    pub char_offset: usize,
+    /// The segment itself; results are shared per sentence through `Arc<TermSearchResults>`.
+    pub segment: TermSearchResultsSegment,
+}
+
+/// Lazily scans a document one batch of sentences at a time.
+///
+/// Created by [`Yomichan::search_document`] or [`TextScanner::scan_document`].
+/// When the `rayon` feature is enabled, the sentences of a batch are scanned in parallel.
+pub struct DocumentScanner<'a> {
+    scanner: &'a TextScanner,
+    sentences: SentenceSplitter<'a>,
+    options: ProfileOptions,
+    batch_size: usize,
+    sentence_index: usize,
+    pending: VecDeque<DocumentSegment>,
+}
+
+impl<'a> DocumentScanner<'a> {
+    /// Sets how many sentences are scanned (and buffered) at once. Defaults to `1`.
+    ///
+    /// Larger batches only pay off with the `rayon` feature enabled.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Returns the remaining, not yet scanned sentences.
+    pub fn sentences(&self) -> SentenceSplitter<'a> {
+        self.sentences.clone()
+    }
+
+    fn scan_span(&self, span: &SentenceSpan) -> Vec<TermSearchResultsSegment> {
+        let passthrough = || {
+            vec![TermSearchResultsSegment {
+                text: span.text.to_string(),
+                results: None,
+                word_status: None,
+                reading: None,
+            }]
+        };
+        if span.text.trim().is_empty() {
+            return passthrough();
+        }
+        match self.scanner.search_sentence(span.text, &self.options) {
+            Some(results) => {
+                let mut segments = SentenceParser::parse(results);
+                self.scanner.annotate_word_statuses(&mut segments);
+                annotate_readings(&mut segments, &self.options.parsing);
+                segments
+            }
+            None => passthrough(),
+        }
+    }
+
+    fn fill_pending(&mut self) {
+        let batch: Vec<SentenceSpan<'a>> = self.sentences.by_ref().take(self.batch_size).collect();
+
+        #[cfg(feature = "rayon")]
+        let scanned: Vec<Vec<TermSearchResultsSegment>> =
+            batch.par_iter().map(|span| self.scan_span(span)).collect();
+        #[cfg(not(feature = "rayon"))]
+        let scanned: Vec<Vec<TermSearchResultsSegment>> =
+            batch.iter().map(|span| self.scan_span(span)).collect();
+
+        for (span, segments) in batch.iter().zip(scanned) {
+            let mut byte_offset = span.byte_offset;
+            let mut char_offset = span.char_offset;
+            for segment in segments {
+                let (byte_len, char_len) = (segment.text.len(), segment.text.chars().count());
+                self.pending.push_back(DocumentSegment {
+                    sentence_index: self.sentence_index,
+                    byte_offset,
+                    char_offset,
+                    segment,
+                });
+                byte_offset += byte_len;
+                char_offset += char_len;
+            }
+            self.sentence_index += 1;
+        }
+    }
+}
+
+impl Iterator for DocumentScanner<'_> {
+    type Item = DocumentSegment;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pending.is_empty() {
+            self.fill_pending();
+        }
+        self.pending.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod stream_tests {
+    use super::*;
+    use crate::utils::test_utils::YCD;
+
+    #[test]
+    fn sentence_splitter_offsets() {
+        let text = "今日は晴れ。「本当？」はい!! ok\nnext";
+        let spans: Vec<_> = SentenceSplitter::new(text, true).collect();
+        let texts: Vec<_> = spans.iter().map(|s| s.text).collect();
+        assert_eq!(
+            texts,
+            ["今日は晴れ。", "「本当？」", "はい!!", " ok\n", "next"]
+        );
+        assert_eq!(texts.concat(), text);
+        for span in &spans {
+            assert_eq!(&text[span.byte_offset..][..span.text.len()], span.text);
+            assert_eq!(text[..span.byte_offset].chars().count(), span.char_offset);
+        }
+    }
+
+    #[test]
+    fn sentence_splitter_ignores_newlines() {
+        let spans: Vec<_> = SentenceSplitter::new("a\nb. c", false)
+            .map(|s| s.text)
+            .collect();
+        assert_eq!(spans, ["a\nb.", " c"]);
+    }
+
+    #[ignore]
+    #[test]
+    fn search_document() {
+        let ycd = &YCD;
+        ycd.set_language("es").unwrap();
+        let text = "El perro es bueno. El gato es malo.";
+        let segments: Vec<_> = ycd
+            .search_document(text)
+            .unwrap()
+            .with_batch_size(2)
+            .collect();
+        let rebuilt: String = segments.iter().map(|s| s.segment.text.as_str()).collect();
+        assert_eq!(rebuilt, text);
+        for seg in &segments {
+            assert_eq!(
+                &text[seg.byte_offset..][..seg.segment.text.len()],
+                seg.segment.text
+            );
+        }
+        assert_eq!(segments.last().unwrap().sentence_index, 1);
+    }
+}
@@ -0,0 +1,341 @@
+use std::{cmp::Reverse, io};
+
+use indexmap::{IndexMap, IndexSet};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    models::dictionary::{TermDictionaryEntry, TermFrequency},
+    scanner::stream::DocumentSegment,
+    settings::core::FrequencyAggregationOptions,
+    translator::frequency::{aggregate_frequency, profile_frequency_aggregation},
+    Yomichan,
+};
+
+impl Yomichan {
+    /// Collects every unique dictionary-form word found in `text`.
+    ///
+    /// Inflected occurrences are merged into the headword of the best (first) dictionary entry
+    /// of each recognized segment, so "飲んだ" and "飲む" are both counted under 飲む/のむ.
+    ///
+    /// Returns `None` if the current user profile cannot be accessed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use yomichan_rs::Yomichan;
+    /// let ycd = Yomichan::new("path/to/db").unwrap();
+    /// let mut report = ycd.extract_vocabulary("猫が鳴いた。猫は鳴く。").unwrap();
+    /// report.sort_by_count();
+    /// println!("{}", report.to_json().unwrap());
+    /// ```
+    pub fn extract_vocabulary(&self, text: &str) -> Option<VocabularyReport> {
+        self.extract_vocabulary_iter(std::iter::once(text))
+    }
+
+    /// Same as [`Yomichan::extract_vocabulary`], but over a sequence of texts
+    /// (e.g., the chapters or lines of a book) which are scanned one at a time.
+    pub fn extract_vocabulary_iter<I, S>(&self, texts: I) -> Option<VocabularyReport>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let frequency_aggregation = self
+            .with_profile(|profile| profile_frequency_aggregation(&profile.options().general))
+            .ok()?;
+        let mut builder = VocabularyBuilder::new(frequency_aggregation);
+        for text in texts {
+            for segment in self.search_document(text.as_ref())? {
+                builder.push(&segment);
+            }
+        }
+        Some(builder.finish())
+    }
+}
+
+/// A unique word (dictionary form) found while extracting vocabulary.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VocabularyEntry {
+    /// The dictionary form (lemma) of the word.
+    pub term: String,
+    pub reading: String,
+    /// How many times the word occurred, across all of its inflected forms.
+    pub count: usize,
+    /// Every distinct surface form the word appeared as, in order of appearance.
+    pub surface_forms: IndexSet<String>,
+    /// The sentence the word first appeared in.
+    pub first_sentence: String,
+    /// Character offset of the first occurrence within the text it was found in.
+    pub first_char_offset: usize,
+    /// Frequencies of the headword from the enabled frequency dictionaries.
+    pub frequencies: Vec<TermFrequency>,
+    /// The headword's rank from the profile's frequency settings
+    /// (`frequency_aggregation`, or else `sort_frequency_dictionary`), if any.
+    pub frequency_rank: Option<i128>,
+}
+
+/// The result of [`Yomichan::extract_vocabulary`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VocabularyReport {
+    /// Unique words in order of first appearance.
+    pub entries: Vec<VocabularyEntry>,
+    /// Number of recognized word occurrences.
+    pub total_occurrences: usize,
+    /// Number of characters that were not part of any recognized word (whitespace excluded).
+    pub unrecognized_chars: usize,
+}
+
+impl VocabularyReport {
+    /// Sorts entries by occurrence count, most frequent first.
+    pub fn sort_by_count(&mut self) {
+        self.entries.sort_by_key(|e| Reverse(e.count));
+    }
+
+    /// Sorts entries by dictionary frequency rank, entries without a rank last.
+    pub fn sort_by_frequency_rank(&mut self) {
+        self.entries
+            .sort_by_key(|e| (e.frequency_rank.is_none(), e.frequency_rank));
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Writes the entries as CSV with the header
+    /// `term,reading,count,frequency_rank,frequencies,surface_forms,first_sentence`.
+    ///
+    /// `frequencies` is formatted as `dictionary:value` pairs separated by `;`.
+    pub fn write_csv<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(
+            writer,
+            "term,reading,count,frequency_rank,frequencies,surface_forms,first_sentence"
+        )?;
+        for entry in &self.entries {
+            let frequencies = entry
+                .frequencies
+                .iter()
+                .map(|f| {
+                    let value = f
+                        .display_value
+                        .clone()
+                        .unwrap_or_else(|| f.frequency.to_string());
+                    format!("{}:{}", f.dictionary, value)
+                })
+                .collect::<Vec<_>>()
+                .join(";");
+            let surface_forms = entry
+                .surface_forms
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(";");
+            let rank = entry
+                .frequency_rank
+                .map(|r| r.to_string())
+                .unwrap_or_default();
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{}",
+                csv_field(&entry.term),
+                csv_field(&entry.reading),
+                entry.count,
+                rank,
+                csv_field(&frequencies),
+                csv_field(&surface_forms),
+                csv_field(&entry.first_sentence),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut buf = Vec::new();
+        self.write_csv(&mut buf)
+            .expect("[unexpected] writing to a Vec<u8> cannot fail");
+        String::from_utf8(buf).expect("[unexpected] csv output is not valid utf-8")
+    }
+}
+
+/// Quotes a CSV field if it contains a delimiter, quote or newline.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Accumulates [`DocumentSegment`]s into a [`VocabularyReport`].
+pub(crate) struct VocabularyBuilder {
+    entries: IndexMap<(String, String), VocabularyEntry>,
+    total_occurrences: usize,
+    unrecognized_chars: usize,
+    /// Ranks the headwords, see [profile_frequency_aggregation].
+    frequency_aggregation: FrequencyAggregationOptions,
+}
+
+impl VocabularyBuilder {
+    pub(crate) fn new(frequency_aggregation: FrequencyAggregationOptions) -> Self {
+        Self {
+            entries: IndexMap::new(),
+            total_occurrences: 0,
+            unrecognized_chars: 0,
+            frequency_aggregation,
+        }
+    }
+
+    pub(crate) fn push(&mut self, segment: &DocumentSegment) {
+        let text = &segment.segment.text;
+        let Some(results) = &segment.segment.results else {
+            self.unrecognized_chars += text.chars().filter(|c| !c.is_whitespace()).count();
+            return;
+        };
+        let Some(entry) = results.dictionary_entries.first() else {
+            return;
+        };
+        let Some((term, reading)) = lemma_of(entry) else {
+            return;
+        };
+        self.total_occurrences += 1;
+        let vocab = self
+            .entries
+            .entry((term.clone(), reading.clone()))
+            .or_insert_with(|| VocabularyEntry {
+                term,
+                reading,
+                count: 0,
+                surface_forms: IndexSet::new(),
+                first_sentence: results.sentence.text.trim().to_string(),
+                first_char_offset: segment.char_offset,
+                frequencies: entry
+                    .frequencies
+                    .iter()
+                    .filter(|f| f.headword_index == 0)
+                    .cloned()
+                    .collect(),
+                frequency_rank: aggregate_frequency(
+                    &self.frequency_aggregation,
+                    &entry.frequencies,
+                    Some(0),
+                ),
+            });
+        vocab.count += 1;
+        vocab.surface_forms.insert(text.clone());
+    }
+
+    pub(crate) fn finish(self) -> VocabularyReport {
+        VocabularyReport {
+            entries: self.entries.into_values().collect(),
+            total_occurrences: self.total_occurrences,
+            unrecognized_chars: self.unrecognized_chars,
+        }
+    }
+}
+
+/// The (term, reading) of an entry's primary headword.
+pub(crate) fn lemma_of(entry: &TermDictionaryEntry) -> Option<(String, String)> {
+    entry
+        .headwords
+        .first()
+        .map(|hw| (hw.term.clone(), hw.reading.clone()))
+}
+
+#[cfg(test)]
+mod vocabulary_tests {
+    use super::*;
+    use crate::{
+        settings::core::{
+            FrequencyAggregationMethod, FrequencyAggregationSource, SortFrequencyDictionaryOrder,
+        },
+        translator::types::FindTermsSortOrder,
+        utils::test_utils::{TestYomichan, FIXTURE_DICTIONARY},
+    };
+
+    /// Ranks of 猫, 犬 and 鳴く.
+    const RANK: &str = "fixture-ja-rank";
+    /// Occurrence counts of 猫, 犬 and 食べる.
+    const OCCURRENCE: &str = "fixture-ja-occurrence";
+
+    #[test]
+    fn csv_field_escaping() {
+        assert_eq!(csv_field("猫"), "猫");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn extract_vocabulary() {
+        let ycd = TestYomichan::new("vocabulary", &[FIXTURE_DICTIONARY, RANK, OCCURRENCE]);
+        ycd.with_profile_mut(|profile| {
+            profile.options_mut().general.frequency_aggregation = FrequencyAggregationOptions {
+                method: FrequencyAggregationMethod::MinimumRank,
+                sources: vec![
+                    FrequencyAggregationSource::new(RANK, SortFrequencyDictionaryOrder::Rank),
+                    FrequencyAggregationSource::new(
+                        OCCURRENCE,
+                        SortFrequencyDictionaryOrder::Occurance,
+                    ),
+                ],
+            };
+        })
+        .unwrap();
+
+        let mut report = ycd
+            .extract_vocabulary("猫が鳴いた。猫は犬を食べる。")
+            .unwrap();
+        assert_eq!(report.total_occurrences, 5);
+        assert_eq!(report.unrecognized_chars, 5);
+        let counts: Vec<_> = report
+            .entries
+            .iter()
+            .map(|e| (e.term.as_str(), e.count))
+            .collect();
+        assert_eq!(counts, [("猫", 2), ("鳴く", 1), ("犬", 1), ("食べる", 1)]);
+        assert_eq!(
+            report.entries[1].surface_forms,
+            IndexSet::from(["鳴いた".to_string()])
+        );
+        assert_eq!(report.entries[1].first_sentence, "猫が鳴いた。");
+
+        // 犬 is rare in the occurrence dictionary, its count of 100 is not a rank
+        report.sort_by_frequency_rank();
+        let ranks: Vec<_> = report
+            .entries
+            .iter()
+            .map(|e| (e.term.as_str(), e.frequency_rank))
+            .collect();
+        assert_eq!(
+            ranks,
+            [
+                ("猫", Some(100)),
+                ("食べる", Some(500)),
+                ("犬", Some(1500)),
+                ("鳴く", Some(3000))
+            ]
+        );
+    }
+
+    #[test]
+    fn sort_frequency_dictionary_rank() {
+        let ycd = TestYomichan::new(
+            "vocabulary_sort_dictionary",
+            &[FIXTURE_DICTIONARY, RANK, OCCURRENCE],
+        );
+        ycd.with_profile_mut(|profile| {
+            let general = &mut profile.options_mut().general;
+            general.sort_frequency_dictionary = Some(OCCURRENCE.to_string());
+            general.sort_frequency_dictionary_order = FindTermsSortOrder::Descending;
+        })
+        .unwrap();
+
+        let report = ycd.extract_vocabulary("猫が鳴いた。犬").unwrap();
+        let ranks: Vec<_> = report
+            .entries
+            .iter()
+            .map(|e| (e.term.as_str(), e.frequency_rank))
+            .collect();
+        assert_eq!(
+            ranks,
+            [("猫", Some(100)), ("鳴く", None), ("犬", Some(10_000))]
+        );
+    }
+}
@@ -24,6 +24,7 @@ use native_model::Model;
 use serde::{Deserialize, Serialize};
 use url::form_urlencoded::Target;
 
+use crate::anki::template::FieldTemplate;
 use crate::translator::{core::FindTermsMode, types::FindTermsSortOrder};
 
 /// Global application-level options.
@@ -44,7 +45,15 @@ pub struct GlobalDatabaseOptions {
 ///
 /// This struct holds all global configuration options for the Yomichan application,
 /// including user profiles, current profile selection, and global Anki settings.
-#[native_model(id = 20, version = 1, with = native_model::postcard_1_0::PostCard)]
+///
+/// Changing the fields of this struct, or of any struct it contains, changes the persisted
+/// layout; bump the version and add a migration in [crate::settings::migration].
+#[native_model(
+    id = 20,
+    version = 2,
+    with = native_model::postcard_1_0::PostCard,
+    from = crate::settings::migration::YomichanOptionsV1
+)]
 #[native_db]
 #[derive(
     Clone, Debug, PartialEq, Serialize, Deserialize, Default, Getters, MutGetters, Setters,
@@ -74,6 +83,12 @@ impl Yomichan {
 }
 
 impl YomichanOptions {
+    /// Decodes options persisted by any version of [YomichanOptions],
+    /// migrating older layouts to the current one.
+    pub fn decode(blob: Vec<u8>) -> Result<Self, native_model::Error> {
+        native_model::decode::<YomichanOptions>(blob).map(|(options, _version)| options)
+    }
+
     /// Creates a new `YomichanOptions` instance with default settings.
     ///
     /// This includes a default profile named "Default" and default global Anki options.
@@ -411,6 +426,7 @@ pub struct GeneralOptions {
     /// The primary language for text processing (e.g., "ja" for Japanese).
     pub language: String,
     /// How search results are grouped and displayed.
+    #[default(FindTermsMode::Group)]
     pub result_output_mode: FindTermsMode,
     /// Whether prefix wildcards are supported in search queries.
     pub prefix_wildcard_supported: bool,
@@ -498,10 +514,70 @@ pub struct GeneralOptions {
     pub sort_frequency_dictionary: Option<String>,
     /// The order to sort frequency by.
     pub sort_frequency_dictionary_order: FindTermsSortOrder,
+    /// Frequency dictionaries combined into one aggregate frequency for sorting.
+    /// Takes precedence over `sort_frequency_dictionary` when it has sources.
+    pub frequency_aggregation: FrequencyAggregationOptions,
     /// Whether the search header is sticky.
     pub sticky_search_header: bool,
 }
 
+/// Combines the frequencies of several dictionaries into `TermDictionaryEntry::aggregate_frequency`.
+///
+/// The dictionaries must be enabled in the profile for their frequencies to be found.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Default)]
+pub struct FrequencyAggregationOptions {
+    /// How the ranks of the sources are combined.
+    pub method: FrequencyAggregationMethod,
+    /// The frequency dictionaries to combine.
+    pub sources: Vec<FrequencyAggregationSource>,
+}
+
+impl FrequencyAggregationOptions {
+    pub fn is_enabled(&self) -> bool {
+        !self.sources.is_empty()
+    }
+}
+
+/// How the ranks from several frequency dictionaries are combined.
+///
+/// Sources without a frequency for a term are left out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum FrequencyAggregationMethod {
+    /// The weighted harmonic mean of the ranks, which favours the sources ranking a term highest.
+    #[default]
+    HarmonicMean,
+    /// The best (lowest) rank of any source.
+    MinimumRank,
+    /// The weighted arithmetic mean of the ranks.
+    WeightedAverage,
+}
+
+/// A frequency dictionary used for [FrequencyAggregationOptions].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Default)]
+pub struct FrequencyAggregationSource {
+    /// The title of the frequency dictionary.
+    pub dictionary: String,
+    /// Whether the dictionary lists occurrence counts or ranks.
+    pub mode: SortFrequencyDictionaryOrder,
+    /// The weight of this dictionary in weighted methods.
+    #[default(1.0)]
+    pub weight: f64,
+    /// For occurrence-based dictionaries, the occurrence count that corresponds to rank 1.
+    /// Counts are converted to ranks with Zipf's law: `rank = occurrence_scale / occurrences`.
+    #[default(1_000_000.0)]
+    pub occurrence_scale: f64,
+}
+
+impl FrequencyAggregationSource {
+    pub fn new(dictionary: impl Into<String>, mode: SortFrequencyDictionaryOrder) -> Self {
+        Self {
+            dictionary: dictionary.into(),
+            mode,
+            ..Default::default()
+        }
+    }
+}
+
 /// Options for the popup window behavior.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Default)]
 pub struct PopupWindowOptions {
@@ -669,22 +745,48 @@ pub struct ScanningPreventMiddleMouseOptions {
 /// Options for text translation and transformation.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Default)]
 pub struct TranslationOptions {
-    /// Whether to convert half-width characters.
+    /// Whether to convert half-width characters (e.g., ｶﾀｶﾅ -> カタカナ).
+    #[default(TranslationConvertType::Variant)]
     pub convert_half_width_characters: TranslationConvertType,
-    /// Whether to convert numeric characters.
+    /// Whether to convert numeric characters (e.g., full-width １２３ -> 123).
+    #[default(TranslationConvertType::Variant)]
     pub convert_numeric_characters: TranslationConvertType,
-    /// Whether to convert alphabetic characters to hiragana.
-    pub alphabetic_to_hiragana: TranslationConvertType,
+    /// Whether to convert alphabetic characters (romaji) to hiragana.
+    /// If unset, romaji is converted like an IME when `GeneralOptions::enable_wanakana` is on.
+    pub alphabetic_to_hiragana: Option<TranslationConvertType>,
     /// Whether to convert hiragana to katakana.
+    #[default(TranslationConvertType::Variant)]
     pub convert_hiragana_to_katakana: TranslationConvertType,
     /// Whether to convert katakana to hiragana.
+    #[default(TranslationConvertType::Variant)]
     pub convert_katakana_to_hiragana: TranslationConvertType,
-    /// Whether to collapse emphatic sequences.
+    /// Whether to collapse emphatic sequences (e.g., すっっごい -> すごい).
+    #[default(TranslationCollapseEmphaticSequences::Full)]
     pub collapse_emphatic_sequences: TranslationCollapseEmphaticSequences,
     /// Options for text replacements.
     pub text_replacements: TranslationTextReplacementOptions,
     /// The resolution for search (Letter or Word).
     pub search_resolution: SearchResolution,
+    /// How search results are ordered.
+    pub ranking_strategy: RankingStrategyKind,
+}
+
+/// Selects the [RankingStrategy](crate::translator::ranking::RankingStrategy)
+/// used to order search results.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+pub enum RankingStrategyKind {
+    /// Yomitan's order, see [YomitanRanking](crate::translator::ranking::YomitanRanking).
+    #[default]
+    Yomitan,
+    /// More frequent terms first, see
+    /// [FrequencyFirstRanking](crate::translator::ranking::FrequencyFirstRanking).
+    FrequencyFirst,
+    /// Name dictionary entries last, see
+    /// [PenalizeNamesRanking](crate::translator::ranking::PenalizeNamesRanking).
+    PenalizeNames,
+    /// A strategy registered with
+    /// [Yomichan::register_ranking_strategy](crate::Yomichan::register_ranking_strategy).
+    Custom(String),
 }
 
 /// Defines the resolution for text search.
@@ -734,7 +836,7 @@ pub struct DictionaryOptions {
     ///
     /// # Merged Mode Example
     /// If you have two dictionaries, `Dict 1` and `Dict 2`:
-    /// - Set the [`ResultOutputMode`] to `Group` results for the main dictionary entry.
+    /// - Set the [`FindTermsMode`] to `Merge` to group results by the main dictionary entry.
     /// - Choose `Dict 1` as the main dictionary for merged mode.
     /// - Enable `allow_secondary_searches` on `Dict 2`.
     ///   (Can be enabled for multiple dictionaries).
@@ -885,6 +987,17 @@ pub enum FieldIndex {
     Frequency(usize),
 }
 
+/// Represents an index mapping for an Anki field of a kanji note.
+#[derive(Clone, Copy, Debug)]
+pub enum KanjiFieldIndex {
+    Character(usize),
+    Onyomi(usize),
+    Kunyomi(usize),
+    Meanings(usize),
+    StrokeCount(usize),
+    Frequency(usize),
+}
+
 /// Defines Anki field types that correspond to a [DictionaryTermEntry] for making notes.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum AnkiTermFieldType {
@@ -899,6 +1012,20 @@ pub enum AnkiTermFieldType {
 }
 
 impl AnkiTermFieldType {
+    /// The name of the Anki field this maps to.
+    pub fn field_name(&self) -> &str {
+        match self {
+            AnkiTermFieldType::Term(name)
+            | AnkiTermFieldType::Reading(name)
+            | AnkiTermFieldType::Sentence(name)
+            | AnkiTermFieldType::Definition(name)
+            | AnkiTermFieldType::TermAudio(name)
+            | AnkiTermFieldType::SentenceAudio(name)
+            | AnkiTermFieldType::Image(name)
+            | AnkiTermFieldType::Frequency(name) => name,
+        }
+    }
+
     /// Converts a slice of user-provided `FieldIndex` mappings into a persistent,
     /// name-based `Vec<AnkiTermFieldType>`.
     ///
@@ -978,6 +1105,60 @@ impl AnkiTermFieldType {
     }
 }
 
+/// Defines Anki field types that correspond to a
+/// [KanjiDictionaryEntry](crate::models::dictionary::KanjiDictionaryEntry) for making kanji notes.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum AnkiKanjiFieldType {
+    Character(String),
+    Onyomi(String),
+    Kunyomi(String),
+    Meanings(String),
+    StrokeCount(String),
+    Frequency(String),
+}
+
+impl AnkiKanjiFieldType {
+    /// The name of the Anki field this maps to.
+    pub fn field_name(&self) -> &str {
+        match self {
+            AnkiKanjiFieldType::Character(name)
+            | AnkiKanjiFieldType::Onyomi(name)
+            | AnkiKanjiFieldType::Kunyomi(name)
+            | AnkiKanjiFieldType::Meanings(name)
+            | AnkiKanjiFieldType::StrokeCount(name)
+            | AnkiKanjiFieldType::Frequency(name) => name,
+        }
+    }
+
+    /// Same as [AnkiTermFieldType::from_field_indices], for kanji notes.
+    pub fn from_field_indices(
+        mappings: &[KanjiFieldIndex],
+        model: &FullModelDetails,
+    ) -> Result<Vec<AnkiKanjiFieldType>, AnkiFieldsError> {
+        let anki_model_fields = &model.fields;
+        let mut resolved_fields = Vec::with_capacity(mappings.len());
+        for mapping in mappings {
+            let (index, constructor): (usize, fn(String) -> AnkiKanjiFieldType) = match *mapping {
+                KanjiFieldIndex::Character(i) => (i, AnkiKanjiFieldType::Character),
+                KanjiFieldIndex::Onyomi(i) => (i, AnkiKanjiFieldType::Onyomi),
+                KanjiFieldIndex::Kunyomi(i) => (i, AnkiKanjiFieldType::Kunyomi),
+                KanjiFieldIndex::Meanings(i) => (i, AnkiKanjiFieldType::Meanings),
+                KanjiFieldIndex::StrokeCount(i) => (i, AnkiKanjiFieldType::StrokeCount),
+                KanjiFieldIndex::Frequency(i) => (i, AnkiKanjiFieldType::Frequency),
+            };
+            let field_name = anki_model_fields.get(index).cloned().ok_or_else(|| {
+                AnkiFieldsError::ModelFieldsOutOfBounds {
+                    model: model.name.clone(),
+                    index,
+                    fields_len: anki_model_fields.len(),
+                }
+            })?;
+            resolved_fields.push(constructor(field_name));
+        }
+        Ok(resolved_fields)
+    }
+}
+
 /// A map for caching discovered Anki note models.
 ///
 /// This struct stores a collection of `FullModelDetails` objects, indexed by their names,
@@ -1167,6 +1348,20 @@ pub struct AnkiFields {
     selected_deck: usize,
 }
 
+/// Type to cache Anki kanji note creation options, see [AnkiFields].
+#[derive(
+    Clone, Default, Debug, PartialEq, Serialize, Deserialize, Getters, Setters, MutGetters,
+)]
+#[getset(get = "pub", get_mut = "pub", set = "pub")]
+pub struct AnkiKanjiFields {
+    /// Maps kanji information to the selected model's fields.
+    fields: Vec<AnkiKanjiFieldType>,
+    /// The index of the selected note model to use when creating kanji notes.
+    selected_model: usize,
+    /// The index of the selected deck to add kanji notes to.
+    selected_deck: usize,
+}
+
 /// Options for Anki integration.
 #[derive(
     Clone, Debug, PartialEq, Serialize, Deserialize, Default, Getters, Setters, MutGetters,
@@ -1187,6 +1382,10 @@ pub struct AnkiOptions {
     /// Pre-mapped fields that specify which Anki field to insert term data.
     #[getset(get_mut = "pub")]
     anki_fields: Option<AnkiFields>,
+    /// Pre-mapped fields that specify which Anki field to insert kanji data,
+    /// independent of the term note's model and deck.
+    #[getset(get_mut = "pub")]
+    kanji_fields: Option<AnkiKanjiFields>,
     /// The scope for duplicate checking.
     duplicate_scope: AnkiDuplicateScope,
     /// Whether to check all models for duplicates.
@@ -1196,8 +1395,36 @@ pub struct AnkiOptions {
     /// Whether to check for duplicates before adding new cards.
     #[default(true)]
     check_for_duplicates: bool,
-    /// Templates for Anki fields.
-    field_templates: Vec<String>,
+    /// Fields that [AnkiDuplicateBehavior::Overwrite] and
+    /// [DisplayAnki::update_note_from_entry](crate::anki::DisplayAnki::update_note_from_entry)
+    /// leave as they are, e.g. fields the user writes by hand.
+    overwrite_keep_fields: Vec<String>,
+    /// [FieldTemplate]s by Anki field name.
+    ///
+    /// A template replaces the default value of the [AnkiFields] mapping of the same field;
+    /// fields that aren't mapped are filled as well.
+    #[getset(get_mut = "pub")]
+    field_templates: IndexMap<String, FieldTemplate>,
+    /// How a field mapped to [AnkiTermFieldType::Frequency] is filled.
+    frequency_format: AnkiFrequencyFormat,
+    /// How a field mapped to [AnkiTermFieldType::Definition] is filled.
+    glossary_format: AnkiGlossaryFormat,
+    /// Dictionaries (names or aliases) whose definitions fill glossary fields, in this order;
+    /// every dictionary of the entry, in the entry's order, if empty.
+    ///
+    /// `{single-glossary-<dictionary>}` templates aren't limited by this.
+    glossary_dictionaries: Vec<String>,
+    /// The maximum number of definitions in a glossary field; 0 = no limit.
+    glossary_max_senses: u32,
+    /// Whether each definition in a glossary field is labeled with its dictionary.
+    #[default(true)]
+    glossary_dictionary_names: bool,
+    /// Whether each definition in a glossary field is labeled with its tags.
+    #[default(true)]
+    glossary_tags: bool,
+    /// Whether [Yomichan::add_entry_or_queue](crate::Yomichan::add_entry_or_queue) queues
+    /// notes in the database while AnkiConnect is unreachable.
+    queue_when_offline: bool,
     /// Whether to suspend new cards.
     suspend_new_cards: bool,
     /// Options for displaying Anki tags.
@@ -1323,31 +1550,6 @@ pub struct PreventMiddleMouseOptions {
     pub on_search_query: bool,
 }
 
-/// Change how related results are grouped.
-///
-/// The Primary dictionary option should be assigned to a
-/// dictionary which contains related term information,
-/// and configuring the Secondary dictionaries will allow definitions for the
-/// related terms to be included from other dictionaries.
-///
-/// _Not all dictionaries are able to be selected as the Primary dictionary_.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
-pub enum ResultOutputMode {
-    /// No grouping.
-    ///
-    /// Every definition will be listed as a separate entry.
-    Split,
-    /// Group term-reading pairs.
-    ///
-    /// Definitions for the same term with the same reading will be grouped together.
-    #[default]
-    Group,
-    /// Group related terms.
-    ///
-    /// Related terms that share the same definitions will be grouped together.
-    Merge,
-}
-
 /// Change the layout of the popup.
 ///
 /// The `Default` mode will position the popup relative to the scanned text.
@@ -1505,18 +1707,24 @@ pub enum AudioSourceType {
 /// Defines how characters are converted during translation.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub enum TranslationConvertType {
+    /// Only search the original text.
     #[default]
     False,
+    /// Only search the converted text.
     True,
+    /// Search both the original and the converted text.
     Variant,
 }
 
 /// Defines how emphatic sequences are collapsed during translation.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub enum TranslationCollapseEmphaticSequences {
+    /// Only search the original text.
     #[default]
     False,
+    /// Also search with repeated characters collapsed (すっっごい -> すっごい).
     True,
+    /// Also search with repeated characters removed entirely (すっっごい -> すごい).
     Full,
 }
 
@@ -1575,13 +1783,41 @@ pub enum AnkiDuplicateScope {
     DeckRoot,
 }
 
-/// When a duplicate is detected.
+/// How the frequencies of an entry are written to a [AnkiTermFieldType::Frequency] field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum AnkiFrequencyFormat {
+    /// Every dictionary's frequency as `dictionary: value`, one per line.
+    #[default]
+    All,
+    /// The lowest rank of any dictionary.
+    LowestRank,
+    /// The harmonic mean of every dictionary's rank.
+    HarmonicMean,
+}
+
+/// How the definitions of an entry are written to a [AnkiTermFieldType::Definition] field,
+/// see [GlossaryRenderer](crate::anki::glossary::GlossaryRenderer).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum AnkiGlossaryFormat {
+    /// Html with each definition's glossaries in a list, and the dictionaries' styles.
+    #[default]
+    Full,
+    /// Html with each definition's glossaries on a single line, separated by ` | `.
+    Compact,
+    /// The plain text of every glossary, one per line.
+    PlainText,
+}
+
+/// What adding an entry does when a duplicate is detected.
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Default)]
 pub enum AnkiDuplicateBehavior {
+    /// The entry can't be added.
     #[default]
     Prevent,
+    /// The fields of the existing note are replaced.
     Overwrite,
-    New,
+    /// A new note is added next to the existing one.
+    Allow,
 }
 
 /// Show card tags.
@@ -0,0 +1,411 @@
+//! Older layouts of the persisted [YomichanOptions].
+//!
+//! Options are stored with postcard, which encodes fields by position,
+//! so every change to the fields of a persisted struct needs a new `native_model` version
+//! of [YomichanOptions] and a migration from the previous one.
+//!
+//! When bumping the version, copy the changed structs (and the structs containing them)
+//! here with the version as a suffix, and implement `From` in both directions.
+
+use indexmap::IndexMap;
+use native_model::native_model;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::core::{
+    AccessibilityOptions, AnkiDisplayTags, AnkiDuplicateBehavior, AnkiDuplicateScope, AnkiFields,
+    AnkiNoteGuiMode, AnkiOptions, AnkiScreenshotOptions, AudioOptions, ClipboardOptions,
+    DictionaryOptions, FrequencyDisplayStyle, GeneralOptions, GlobalAnkiOptions, GlobalOptions,
+    InputsOptions, ParsingOptions, PopupActionBarLocation, PopupActionBarVisibility,
+    PopupCurrentIndicatorMode, PopupDisplayMode, PopupHorizontalTextPosition, PopupShadow,
+    PopupTheme, PopupVerticalTextPosition, PopupWindowOptions, ProfileConditionGroup,
+    ProfileOptions, ScanningOptions, SearchResolution, SentenceParsingOptions, TermDisplayStyle,
+    TranslationCollapseEmphaticSequences, TranslationConvertType, TranslationOptions,
+    TranslationTextReplacementOptions, YomichanOptions, YomichanProfile,
+};
+use crate::translator::{core::FindTermsMode, types::FindTermsSortOrder};
+use crate::Ptr;
+
+/// Implements `From` in both directions between two versions of a struct with public fields.
+///
+/// Every listed field is converted with `into`;
+/// fields only the newer version has get their default value.
+macro_rules! migrate_fields {
+    ($old:ident => $new:ident { $($field:ident),* $(,)? }) => {
+        impl From<$old> for $new {
+            #[allow(clippy::needless_update, clippy::useless_conversion)]
+            fn from(old: $old) -> Self {
+                Self {
+                    $($field: old.$field.into(),)*
+                    ..Default::default()
+                }
+            }
+        }
+
+        impl From<$new> for $old {
+            #[allow(clippy::useless_conversion)]
+            fn from(new: $new) -> Self {
+                Self {
+                    $($field: new.$field.into(),)*
+                }
+            }
+        }
+    };
+}
+
+/// [YomichanOptions] before frequency aggregation, ranking strategies,
+/// field templates and kanji notes were added.
+#[native_model(id = 20, version = 1, with = native_model::postcard_1_0::PostCard)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct YomichanOptionsV1 {
+    pub id: String,
+    pub version: String,
+    pub profiles: IndexMap<String, Ptr<YomichanProfileV1>>,
+    pub current_profile: usize,
+    pub global: GlobalOptions,
+    pub anki: Ptr<GlobalAnkiOptions>,
+}
+
+impl From<YomichanOptionsV1> for YomichanOptions {
+    fn from(old: YomichanOptionsV1) -> Self {
+        let mut new = YomichanOptions::default();
+        new.set_id(old.id)
+            .set_version(old.version)
+            .set_profiles(
+                old.profiles
+                    .into_iter()
+                    .map(|(name, profile)| {
+                        let profile = profile.read_arc().clone();
+                        (name, Ptr::new(YomichanProfile::from(profile)))
+                    })
+                    .collect(),
+            )
+            .set_current_profile(old.current_profile)
+            .set_global(old.global)
+            .set_anki(old.anki);
+        new
+    }
+}
+
+impl From<YomichanOptions> for YomichanOptionsV1 {
+    fn from(new: YomichanOptions) -> Self {
+        Self {
+            id: new.id().clone(),
+            version: new.version,
+            profiles: new
+                .profiles
+                .into_iter()
+                .map(|(name, profile)| {
+                    let profile = profile.read_arc().clone();
+                    (name, Ptr::new(YomichanProfileV1::from(profile)))
+                })
+                .collect(),
+            current_profile: new.current_profile,
+            global: new.global,
+            anki: new.anki,
+        }
+    }
+}
+
+/// [YomichanProfile] of [YomichanOptionsV1].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct YomichanProfileV1 {
+    pub name: String,
+    pub condition_groups: Vec<ProfileConditionGroup>,
+    pub options: ProfileOptionsV1,
+}
+
+migrate_fields!(YomichanProfileV1 => YomichanProfile { name, condition_groups, options });
+
+/// [ProfileOptions] of [YomichanOptionsV1].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProfileOptionsV1 {
+    pub general: GeneralOptionsV1,
+    pub popup_window: PopupWindowOptions,
+    pub audio: AudioOptions,
+    pub scanning: ScanningOptions,
+    pub translation: TranslationOptionsV1,
+    pub dictionaries: IndexMap<String, DictionaryOptions>,
+    pub parsing: ParsingOptions,
+    pub anki: AnkiOptionsV1,
+    pub sentence_parsing: SentenceParsingOptions,
+    pub inputs: InputsOptions,
+    pub clipboard: ClipboardOptions,
+    pub accessibility: AccessibilityOptions,
+}
+
+migrate_fields!(ProfileOptionsV1 => ProfileOptions {
+    general,
+    popup_window,
+    audio,
+    scanning,
+    translation,
+    dictionaries,
+    parsing,
+    anki,
+    sentence_parsing,
+    inputs,
+    clipboard,
+    accessibility,
+});
+
+/// [GeneralOptions] without `frequency_aggregation`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GeneralOptionsV1 {
+    pub enable: bool,
+    pub language: String,
+    pub result_output_mode: FindTermsMode,
+    pub prefix_wildcard_supported: bool,
+    pub debug_info: bool,
+    pub max_results: u8,
+    pub show_advanced: bool,
+    pub font_family: String,
+    pub font_size: u8,
+    pub line_height: String,
+    pub popup_display_mode: PopupDisplayMode,
+    pub popup_width: u8,
+    pub popup_height: u8,
+    pub popup_horizontal_offset: u8,
+    pub popup_vertical_offset: u8,
+    pub popup_horizontal_offset2: u8,
+    pub popup_vertical_offset2: u8,
+    pub popup_horizontal_text_position: PopupHorizontalTextPosition,
+    pub popup_vertical_text_position: PopupVerticalTextPosition,
+    pub popup_scaling_factor: u8,
+    pub popup_scale_relative_to_page_zoom: bool,
+    pub popup_scale_relative_to_visual_viewport: bool,
+    pub show_guide: bool,
+    pub enable_context_menu_scan_selected: bool,
+    pub compact_tags: bool,
+    pub compact_glossaries: bool,
+    pub main_dictionary: String,
+    pub popup_theme: PopupTheme,
+    pub popup_outer_theme: PopupShadow,
+    pub custom_popup_css: String,
+    pub custom_popup_outer_css: String,
+    pub enable_wanakana: bool,
+    pub show_pitch_accent_downstep_notation: bool,
+    pub show_pitch_accent_position_notation: bool,
+    pub show_pitch_accent_graph: bool,
+    pub show_iframe_popups_in_root_frame: bool,
+    pub use_secure_popup_frame_url: bool,
+    pub use_popup_shadow_dom: bool,
+    pub use_popup_window: bool,
+    pub popup_current_indicator_mode: PopupCurrentIndicatorMode,
+    pub popup_action_bar_visibility: PopupActionBarVisibility,
+    pub popup_action_bar_location: PopupActionBarLocation,
+    pub frequency_display_mode: FrequencyDisplayStyle,
+    pub term_display_mode: TermDisplayStyle,
+    pub sort_frequency_dictionary: Option<String>,
+    pub sort_frequency_dictionary_order: FindTermsSortOrder,
+    pub sticky_search_header: bool,
+}
+
+migrate_fields!(GeneralOptionsV1 => GeneralOptions {
+    enable,
+    language,
+    result_output_mode,
+    prefix_wildcard_supported,
+    debug_info,
+    max_results,
+    show_advanced,
+    font_family,
+    font_size,
+    line_height,
+    popup_display_mode,
+    popup_width,
+    popup_height,
+    popup_horizontal_offset,
+    popup_vertical_offset,
+    popup_horizontal_offset2,
+    popup_vertical_offset2,
+    popup_horizontal_text_position,
+    popup_vertical_text_position,
+    popup_scaling_factor,
+    popup_scale_relative_to_page_zoom,
+    popup_scale_relative_to_visual_viewport,
+    show_guide,
+    enable_context_menu_scan_selected,
+    compact_tags,
+    compact_glossaries,
+    main_dictionary,
+    popup_theme,
+    popup_outer_theme,
+    custom_popup_css,
+    custom_popup_outer_css,
+    enable_wanakana,
+    show_pitch_accent_downstep_notation,
+    show_pitch_accent_position_notation,
+    show_pitch_accent_graph,
+    show_iframe_popups_in_root_frame,
+    use_secure_popup_frame_url,
+    use_popup_shadow_dom,
+    use_popup_window,
+    popup_current_indicator_mode,
+    popup_action_bar_visibility,
+    popup_action_bar_location,
+    frequency_display_mode,
+    term_display_mode,
+    sort_frequency_dictionary,
+    sort_frequency_dictionary_order,
+    sticky_search_header,
+});
+
+/// [TranslationOptions] without `ranking_strategy`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TranslationOptionsV1 {
+    pub convert_half_width_characters: TranslationConvertType,
+    pub convert_numeric_characters: TranslationConvertType,
+    pub alphabetic_to_hiragana: TranslationConvertType,
+    pub convert_hiragana_to_katakana: TranslationConvertType,
+    pub convert_katakana_to_hiragana: TranslationConvertType,
+    pub collapse_emphatic_sequences: TranslationCollapseEmphaticSequences,
+    pub text_replacements: TranslationTextReplacementOptions,
+    pub search_resolution: SearchResolution,
+}
+
+/// `alphabetic_to_hiragana` had no effect in V1, so `False` becomes unset,
+/// which leaves the conversion to `enable_wanakana`.
+impl From<TranslationOptionsV1> for TranslationOptions {
+    fn from(old: TranslationOptionsV1) -> Self {
+        Self {
+            convert_half_width_characters: old.convert_half_width_characters,
+            convert_numeric_characters: old.convert_numeric_characters,
+            alphabetic_to_hiragana: match old.alphabetic_to_hiragana {
+                TranslationConvertType::False => None,
+                convert => Some(convert),
+            },
+            convert_hiragana_to_katakana: old.convert_hiragana_to_katakana,
+            convert_katakana_to_hiragana: old.convert_katakana_to_hiragana,
+            collapse_emphatic_sequences: old.collapse_emphatic_sequences,
+            text_replacements: old.text_replacements,
+            search_resolution: old.search_resolution,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<TranslationOptions> for TranslationOptionsV1 {
+    fn from(new: TranslationOptions) -> Self {
+        Self {
+            convert_half_width_characters: new.convert_half_width_characters,
+            convert_numeric_characters: new.convert_numeric_characters,
+            alphabetic_to_hiragana: new.alphabetic_to_hiragana.unwrap_or_default(),
+            convert_hiragana_to_katakana: new.convert_hiragana_to_katakana,
+            convert_katakana_to_hiragana: new.convert_katakana_to_hiragana,
+            collapse_emphatic_sequences: new.collapse_emphatic_sequences,
+            text_replacements: new.text_replacements,
+            search_resolution: new.search_resolution,
+        }
+    }
+}
+
+/// [AnkiOptions] before kanji fields, overwrite options, field templates,
+/// glossary and frequency formats and the offline queue.
+///
+/// `field_templates` were unused strings without field names; they are dropped when migrating.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnkiOptionsV1 {
+    pub enable: bool,
+    pub server: String,
+    pub tags: Vec<String>,
+    pub screenshot: AnkiScreenshotOptions,
+    pub global_anki_options: Ptr<GlobalAnkiOptions>,
+    pub anki_fields: Option<AnkiFields>,
+    pub duplicate_scope: AnkiDuplicateScope,
+    pub duplicate_scope_check_all_models: bool,
+    pub duplicate_behavior: AnkiDuplicateBehavior,
+    pub check_for_duplicates: bool,
+    pub field_templates: Vec<String>,
+    pub suspend_new_cards: bool,
+    pub display_tags: AnkiDisplayTags,
+    pub note_gui_mode: AnkiNoteGuiMode,
+    pub api_key: String,
+    pub download_timeout: u32,
+}
+
+impl From<AnkiOptionsV1> for AnkiOptions {
+    fn from(old: AnkiOptionsV1) -> Self {
+        let mut new = AnkiOptions::default();
+        new.set_enable(old.enable)
+            .set_server(old.server)
+            .set_tags(old.tags)
+            .set_screenshot(old.screenshot)
+            .set_global_anki_options(old.global_anki_options)
+            .set_anki_fields(old.anki_fields)
+            .set_duplicate_scope(old.duplicate_scope)
+            .set_duplicate_scope_check_all_models(old.duplicate_scope_check_all_models)
+            .set_duplicate_behavior(old.duplicate_behavior)
+            .set_check_for_duplicates(old.check_for_duplicates)
+            .set_suspend_new_cards(old.suspend_new_cards)
+            .set_display_tags(old.display_tags)
+            .set_note_gui_mode(old.note_gui_mode)
+            .set_api_key(old.api_key)
+            .set_download_timeout(old.download_timeout);
+        new
+    }
+}
+
+impl From<AnkiOptions> for AnkiOptionsV1 {
+    fn from(new: AnkiOptions) -> Self {
+        Self {
+            enable: *new.enable(),
+            server: new.server().clone(),
+            tags: new.tags().clone(),
+            screenshot: new.screenshot().clone(),
+            global_anki_options: new.global_anki_options().clone(),
+            anki_fields: new.anki_fields().clone(),
+            duplicate_scope: *new.duplicate_scope(),
+            duplicate_scope_check_all_models: *new.duplicate_scope_check_all_models(),
+            duplicate_behavior: *new.duplicate_behavior(),
+            check_for_duplicates: *new.check_for_duplicates(),
+            field_templates: new
+                .field_templates()
+                .values()
+                .map(|template| template.source().to_string())
+                .collect(),
+            suspend_new_cards: *new.suspend_new_cards(),
+            display_tags: new.display_tags().clone(),
+            note_gui_mode: new.note_gui_mode().clone(),
+            api_key: new.api_key().clone(),
+            download_timeout: *new.download_timeout(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod migration_tests {
+    use super::*;
+    use crate::settings::core::{FrequencyAggregationOptions, RankingStrategyKind};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn decodes_v1_options() {
+        let mut options = YomichanOptions::new();
+        {
+            let profile = options.get_current_profile().unwrap();
+            let mut profile = profile.write();
+            profile.set_language("es");
+            profile.set_main_dictionary("JMdict".into());
+            profile.anki_options_mut().set_server("8766".into());
+        }
+        options.create_new_profile("Second").unwrap();
+        let v1 = YomichanOptionsV1::from(options.clone());
+        let blob = native_model::encode(&v1).unwrap();
+
+        let (decoded, version) = native_model::decode::<YomichanOptions>(blob).unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(decoded, options);
+        let profile = decoded.find_profile_by_name("Second").unwrap();
+        let profile = profile.read();
+        assert_eq!(profile.options.general.language, "es");
+        assert_eq!(profile.anki_options().server(), "8766");
+        assert_eq!(
+            profile.options.general.frequency_aggregation,
+            FrequencyAggregationOptions::default()
+        );
+        assert_eq!(
+            profile.options.translation.ranking_strategy,
+            RankingStrategyKind::Yomitan
+        );
+    }
+}
@@ -1,4 +1,5 @@
 pub mod core;
 pub mod dictionary_options;
 pub mod environment;
+pub mod migration;
 pub mod options;
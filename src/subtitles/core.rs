@@ -0,0 +1,287 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "anki")]
+use crate::{
+    anki::{template::NoteContext, DisplayAnki, DisplayAnkiError, NewNote, NoteMedia},
+    database::note_queue::AddOrQueued,
+};
+use crate::{
+    database::WordStatus,
+    models::dictionary::TermDictionaryEntry,
+    scanner::{
+        core::{Sentence, SentenceParser, TermSearchResults, TermSearchResultsSegment},
+        reading::annotate_readings,
+        vocabulary::lemma_of,
+    },
+    subtitles::parser::{format_timestamp, read_subtitles, SubtitleCue, SubtitleError},
+    Yomichan,
+};
+
+/// Identifies the subtitle cue a [Sentence] was mined from.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct SentenceCue {
+    pub id: String,
+    pub start: Duration,
+    pub end: Duration,
+}
+
+impl From<&SubtitleCue> for SentenceCue {
+    fn from(cue: &SubtitleCue) -> Self {
+        Self {
+            id: cue.id.clone(),
+            start: cue.start,
+            end: cue.end,
+        }
+    }
+}
+
+/// A cue and the segments its text was parsed into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScannedCue {
+    pub cue: SubtitleCue,
+    pub segments: Vec<TermSearchResultsSegment>,
+}
+
+/// A word worth mining: the first occurrence of a not yet known dictionary form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MiningItem {
+    pub term: String,
+    pub reading: String,
+    pub word_status: WordStatus,
+    /// The segment's results; `results.sentence` is the cue it first appeared in.
+    pub results: Arc<TermSearchResults>,
+}
+
+impl MiningItem {
+    /// The best dictionary entry for the word.
+    pub fn entry(&self) -> &TermDictionaryEntry {
+        &self.results.dictionary_entries[0]
+    }
+
+    pub fn sentence(&self) -> &Sentence {
+        &self.results.sentence
+    }
+}
+
+/// Every minable word of an episode, in order of first appearance.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubtitleMiningList {
+    /// Usually the subtitle file stem, e.g. `"Show - 01"`.
+    pub episode: Option<String>,
+    pub items: Vec<MiningItem>,
+}
+
+impl Yomichan {
+    /// Scans every cue's text. The [Sentence] of each result carries the cue id and time range.
+    ///
+    /// Returns `None` if the current user profile cannot be accessed.
+    pub fn scan_subtitles(&self, cues: &[SubtitleCue]) -> Option<Vec<ScannedCue>> {
+        let profile = self.backend.get_current_profile().ok()?;
+        let options = profile.read().options().clone();
+        let scanner = &self.backend.scanner;
+        let scanned = cues
+            .iter()
+            .map(|cue| {
+                let segments = match scanner.search_sentence(&cue.text, &options) {
+                    Some(mut results) => {
+                        results.sentence.cue = Some(cue.into());
+                        let mut segments = SentenceParser::parse(results);
+                        scanner.annotate_word_statuses(&mut segments);
+                        annotate_readings(&mut segments, &options.parsing);
+                        segments
+                    }
+                    None => vec![TermSearchResultsSegment {
+                        text: cue.text.clone(),
+                        results: None,
+                        word_status: None,
+                        reading: None,
+                    }],
+                };
+                ScannedCue {
+                    cue: cue.clone(),
+                    segments,
+                }
+            })
+            .collect();
+        Some(scanned)
+    }
+
+    /// Turns cues into a deduplicated mining list.
+    ///
+    /// Words marked `Known` or `Ignored` in the known words table are skipped.
+    pub fn mine_subtitles(
+        &self,
+        cues: &[SubtitleCue],
+        episode: Option<&str>,
+    ) -> Option<SubtitleMiningList> {
+        let mut items: IndexMap<(String, String), MiningItem> = IndexMap::new();
+        for scanned in self.scan_subtitles(cues)? {
+            for segment in scanned.segments {
+                let Some(results) = segment.results else {
+                    continue;
+                };
+                let Some((term, reading)) = results.dictionary_entries.first().and_then(lemma_of)
+                else {
+                    continue;
+                };
+                let word_status = segment.word_status.unwrap_or_default();
+                if matches!(word_status, WordStatus::Known | WordStatus::Ignored) {
+                    continue;
+                }
+                items
+                    .entry((term.clone(), reading.clone()))
+                    .or_insert(MiningItem {
+                        term,
+                        reading,
+                        word_status,
+                        results,
+                    });
+            }
+        }
+        Some(SubtitleMiningList {
+            episode: episode.map(str::to_string),
+            items: items.into_values().collect(),
+        })
+    }
+
+    /// Reads a `.srt`, `.vtt` or `.ass` file and mines it, using the file stem as the episode.
+    pub fn mine_subtitle_file(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<SubtitleMiningList, SubtitleError> {
+        let path = path.as_ref();
+        let cues = read_subtitles(path)?;
+        let episode = path.file_stem().map(|s| s.to_string_lossy().to_string());
+        self.mine_subtitles(&cues, episode.as_deref())
+            .ok_or(SubtitleError::ProfileUnavailable)
+    }
+}
+
+/// Anki tags for a mined cue: `episode::<episode>` and `time::<HH:MM:SS>`.
+///
+/// Whitespace in the episode name is replaced with `_`, as Anki tags are space separated.
+pub fn subtitle_tags(cue: Option<&SentenceCue>, episode: Option<&str>) -> Vec<String> {
+    let mut tags = Vec::new();
+    if let Some(episode) = episode {
+        let episode: String = episode
+            .chars()
+            .map(|c| if c.is_whitespace() { '_' } else { c })
+            .collect();
+        tags.push(format!("episode::{episode}"));
+    }
+    if let Some(cue) = cue {
+        tags.push(format!("time::{}", format_timestamp(cue.start)));
+    }
+    tags
+}
+
+/// Subtitle hooks for note creation.
+#[cfg(feature = "anki")]
+impl DisplayAnki {
+    /// Builds a note for a mined word. The cue text fills the `Sentence` field and the note
+    /// is tagged with the episode and cue start time (see [subtitle_tags]).
+    pub fn build_note_from_mining_item(
+        &self,
+        item: &MiningItem,
+        episode: Option<&str>,
+    ) -> Result<NewNote, DisplayAnkiError> {
+        let tags = subtitle_tags(item.sentence().cue.as_ref(), episode);
+        let context = NoteContext::new(item.entry(), None).with_sentence(item.sentence());
+        self.build_note_from_context(&context, &tags)
+    }
+}
+
+#[cfg(feature = "anki")]
+impl Yomichan {
+    /// Adds a note for every item of `list` with [Yomichan::add_entry_or_queue_with_tags],
+    /// so the profile's `duplicate_behavior`, `overwrite_keep_fields` and `queue_when_offline`
+    /// apply like for any other note. Notes are tagged like
+    /// [DisplayAnki::build_note_from_mining_item] tags them.
+    ///
+    /// Returns the ids of the added (or overwritten) notes. Duplicates the profile prevents
+    /// are skipped, and queued notes have no id until they are flushed.
+    pub fn add_mining_list(&self, list: &SubtitleMiningList) -> Result<Vec<u64>, DisplayAnkiError> {
+        let mut note_ids = Vec::new();
+        for item in &list.items {
+            let tags = subtitle_tags(item.sentence().cue.as_ref(), list.episode.as_deref());
+            let added = self.add_entry_or_queue_with_tags(
+                item.entry(),
+                Some(&item.sentence().text),
+                &NoteMedia::default(),
+                &tags,
+            );
+            match added {
+                Ok(AddOrQueued::Added(ids)) => note_ids.extend(ids.into_iter().map(|id| id as u64)),
+                Ok(AddOrQueued::Queued(_)) | Err(DisplayAnkiError::DuplicateNote { .. }) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(note_ids)
+    }
+}
+
+#[cfg(test)]
+mod subtitle_mining_tests {
+    use super::*;
+    use crate::subtitles::parser::{parse_subtitles, SubtitleFormat};
+    use crate::utils::test_utils::{TestYomichan, FIXTURE_DICTIONARY};
+
+    #[test]
+    fn tags() {
+        let cue = SentenceCue {
+            id: "12".into(),
+            start: Duration::from_millis(754_250),
+            end: Duration::from_millis(756_000),
+        };
+        assert_eq!(
+            subtitle_tags(Some(&cue), Some("Show - 01")),
+            ["episode::Show_-_01", "time::00:12:34"]
+        );
+        assert!(subtitle_tags(None, None).is_empty());
+    }
+
+    #[test]
+    fn mine_srt() {
+        let ycd = TestYomichan::new("mine_srt", &[FIXTURE_DICTIONARY]);
+        ycd.set_word_status("犬", "いぬ", WordStatus::Known)
+            .unwrap();
+        let srt = "1\n00:00:01,000 --> 00:00:02,500\n猫が鳴く。\n\n\
+                   2\n00:00:03,000 --> 00:00:04,000\n犬は猫を食べる。\n";
+        let cues = parse_subtitles(srt, SubtitleFormat::Srt).unwrap();
+        let list = ycd.mine_subtitles(&cues, Some("Show - 01")).unwrap();
+        assert_eq!(list.episode.as_deref(), Some("Show - 01"));
+
+        // 猫 is only listed for the cue it first appeared in, and the known 犬 is skipped
+        let items: Vec<_> = list
+            .items
+            .iter()
+            .map(|item| {
+                let cue = item.sentence().cue.clone().unwrap();
+                (item.term.as_str(), item.sentence().text.as_str(), cue)
+            })
+            .collect();
+        let cue = |id: &str, start: u64, end: u64| SentenceCue {
+            id: id.to_string(),
+            start: Duration::from_millis(start),
+            end: Duration::from_millis(end),
+        };
+        assert_eq!(
+            items,
+            [
+                ("猫", "猫が鳴く。", cue("1", 1_000, 2_500)),
+                ("鳴く", "猫が鳴く。", cue("1", 1_000, 2_500)),
+                ("食べる", "犬は猫を食べる。", cue("2", 3_000, 4_000)),
+            ]
+        );
+        assert_eq!(
+            subtitle_tags(
+                list.items[2].sentence().cue.as_ref(),
+                list.episode.as_deref()
+            ),
+            ["episode::Show_-_01", "time::00:00:03"]
+        );
+    }
+}
@@ -0,0 +1,7 @@
+//! Subtitle (`.srt`, `.vtt`, `.ass`) ingestion for sentence mining.
+
+pub mod core;
+pub mod parser;
+
+pub use core::{MiningItem, ScannedCue, SentenceCue, SubtitleMiningList};
+pub use parser::{parse_subtitles, read_subtitles, SubtitleCue, SubtitleError, SubtitleFormat};
@@ -0,0 +1,327 @@
+use std::{path::Path, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(thiserror::Error, Debug)]
+pub enum SubtitleError {
+    #[error("failed to read subtitle file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not detect subtitle format of {0}")]
+    UnknownFormat(String),
+    #[error("invalid timestamp `{value}` on line {line}")]
+    InvalidTimestamp { line: usize, value: String },
+    #[error("ass file has no [Events] format line")]
+    MissingAssFormat,
+    #[error("current profile is unavailable")]
+    ProfileUnavailable,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SubtitleFormat {
+    Srt,
+    WebVtt,
+    Ass,
+}
+
+impl SubtitleFormat {
+    /// Detects the format from a file extension (`srt`, `vtt`, `ass`/`ssa`).
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "srt" => Some(Self::Srt),
+            "vtt" => Some(Self::WebVtt),
+            "ass" | "ssa" => Some(Self::Ass),
+            _ => None,
+        }
+    }
+
+    /// Detects the format from the file contents.
+    pub fn detect(content: &str) -> Option<Self> {
+        let content = content.trim_start_matches('\u{feff}').trim_start();
+        if content.starts_with("WEBVTT") {
+            Some(Self::WebVtt)
+        } else if content.starts_with("[Script Info]") || content.contains("\nDialogue:") {
+            Some(Self::Ass)
+        } else if content.contains("-->") {
+            Some(Self::Srt)
+        } else {
+            None
+        }
+    }
+}
+
+/// A single timed line of dialogue.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SubtitleCue {
+    /// The cue identifier: the srt index, the vtt cue id (or position if it has none),
+    /// or the position of the ass `Dialogue` line.
+    pub id: String,
+    pub start: Duration,
+    pub end: Duration,
+    /// The cue text with formatting tags removed. Lines are joined with `\n`.
+    pub text: String,
+}
+
+/// Parses `content` as `format`. Cues with no text left after stripping tags are dropped.
+pub fn parse_subtitles(
+    content: &str,
+    format: SubtitleFormat,
+) -> Result<Vec<SubtitleCue>, SubtitleError> {
+    let content = content.trim_start_matches('\u{feff}');
+    let cues = match format {
+        SubtitleFormat::Srt => parse_timed_blocks(content, false)?,
+        SubtitleFormat::WebVtt => parse_timed_blocks(content, true)?,
+        SubtitleFormat::Ass => parse_ass(content)?,
+    };
+    Ok(cues.into_iter().filter(|c| !c.text.is_empty()).collect())
+}
+
+/// Reads and parses a subtitle file, detecting the format from its extension or contents.
+pub fn read_subtitles(path: impl AsRef<Path>) -> Result<Vec<SubtitleCue>, SubtitleError> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path)?;
+    let format = SubtitleFormat::from_path(path)
+        .or_else(|| SubtitleFormat::detect(&content))
+        .ok_or_else(|| SubtitleError::UnknownFormat(path.display().to_string()))?;
+    parse_subtitles(&content, format)
+}
+
+/// Srt and WebVTT share the same block layout:
+/// an optional id line, a `start --> end` timing line, then text lines until a blank line.
+fn parse_timed_blocks(content: &str, is_vtt: bool) -> Result<Vec<SubtitleCue>, SubtitleError> {
+    let mut cues = Vec::new();
+    let lines: Vec<&str> = content.lines().collect();
+    let mut i = 0;
+    while i < lines.len() {
+        let Some(timing_offset) = lines[i..]
+            .iter()
+            .take_while(|l| !l.trim().is_empty())
+            .position(|l| l.contains("-->"))
+        else {
+            // not a cue (vtt header, NOTE/STYLE/REGION blocks, garbage): skip the block
+            i += lines[i..]
+                .iter()
+                .position(|l| l.trim().is_empty())
+                .unwrap_or(lines.len() - i)
+                + 1;
+            continue;
+        };
+        let timing_idx = i + timing_offset;
+        let id = match timing_offset {
+            0 => (cues.len() + 1).to_string(),
+            _ => lines[timing_idx - 1].trim().to_string(),
+        };
+        let (start, end) = parse_timing_line(lines[timing_idx], timing_idx + 1)?;
+        let text_lines: Vec<String> = lines[timing_idx + 1..]
+            .iter()
+            .take_while(|l| !l.trim().is_empty())
+            .map(|l| strip_markup_tags(l, is_vtt))
+            .filter(|l| !l.is_empty())
+            .collect();
+        i = timing_idx + 1 + text_lines.len();
+        // skip to the end of the block
+        while i < lines.len() && !lines[i].trim().is_empty() {
+            i += 1;
+        }
+        cues.push(SubtitleCue {
+            id,
+            start,
+            end,
+            text: text_lines.join("\n"),
+        });
+    }
+    Ok(cues)
+}
+
+fn parse_timing_line(line: &str, line_no: usize) -> Result<(Duration, Duration), SubtitleError> {
+    let (start, rest) = line.split_once("-->").unwrap_or((line, ""));
+    // vtt cue settings follow the end timestamp: `00:01.000 --> 00:02.000 align:start`
+    let end = rest.split_whitespace().next().unwrap_or_default();
+    let parse = |value: &str| {
+        parse_timestamp(value.trim()).ok_or_else(|| SubtitleError::InvalidTimestamp {
+            line: line_no,
+            value: value.trim().to_string(),
+        })
+    };
+    Ok((parse(start)?, parse(end)?))
+}
+
+/// Parses `HH:MM:SS,mmm`, `HH:MM:SS.mmm`, `MM:SS.mmm` and ass `H:MM:SS.cc` timestamps.
+pub fn parse_timestamp(value: &str) -> Option<Duration> {
+    let value = value.replace(',', ".");
+    let (hms, fraction) = value.split_once('.').unwrap_or((value.as_str(), "0"));
+    let mut secs: u64 = 0;
+    let parts: Vec<&str> = hms.split(':').collect();
+    if !(2..=3).contains(&parts.len()) {
+        return None;
+    }
+    for part in parts {
+        secs = secs * 60 + part.trim().parse::<u64>().ok()?;
+    }
+    if fraction.is_empty() || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    // normalize ".5", ".50" and ".500" to milliseconds
+    let millis: u64 = format!("{fraction:0<3}")[..3].parse().ok()?;
+    Some(Duration::from_secs(secs) + Duration::from_millis(millis))
+}
+
+/// Formats a duration as `HH:MM:SS`, e.g. for Anki tags.
+pub fn format_timestamp(time: Duration) -> String {
+    let secs = time.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        (secs / 60) % 60,
+        secs % 60
+    )
+}
+
+/// Removes html-like tags (`<i>`, `<font ..>`, vtt `<c.x>`/`<v Name>`/`<00:00:01.000>`)
+/// and srt ass-style positioning (`{\an8}`).
+fn strip_markup_tags(line: &str, is_vtt: bool) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut closing = None;
+    for c in line.chars() {
+        match (c, closing) {
+            ('<', None) => closing = Some('>'),
+            ('{', None) if !is_vtt => closing = Some('}'),
+            (c, Some(close)) if c == close => closing = None,
+            (_, Some(_)) => {}
+            (c, None) => out.push(c),
+        }
+    }
+    let out = match is_vtt {
+        true => out
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&nbsp;", " ")
+            .replace("&amp;", "&"),
+        false => out,
+    };
+    out.trim().to_string()
+}
+
+fn parse_ass(content: &str) -> Result<Vec<SubtitleCue>, SubtitleError> {
+    let mut in_events = false;
+    let mut columns: Option<Vec<String>> = None;
+    let mut cues = Vec::new();
+    for (line_idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_events = line.eq_ignore_ascii_case("[Events]");
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+        if let Some(format) = line.strip_prefix("Format:") {
+            columns = Some(
+                format
+                    .split(',')
+                    .map(|c| c.trim().to_ascii_lowercase())
+                    .collect(),
+            );
+            continue;
+        }
+        let Some(dialogue) = line.strip_prefix("Dialogue:") else {
+            continue;
+        };
+        let columns = columns.as_ref().ok_or(SubtitleError::MissingAssFormat)?;
+        let col = |name: &str| columns.iter().position(|c| c == name);
+        let (Some(start_col), Some(end_col), Some(text_col)) =
+            (col("start"), col("end"), col("text"))
+        else {
+            return Err(SubtitleError::MissingAssFormat);
+        };
+        // the text column is always last and may itself contain commas
+        let values: Vec<&str> = dialogue.splitn(columns.len(), ',').collect();
+        let get = |idx: usize| values.get(idx).map(|v| v.trim()).unwrap_or_default();
+        let parse = |value: &str| {
+            parse_timestamp(value).ok_or_else(|| SubtitleError::InvalidTimestamp {
+                line: line_idx + 1,
+                value: value.to_string(),
+            })
+        };
+        cues.push(SubtitleCue {
+            id: (cues.len() + 1).to_string(),
+            start: parse(get(start_col))?,
+            end: parse(get(end_col))?,
+            text: strip_ass_text(get(text_col)),
+        });
+    }
+    Ok(cues)
+}
+
+/// Removes `{...}` override blocks and converts ass line breaks and hard spaces.
+fn strip_ass_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_override = false;
+    for c in text.chars() {
+        match c {
+            '{' => in_override = true,
+            '}' if in_override => in_override = false,
+            _ if in_override => {}
+            _ => out.push(c),
+        }
+    }
+    out.replace("\\N", "\n")
+        .replace("\\n", "\n")
+        .replace("\\h", " ")
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod subtitle_parser_tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(parse_timestamp("00:00:01,500"), Some(ms(1_500)));
+        assert_eq!(parse_timestamp("01:02.250"), Some(ms(62_250)));
+        assert_eq!(parse_timestamp("0:00:03.07"), Some(ms(3_070)));
+        assert_eq!(parse_timestamp("nope"), None);
+        assert_eq!(format_timestamp(ms(3_723_900)), "01:02:03");
+    }
+
+    #[test]
+    fn srt() {
+        let srt = "\u{feff}1\r\n00:00:01,000 --> 00:00:02,500\r\n<i>猫が</i>\r\n{\\an8}鳴いた。\r\n\r\n2\r\n00:00:03,000 --> 00:00:04,000\r\nはい\r\n";
+        let cues = parse_subtitles(srt, SubtitleFormat::Srt).unwrap();
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].id, "1");
+        assert_eq!(cues[0].text, "猫が\n鳴いた。");
+        assert_eq!(cues[0].end, ms(2_500));
+        assert_eq!(cues[1].text, "はい");
+    }
+
+    #[test]
+    fn webvtt() {
+        let vtt = "WEBVTT\n\nNOTE a comment\nspanning lines\n\nintro\n00:01.000 --> 00:02.000 align:start\n<v Mio>おはよう</v>\n\n00:00:03.000 --> 00:00:04.000\n<c.yellow>また</c> &amp; ね\n";
+        let cues = parse_subtitles(vtt, SubtitleFormat::WebVtt).unwrap();
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].id, "intro");
+        assert_eq!(cues[0].text, "おはよう");
+        assert_eq!(cues[1].id, "2");
+        assert_eq!(cues[1].text, "また & ね");
+        assert_eq!(cues[1].start, ms(3_000));
+    }
+
+    #[test]
+    fn ass() {
+        let ass = "[Script Info]\nTitle: test\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nComment: 0,0:00:00.00,0:00:01.00,Default,,0,0,0,,skip\nDialogue: 0,0:00:01.50,0:00:03.00,Default,,0,0,0,,{\\i1}行くよ、{\\i0}早く\\Nね\n";
+        let cues = parse_subtitles(ass, SubtitleFormat::Ass).unwrap();
+        assert_eq!(cues.len(), 1);
+        assert_eq!(cues[0].text, "行くよ、早く\nね");
+        assert_eq!(cues[0].start, ms(1_500));
+        assert_eq!(SubtitleFormat::detect(ass), Some(SubtitleFormat::Ass));
+    }
+}
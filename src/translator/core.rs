@@ -1,3 +1,6 @@
+use crate::japanese::romaji::{is_romaji, romaji_to_kana};
+use crate::translator::frequency::aggregate_frequency;
+use crate::translator::ranking::RankingStrategy;
 use crate::translator::regex_util::apply_text_replacement;
 use crate::translator::{
     internal_types::{
@@ -13,7 +16,7 @@ use crate::translator::{
 use crate::{
     backend::FindTermsDetails,
     database::{
-        dictionary_database::{DatabaseMetaMatchType, DictionarySet},
+        dictionary_database::{DatabaseMetaMatchType, DictionaryDatabaseError, DictionarySet},
         DatabaseTag, DatabaseTermMeta, DictionaryService, GenericQueryRequest, QueryType,
         TermExactQueryRequest,
     },
@@ -22,8 +25,10 @@ use crate::{
         TermSource,
     },
     settings::core::{
-        DictionaryOptions, GeneralOptions, ProfileOptions, ScanningOptions, SearchResolution,
-        TranslationOptions, TranslationTextReplacementGroup, TranslationTextReplacementOptions,
+        DictionaryOptions, FrequencyAggregationOptions, GeneralOptions, ProfileOptions,
+        RankingStrategyKind, ScanningOptions, SearchResolution,
+        TranslationCollapseEmphaticSequences, TranslationConvertType, TranslationOptions,
+        TranslationTextReplacementGroup, TranslationTextReplacementOptions,
     },
 };
 
@@ -95,6 +100,8 @@ pub struct Translator {
     pub number_regex: &'static Regex,
     pub text_processors: TextProcessorMap,
     pub reading_normalizers: ReadingNormalizerMap,
+    /// Strategies selectable with [RankingStrategyKind::Custom].
+    pub ranking_strategies: RwLock<IndexMap<String, Arc<dyn RankingStrategy>>>,
 }
 
 static TRANSLATOR_NUMBER_REGEX: LazyLock<Regex> =
@@ -114,6 +121,7 @@ impl Translator {
             number_regex: &*TRANSLATOR_NUMBER_REGEX,
             text_processors: IndexMap::new(),
             reading_normalizers: IndexMap::new(),
+            ranking_strategies: RwLock::new(IndexMap::new()),
         }
     }
     /// Initializes the instance for use.
@@ -172,7 +180,6 @@ impl Translator {
         text: &str,
         opts: &FindTermsOptions,
     ) -> FindTermsResult {
-        dbg!(text, mode, opts);
         let mut text = text.to_string();
         let FindTermsOptions {
             enabled_dictionary_map,
@@ -181,6 +188,9 @@ impl Translator {
             sort_frequency_dictionary_order,
             language,
             primary_reading,
+            offset,
+            max_results,
+            frequency_aggregation,
             ..
         } = opts;
 
@@ -189,7 +199,6 @@ impl Translator {
             mut dictionary_entries,
             original_text_length,
         } = self.find_terms_internal(&mut text, opts, &mut tag_aggregator, primary_reading);
-        dbg!(&dictionary_entries.len());
         match mode {
             FindTermsMode::Group => {
                 dictionary_entries = self._group_dictionary_entries_by_headword(
@@ -206,7 +215,8 @@ impl Translator {
                     &mut tag_aggregator,
                 )
             }
-            _ => {}
+            // every database entry stays its own dictionary entry
+            FindTermsMode::Split | FindTermsMode::Simple => {}
         }
         if let Some(exclude_dictionary_definitions) = exclude_dictionary_definitions {
             Translator::_remove_excluded_definitions(
@@ -214,41 +224,30 @@ impl Translator {
                 exclude_dictionary_definitions,
             );
         }
-        match mode != FindTermsMode::Simple {
-            true => {
-                self._add_term_meta(
-                    &mut dictionary_entries,
-                    enabled_dictionary_map,
-                    &mut tag_aggregator,
-                );
-                self._expand_tag_groups_and_group(&mut tag_aggregator.get_tag_expansion_targets());
-            }
-            // this is most likely incorrect because of references
-            false => {
-                if let Some(sort_frequency_dictionary) = sort_frequency_dictionary {
-                    let mut sort_dictionary_map: TermEnabledDictionaryMap = IndexMap::new();
-                    let value = enabled_dictionary_map.get(sort_frequency_dictionary);
-                    // in js the values inside the map (sort_frequency_dictionary & value)
-                    // return mutable references with .get(),
-                    // if _add_term_meta updates these, it's updating clones in rust
-                    // sort_dictionary_map might need
-                    // to hold mutable refs since it doesnt get used after this block
-                    //
-                    // if this is true, don't use the TermEnabledDictionaryMap type,
-                    // as it holds owned values
-                    if let Some(value) = value {
-                        sort_dictionary_map
-                            .insert(sort_frequency_dictionary.clone(), value.clone());
-                    }
-                    self._add_term_meta(
-                        &mut dictionary_entries,
-                        &sort_dictionary_map,
-                        &mut tag_aggregator,
-                    );
-                }
-            }
+        // Only the sort dictionaries' frequencies are needed to order the entries,
+        // the rest of the meta is added after pagination so discarded entries are never looked up.
+        let sort_dictionaries: IndexSet<String> = sort_frequency_dictionary
+            .iter()
+            .cloned()
+            .chain(
+                frequency_aggregation
+                    .sources
+                    .iter()
+                    .map(|s| s.dictionary.clone()),
+            )
+            .collect();
+        if !sort_dictionaries.is_empty() {
+            let sort_dictionary_map: TermEnabledDictionaryMap = enabled_dictionary_map
+                .iter()
+                .filter(|(name, _)| sort_dictionaries.contains(*name))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect();
+            self._add_term_meta(
+                &mut dictionary_entries,
+                &sort_dictionary_map,
+                &mut tag_aggregator,
+            );
         }
-
         if let Some(sort_frequency_dictionary) = sort_frequency_dictionary {
             Translator::_update_sort_frequencies(
                 &mut dictionary_entries,
@@ -256,8 +255,43 @@ impl Translator {
                 *sort_frequency_dictionary_order == FindTermsSortOrder::Ascending,
             );
         }
+        if frequency_aggregation.is_enabled() {
+            Translator::_update_aggregate_frequencies(
+                &mut dictionary_entries,
+                frequency_aggregation,
+            );
+        }
         if dictionary_entries.len() > 1 {
-            self._sort_term_dictionary_entries(&mut dictionary_entries);
+            self._sort_term_dictionary_entries(&mut dictionary_entries, &opts.ranking_strategy);
+        }
+
+        let total_results = dictionary_entries.len();
+        Translator::_paginate_term_dictionary_entries(
+            &mut dictionary_entries,
+            *offset,
+            *max_results,
+        );
+
+        if mode != FindTermsMode::Simple {
+            // Tags are expanded for the kept entries only. The sort dictionaries' meta is
+            // looked up again with the rest, so frequencies and pronunciations come out
+            // in the same order as without pagination.
+            let mut page_tag_aggregator = tag_aggregator.for_entries(
+                dictionary_entries
+                    .iter()
+                    .flat_map(|entry| entry.definitions.iter())
+                    .map(|definition| definition.id.as_str()),
+            );
+            for entry in &mut dictionary_entries {
+                entry.frequencies.clear();
+                entry.pronunciations.clear();
+            }
+            self._add_term_meta(
+                &mut dictionary_entries,
+                enabled_dictionary_map,
+                &mut page_tag_aggregator,
+            );
+            self._expand_tag_groups_and_group(&mut page_tag_aggregator.get_tag_expansion_targets());
         }
 
         dictionary_entries.iter_mut().for_each(|entry| {
@@ -304,6 +338,21 @@ impl Translator {
         FindTermsResult {
             dictionary_entries: with_user_facing_inflections,
             original_text_length,
+            total_results,
+        }
+    }
+
+    /// Keeps at most `max_results` entries, starting at `offset`.
+    ///
+    /// `max_results` of `None` keeps every entry after `offset`.
+    fn _paginate_term_dictionary_entries<T>(
+        dictionary_entries: &mut Vec<T>,
+        offset: usize,
+        max_results: Option<usize>,
+    ) {
+        dictionary_entries.drain(..offset.min(dictionary_entries.len()));
+        if let Some(max_results) = max_results {
+            dictionary_entries.truncate(max_results);
         }
     }
 
@@ -336,6 +385,9 @@ impl Translator {
                     sort_frequency_dictionary,
                     sort_frequency_dictionary_order,
                     language,
+                    enable_wanakana,
+                    max_results,
+                    frequency_aggregation,
                     ..
                 },
             scanning: ScanningOptions { alphanumeric, .. },
@@ -343,12 +395,20 @@ impl Translator {
                 TranslationOptions {
                     text_replacements: text_replacements_opts,
                     search_resolution,
-                    ..
+                    alphabetic_to_hiragana,
+                    convert_half_width_characters,
+                    convert_numeric_characters,
+                    convert_hiragana_to_katakana,
+                    convert_katakana_to_hiragana,
+                    collapse_emphatic_sequences,
+                    ranking_strategy,
                 },
             ..
         } = opts;
 
         let text_replacements = Self::_get_translator_text_replacements(text_replacements_opts);
+        let alphabetic_to_hiragana =
+            Translator::_get_alphabetic_to_hiragana(*alphabetic_to_hiragana, *enable_wanakana);
         let mut exclude_dictionary_definitions: Option<IndexSet<String>> = None;
         if mode == FindTermsMode::Merge && !enabled_dictionary_map.contains_key(main_dictionary) {
             let new = FindTermDictionary {
@@ -379,6 +439,16 @@ impl Translator {
             exclude_dictionary_definitions: exclude_dictionary_definitions.clone(),
             search_resolution: *search_resolution,
             language: language.to_string(),
+            alphabetic_to_hiragana,
+            offset: 0,
+            max_results: (*max_results > 0).then_some(*max_results as usize),
+            convert_half_width_characters: *convert_half_width_characters,
+            convert_numeric_characters: *convert_numeric_characters,
+            convert_hiragana_to_katakana: *convert_hiragana_to_katakana,
+            convert_katakana_to_hiragana: *convert_katakana_to_hiragana,
+            collapse_emphatic_sequences: *collapse_emphatic_sequences,
+            ranking_strategy: ranking_strategy.clone(),
+            frequency_aggregation: frequency_aggregation.clone(),
         }
     }
 
@@ -478,6 +548,7 @@ impl Translator {
                 inflection_rule_chain_candidates: _,
                 score,
                 frequency_order,
+                aggregate_frequency,
                 dictionary_alias,
                 dictionary_index,
                 source_term_exact_match_count,
@@ -498,6 +569,7 @@ impl Translator {
                 inflection_rule_chain_candidates: expanded_chains,
                 score,
                 frequency_order,
+                aggregate_frequency,
                 dictionary_alias,
                 dictionary_index,
                 source_term_exact_match_count,
@@ -630,127 +702,35 @@ impl Translator {
     fn _sort_term_dictionary_entries(
         &self,
         dictionary_entries: &mut [InternalTermDictionaryEntry],
+        ranking_strategy: &RankingStrategyKind,
     ) {
-
-        dictionary_entries.sort_by(|v1, v2| {
-            // 1. Sort by reading match (descending: true comes before false)
-            // (v2.match_primary_reading ? 1 : 0) - (v1.match_primary_reading ? 1 : 0)
-            // This means v2 true and v1 false -> 1 (v1 < v2, so v2 comes first)
-            // v2 false and v1 true -> -1 (v1 > v2, so v1 comes first)
-            // In Rust, .cmp orders bools as false < true. We want true first, so v2.cmp(&v1).
-            let cmp = v2.match_primary_reading.cmp(&v1.match_primary_reading);
-            if cmp != Ordering::Equal {
-                return cmp;
-            }
-
-            // 2. Sort by length of source term (descending)
-            // i = v2.max_original_text_length - v1.max_original_text_length;
-            let cmp = v2
-                .max_original_text_length
-                .cmp(&v1.max_original_text_length);
-            if cmp != Ordering::Equal {
-                return cmp;
-            }
-
-            // 3. Sort by length of the shortest text processing chain (ascending)
-            // i = self._get_shortest_text_processing_chain_length(v1.text_processor_rule_chain_candidates) - self._get_shortest_text_processing_chain_length(v2.text_processor_rule_chain_candidates);
-            let len1 = self._get_shortest_text_processing_chain_length(
-                &v1.text_processor_rule_chain_candidates,
-            );
-            let len2 = self._get_shortest_text_processing_chain_length(
-                &v2.text_processor_rule_chain_candidates,
-            );
-            let cmp = len1.cmp(&len2);
-            if cmp != Ordering::Equal {
-                return cmp;
-            }
-
-            // 4. Sort by length of the shortest inflection chain (ascending)
-            // i = self._get_shortest_inflection_chain_length(v1.inflection_rule_chain_candidates) - self._get_shortest_inflection_chain_length(v2.inflection_rule_chain_candidates);
-            let len1 =
-                self._get_shortest_inflection_chain_length(&v1.inflection_rule_chain_candidates);
-            let len2 =
-                self._get_shortest_inflection_chain_length(&v2.inflection_rule_chain_candidates);
-            let cmp = len1.cmp(&len2);
-            if cmp != Ordering::Equal {
-                return cmp;
-            }
-
-            // 5. Sort by how many terms exactly match the source (descending)
-            // i = v2.source_term_exact_match_count - v1.source_term_exact_match_count;
-            let cmp = v2
-                .source_term_exact_match_count
-                .cmp(&v1.source_term_exact_match_count);
-            if cmp != Ordering::Equal {
-                return cmp;
-            }
-
-            // 6. Sort by frequency order (ascending)
-            // i = v1.frequency_order - v2.frequency_order;
-            let cmp = v1.frequency_order.cmp(&v2.frequency_order);
-            if cmp != Ordering::Equal {
-                return cmp;
-            }
-
-            // 7. Sort by dictionary order (ascending)
-            // i = v1.dictionary_index - v2.dictionary_index;
-            let cmp = v1.dictionary_index.cmp(&v2.dictionary_index);
-            if cmp != Ordering::Equal {
-                return cmp;
-            }
-
-            let cmp = v2.score.cmp(&v1.score);
-            if cmp != Ordering::Equal {
-                return cmp;
-            }
-
-            // 9. Sort by headword term text
-            let headwords1 = &v1.headwords;
-            let headwords2 = &v2.headwords;
-            let min_len = headwords1.len().min(headwords2.len());
-
-            for j in 0..min_len {
-                let term1 = &headwords1[j].term;
-                let term2 = &headwords2[j].term;
-
-                // Sort by term length (descending)
-                // i = term2.length - term1.length;
-                let cmp_len = term2.len().cmp(&term1.len());
-                if cmp_len != Ordering::Equal {
-                    return cmp_len;
-                }
-
-                // Sort by string comparison (ascending)
-                // i = stringComparer.compare(term1, term2);
-                let cmp_str = term1.cmp(term2);
-                if cmp_str != Ordering::Equal {
-                    return cmp_str;
-                }
-            }
-            v2.definitions.len().cmp(&v1.definitions.len())
-        });
+        let strategy = self.ranking_strategy(ranking_strategy);
+        dictionary_entries.sort_by(|v1, v2| strategy.compare(v1, v2));
     }
 
-    fn _get_shortest_text_processing_chain_length(
-        &self,
-        candidates: &[TextProcessorRuleChainCandidate],
-    ) -> usize {
-        candidates
-            .iter()
-            .map(|chain| chain.len())
-            .min()
-            .unwrap_or(0)
-    }
-
-    fn _get_shortest_inflection_chain_length(
-        &self,
-        candidates: &[InternalInflectionRuleChainCandidate],
-    ) -> usize {
-        candidates
-            .iter()
-            .map(|candidate| candidate.inflection_rules.len())
-            .min()
-            .unwrap_or(0)
+    /// Sets the aggregate frequency of every entry (its best headword's) and uses it
+    /// as the frequency order of entries and definitions.
+    fn _update_aggregate_frequencies(
+        dictionary_entries: &mut [InternalTermDictionaryEntry],
+        aggregation: &FrequencyAggregationOptions,
+    ) {
+        for entry in dictionary_entries.iter_mut() {
+            let headword_frequencies: Vec<Option<i128>> = (0..entry.headwords.len())
+                .map(|headword_index| {
+                    aggregate_frequency(aggregation, &entry.frequencies, Some(headword_index))
+                })
+                .collect();
+            entry.aggregate_frequency = headword_frequencies.iter().flatten().min().copied();
+            entry.frequency_order = entry.aggregate_frequency.unwrap_or(i128::MAX);
+            for definition in entry.definitions.iter_mut() {
+                let frequency = definition
+                    .headword_indices
+                    .iter()
+                    .filter_map(|i| headword_frequencies.get(*i).copied().flatten())
+                    .min();
+                definition.frequency_order = frequency.unwrap_or(i128::MAX);
+            }
+        }
     }
 
     // Corrected _update_sort_frequencies
@@ -1059,7 +1039,7 @@ impl Translator {
     }
 
     /// this is called _create_tag() in js
-    fn _create_dictionary_tag(
+    pub(crate) fn _create_dictionary_tag(
         database_tag: Option<DatabaseTag>,
         name: String,
         dictionary: String,
@@ -1405,14 +1385,10 @@ impl Translator {
         primary_reading: &str,
     ) -> FindInternalTermsResult {
         let FindTermsOptions {
-            remove_non_japanese_characters,
             enabled_dictionary_map,
             ..
         } = opts;
-        if *remove_non_japanese_characters && ["ja", "zh", "yue"].contains(&opts.language.as_str())
-        {
-            *text = Translator::get_japanese_chinese_only_text(text);
-        }
+        Translator::_prepare_search_text(text, opts);
         if text.is_empty() {
             return FindInternalTermsResult::default();
         }
@@ -1425,6 +1401,17 @@ impl Translator {
         )
     }
 
+    /// Strips non-Japanese/Chinese characters from `text` if
+    /// [FindTermsOptions::remove_non_japanese_characters] applies to it.
+    pub(crate) fn _prepare_search_text(text: &mut String, opts: &FindTermsOptions) {
+        if opts.remove_non_japanese_characters
+            && ["ja", "zh", "yue"].contains(&opts.language.as_str())
+            && Translator::_get_romaji_conversion(text, opts).is_none()
+        {
+            *text = Translator::get_japanese_chinese_only_text(text);
+        }
+    }
+
     // Corrected logic for _remove_excluded_definitions
     fn _remove_excluded_definitions(
         dictionary_entries: &mut Vec<InternalTermDictionaryEntry>,
@@ -1850,7 +1837,6 @@ impl Translator {
                 eprintln!("Error finding terms exact bulk: {e:?}");
                 Vec::new()
             });
-        dbg!(&database_entries);
         // this._sortDatabaseEntriesByIndex(databaseEntries);
         // Assuming TermEntry has an `index` field which is the original index from term_list
         database_entries.sort_by_key(|e| e.index);
@@ -2627,8 +2613,8 @@ impl Translator {
         };
         let headword_tag_groups: Vec<DictionaryTag> = vec![];
         let definition_tag_groups: Vec<DictionaryTag> = vec![];
-        tag_aggregator.add_tags(&headword_tag_groups, &dictionary, &term_tags);
-        tag_aggregator.add_tags(&definition_tag_groups, &dictionary, &definition_tags);
+        tag_aggregator.add_entry_tags(&headword_tag_groups, &id, &dictionary, &term_tags);
+        tag_aggregator.add_entry_tags(&definition_tag_groups, &id, &dictionary, &definition_tags);
         let headwords = vec![Translator::_create_term_headword(
             0,
             term,
@@ -2729,6 +2715,7 @@ impl Translator {
             inflection_rule_chain_candidates,
             score,
             frequency_order: 0,
+            aggregate_frequency: None,
             dictionary_index,
             source_term_exact_match_count,
             max_original_text_length,
@@ -2790,19 +2777,28 @@ impl Translator {
         }
     }
 
-    fn _get_deinflections(&self, text: &str, opts: &FindTermsOptions) -> Vec<DatabaseDeinflection> {
-        let mut deinflections = if opts.deinflect {
-            self._get_algorithm_deinflections(text, opts).unwrap()
+    /// The deinflections of `text` to look up, before any database entries are added.
+    pub(crate) fn _get_candidate_deinflections(
+        &self,
+        text: &str,
+        opts: &FindTermsOptions,
+    ) -> Result<Vec<DatabaseDeinflection>, TranslatorError> {
+        if opts.deinflect {
+            self._get_algorithm_deinflections(text, opts)
         } else {
-            vec![Translator::_create_deinflection(
+            Ok(vec![Translator::_create_deinflection(
                 text,
                 text,
                 text,
                 0,
                 vec![],
                 vec![],
-            )]
-        };
+            )])
+        }
+    }
+
+    fn _get_deinflections(&self, text: &str, opts: &FindTermsOptions) -> Vec<DatabaseDeinflection> {
+        let mut deinflections = self._get_candidate_deinflections(text, opts).unwrap();
         if deinflections.is_empty() {
             return vec![];
         }
@@ -2941,11 +2937,10 @@ impl Translator {
         let mut source_cache = IndexMap::new();
         let mut raw_source = text.to_string();
         while !raw_source.is_empty() {
-            let text_replacements = Translator::_get_text_replacement_variants(opts);
-            let pre_processed_text_variants = Translator::_get_text_variants(
+            let pre_processed_text_variants = Translator::_get_preprocessed_text_variants(
                 &raw_source,
                 pre,
-                text_replacements,
+                opts,
                 &mut source_cache,
             );
             for pre_processed_variant in pre_processed_text_variants {
@@ -2961,6 +2956,7 @@ impl Translator {
                         post,
                         vec![None],
                         &mut source_cache,
+                        opts,
                     );
                     for post_processed_variant in postprocessed_text_variants {
                         let (transformed_text, postprocessor_rule_chain_candidates) =
@@ -3011,11 +3007,132 @@ impl Translator {
         Ok(db_deinflections)
     }
 
+    /// Every preprocessor variant of `raw_source` (and of its romaji conversion),
+    /// with the text processor rule chains that produce it.
+    pub(crate) fn _get_preprocessed_text_variants(
+        raw_source: &str,
+        pre: &[TextProcessorWithId],
+        opts: &FindTermsOptions,
+        source_cache: &mut TextCache,
+    ) -> VariantAndTextProcessorRuleChainCandidatesMap {
+        let mut pre_processed_text_variants: VariantAndTextProcessorRuleChainCandidatesMap =
+            IndexMap::new();
+        for (raw_variant, rule) in Translator::_get_romaji_variants(raw_source, opts) {
+            let text_replacements = Translator::_get_text_replacement_variants(opts);
+            let variants = Translator::_get_text_variants(
+                &raw_variant,
+                pre,
+                text_replacements,
+                source_cache,
+                opts,
+            );
+            for (variant, mut candidates) in variants {
+                if let Some(rule) = rule {
+                    for candidate in candidates.iter_mut() {
+                        candidate.insert(0, rule.to_string());
+                    }
+                }
+                pre_processed_text_variants
+                    .entry(variant)
+                    .or_default()
+                    .extend(candidates);
+            }
+        }
+        pre_processed_text_variants
+    }
+
+    /// How romaji is converted to hiragana: the profile's `alphabetic_to_hiragana` if set,
+    /// or else like an IME (only the converted text) if `enable_wanakana` is on.
+    fn _get_alphabetic_to_hiragana(
+        option: Option<TranslationConvertType>,
+        enable_wanakana: bool,
+    ) -> TranslationConvertType {
+        match (option, enable_wanakana) {
+            (Some(convert), _) => convert,
+            (None, true) => TranslationConvertType::True,
+            (None, false) => TranslationConvertType::False,
+        }
+    }
+
+    /// The hiragana form of `text` if it is romaji and
+    /// [FindTermsOptions::alphabetic_to_hiragana] is enabled for Japanese.
+    fn _get_romaji_conversion(text: &str, opts: &FindTermsOptions) -> Option<String> {
+        if opts.language != "ja"
+            || opts.alphabetic_to_hiragana == TranslationConvertType::False
+            || !is_romaji(text)
+        {
+            return None;
+        }
+        let kana = romaji_to_kana(text);
+        // incomplete trailing syllables (e.g. the "r" of "taber") can't be looked up
+        (!kana.chars().any(|c| c.is_ascii_alphabetic())).then_some(kana)
+    }
+
+    /// `text` and/or its romaji conversion, paired with the text processor rule
+    /// that produced it.
+    fn _get_romaji_variants(
+        text: &str,
+        opts: &FindTermsOptions,
+    ) -> Vec<(String, Option<&'static str>)> {
+        let original = (text.to_string(), None);
+        let Some(kana) = Translator::_get_romaji_conversion(text, opts) else {
+            return vec![original];
+        };
+        let converted = (kana, Some("alphabeticToHiragana"));
+        match opts.alphabetic_to_hiragana {
+            TranslationConvertType::Variant => vec![original, converted],
+            _ => vec![converted],
+        }
+    }
+
+    /// Whether the option at `index` of the text processor `id` should be applied,
+    /// according to the profile's [TranslationOptions].
+    ///
+    /// The option lists of Yomitan's text processors start with their "off" setting:
+    /// - `convertHalfWidthCharacters`, `alphanumericWidthVariants`, `alphabeticToHiragana`:
+    ///   `[false, true]`
+    /// - `convertHiraganaToKatakana`: `[off, direct, inverse]`
+    /// - `collapseEmphaticSequences`: `[[false, false], [true, false], [true, true]]`
+    ///
+    /// Options of any other processor are always applied.
+    fn _is_text_processor_option_enabled(id: &str, index: usize, opts: &FindTermsOptions) -> bool {
+        let convert = |convert_type: TranslationConvertType| match convert_type {
+            TranslationConvertType::False => index == 0,
+            TranslationConvertType::True => index != 0,
+            TranslationConvertType::Variant => true,
+        };
+        match id {
+            "convertHalfWidthCharacters" => convert(opts.convert_half_width_characters),
+            "alphanumericWidthVariants" => convert(opts.convert_numeric_characters),
+            "alphabeticToHiragana" => convert(opts.alphabetic_to_hiragana),
+            "convertHiraganaToKatakana" => {
+                let direct = opts.convert_hiragana_to_katakana;
+                let inverse = opts.convert_katakana_to_hiragana;
+                match index {
+                    0 => {
+                        direct != TranslationConvertType::True
+                            && inverse != TranslationConvertType::True
+                    }
+                    1 => direct != TranslationConvertType::False,
+                    2 => inverse != TranslationConvertType::False,
+                    _ => true,
+                }
+            }
+            "collapseEmphaticSequences" => match opts.collapse_emphatic_sequences {
+                TranslationCollapseEmphaticSequences::False => index == 0,
+                TranslationCollapseEmphaticSequences::True => index <= 1,
+                TranslationCollapseEmphaticSequences::Full => true,
+            },
+            _ => true,
+        }
+    }
+
     fn _get_text_variants(
         text: &str,
         text_processors: &[TextProcessorWithId],
         text_replacements: FindTermsTextReplacements,
         text_cache: &mut TextCache,
+        opts: &FindTermsOptions,
     ) -> VariantAndTextProcessorRuleChainCandidatesMap {
         let mut variants_map: VariantAndTextProcessorRuleChainCandidatesMap = IndexMap::new();
         variants_map.insert(text.to_string(), vec![vec![]]);
@@ -3032,16 +3149,28 @@ impl Translator {
             let TextProcessor {
                 options, process, ..
             } = processor;
+            let processor_id = id.to_string();
+            let mut enabled_options: Vec<_> = options
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| {
+                    Translator::_is_text_processor_option_enabled(&processor_id, *i, opts)
+                })
+                .map(|(_, opt)| opt)
+                .collect();
+            if enabled_options.is_empty() {
+                enabled_options = options.iter().collect();
+            }
             let mut new_variants_map: VariantAndTextProcessorRuleChainCandidatesMap =
                 IndexMap::new();
             for variant in variants_map.iter() {
                 let (variant, current_preprocessor_rule_chain_candidates) = variant;
-                for opt in options.iter() {
+                for opt in enabled_options.iter() {
                     let processed = Translator::_get_processed_text(
                         text_cache,
                         variant.clone(),
-                        id.to_string(),
-                        opt.clone(),
+                        processor_id.clone(),
+                        (*opt).clone(),
                         *process,
                     );
                     let existing_candidates = new_variants_map.get(&processed);
@@ -3158,7 +3287,6 @@ impl Translator {
         enabled_dictionary_map: &FindTermDictionaryMap,
     ) {
         for entry in database_entries {
-            dbg!(&entry);
             let entry_dictionary = enabled_dictionary_map
                 .get(&entry.dictionary)
                 .unwrap_or_else(|| {
@@ -3177,12 +3305,11 @@ impl Translator {
                 // Iterate through each deinflection in the group and push the entry.
                 // This replicates the one-to-many mapping.
                 for deinflection in deinflection_group.iter_mut() {
-                    if !entry_dictionary.parts_of_speech_filter
-                        || LanguageTransformer::conditions_match(
-                            deinflection.conditions,
-                            definition_conditions,
-                        )
-                    {
+                    if Translator::_passes_parts_of_speech_filter(
+                        entry_dictionary,
+                        deinflection.conditions,
+                        definition_conditions,
+                    ) {
                         // `deinflection` is `&mut &mut DatabaseDeinflection`, so we dereference it
                         // to access the `database_entries` field and push the cloned entry.
                         deinflection.database_entries.push(entry.clone());
@@ -3192,6 +3319,17 @@ impl Translator {
         }
     }
 
+    /// Whether an entry with `definition_conditions` (from its parts of speech) may be
+    /// matched to a deinflection with `deinflection_conditions`.
+    pub(crate) fn _passes_parts_of_speech_filter(
+        entry_dictionary: &FindTermDictionary,
+        deinflection_conditions: usize,
+        definition_conditions: usize,
+    ) -> bool {
+        !entry_dictionary.parts_of_speech_filter
+            || LanguageTransformer::conditions_match(deinflection_conditions, definition_conditions)
+    }
+
     /// this might be incorrect based on the javascript function
     fn _group_deinflections_by_term(
         deinflections: &[DatabaseDeinflection],
@@ -3263,7 +3401,10 @@ impl Translator {
             processed_text_string
         }
     }
-    fn _get_next_substring(search_resolution: SearchResolution, current_str: &str) -> String {
+    pub(crate) fn _get_next_substring(
+        search_resolution: SearchResolution,
+        current_str: &str,
+    ) -> String {
         let end_byte_index: usize;
         if search_resolution == SearchResolution::Word {
             if let Some(mat) = GET_NEXT_SUBSTRING_REGEX.find(current_str).unwrap() {
@@ -3342,8 +3483,47 @@ struct TagExpansionTarget {
 #[derive(Clone, Debug, Default)]
 struct TranslatorTagAggregator {
     tag_expansion_target_map: IndexMap<Vec<DictionaryTag>, Vec<TagGroup>>,
+    /// The tags added by [Self::add_entry_tags], by database entry id.
+    entry_tags: IndexMap<String, Vec<(Vec<DictionaryTag>, TagGroup)>>,
 }
 impl TranslatorTagAggregator {
+    /// Same as [Self::add_tags], but remembers that the tags belong to the database entry `id`,
+    /// see [Self::for_entries].
+    pub fn add_entry_tags(
+        &mut self,
+        tags_key: &[DictionaryTag],
+        id: &str,
+        dictionary_name: &str,
+        tag_names_to_add: &[String],
+    ) {
+        if tag_names_to_add.is_empty() {
+            return;
+        }
+        self.add_tags(tags_key, dictionary_name, tag_names_to_add);
+        self.entry_tags.entry(id.to_string()).or_default().push((
+            tags_key.to_vec(),
+            TagGroup {
+                dictionary: dictionary_name.to_string(),
+                tag_names: tag_names_to_add.to_vec(),
+            },
+        ));
+    }
+
+    /// A new aggregator with only the tags of the database entries `ids`,
+    /// e.g. to expand the tags of the entries that are kept after pagination.
+    pub fn for_entries<'a>(&self, ids: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut aggregator = Self::default();
+        for id in ids {
+            let Some(tags) = self.entry_tags.get(id) else {
+                continue;
+            };
+            for (tags_key, group) in tags {
+                aggregator.add_entry_tags(tags_key, id, &group.dictionary, &group.tag_names);
+            }
+        }
+        aggregator
+    }
+
     /// Adds tags to a specific dictionary group associated with a primary list of tags.
     ///
     /// # Arguments
@@ -3474,12 +3654,28 @@ impl TranslatorTagAggregator {
 //     dictionary: String,
 // }
 //
+/// How the entries found by [Translator::find_terms] are grouped.
+///
+/// Used as the profile's `general.result_output_mode`.
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize, Default)]
 pub enum FindTermsMode {
+    /// Like `Split`, but without frequencies, pronunciations and tag expansion.
     #[default]
     Simple,
+    /// Group term-reading pairs.
+    ///
+    /// Definitions for the same term with the same reading will be grouped together.
     Group,
+    /// Group related terms.
+    ///
+    /// Entries of the main dictionary are grouped with every other entry sharing their sequence
+    /// number, and dictionaries with `allow_secondary_searches` add definitions for the
+    /// grouped terms. The main dictionary should contain related term information
+    /// (e.g. JMdict); _not all dictionaries are able to be selected as the main dictionary_.
     Merge,
+    /// No grouping.
+    ///
+    /// Every definition will be listed as a separate entry.
     Split,
 }
 
@@ -3640,6 +3836,9 @@ pub struct InternalTermDictionaryEntryWithIndexes {
 pub struct FindTermsResult {
     pub dictionary_entries: Vec<TermDictionaryEntry>,
     pub original_text_length: i128,
+    /// The number of entries found before `FindTermsOptions::offset` and
+    /// `FindTermsOptions::max_results` were applied.
+    pub total_results: usize,
 }
 
 pub type TextProcessorMap = IndexMap<&'static str, PreAndPostProcessorsWithId>;
@@ -3648,6 +3847,8 @@ pub type ReadingNormalizerMap = IndexMap<&'static str, ReadingNormalizer>;
 pub enum TranslatorError {
     #[error("Unsupported Language: {0}")]
     UnsupportedLanguage(String),
+    #[error("database error: {0}")]
+    Database(#[from] Box<DictionaryDatabaseError>),
 }
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 struct TermMetaHeadword {
@@ -3659,3 +3860,197 @@ struct TermMetaHeadword {
 
 // never used
 //type TermMetaHeadwordMap = IndexMap<String, IndexMap<String, Vec<TermMetaHeadword>>>;
+
+#[cfg(test)]
+mod tag_aggregator_tests {
+    use super::*;
+
+    #[test]
+    fn for_entries_keeps_only_their_tags() {
+        let mut aggregator = TranslatorTagAggregator::default();
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        aggregator.add_entry_tags(&[], "1", "JMdict", &names(&["n"]));
+        aggregator.add_entry_tags(&[], "2", "JMdict", &names(&["v5", "vi"]));
+        aggregator.add_entry_tags(&[], "3", "Other", &names(&["adj"]));
+
+        let targets = aggregator
+            .for_entries(["1", "3"])
+            .get_tag_expansion_targets();
+        assert_eq!(targets.len(), 1);
+        let groups: Vec<(&str, &[String])> = targets[0]
+            .tag_groups
+            .iter()
+            .map(|group| (group.dictionary.as_str(), group.tag_names.as_slice()))
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                ("JMdict", &names(&["n"])[..]),
+                ("Other", &names(&["adj"])[..])
+            ]
+        );
+    }
+}
+
+#[cfg(test)]
+mod alphabetic_to_hiragana_tests {
+    use super::*;
+
+    #[test]
+    fn explicit_option_wins_over_wanakana() {
+        let convert = Translator::_get_alphabetic_to_hiragana;
+        assert_eq!(convert(None, false), TranslationConvertType::False);
+        assert_eq!(convert(None, true), TranslationConvertType::True);
+        assert_eq!(
+            convert(Some(TranslationConvertType::False), true),
+            TranslationConvertType::False
+        );
+        assert_eq!(
+            convert(Some(TranslationConvertType::Variant), false),
+            TranslationConvertType::Variant
+        );
+    }
+}
+
+#[cfg(test)]
+mod text_processor_option_tests {
+    use super::*;
+    use crate::utils::test_utils::TestYomichan;
+
+    /// カタカナ, 123 and １２３, すごい and すっごい.
+    const CONVERSION: &str = "fixture-ja-conversion";
+
+    /// The sorted terms found for `text` with `translation` applied to the profile.
+    fn terms(
+        ycd: &TestYomichan,
+        text: &str,
+        translation: impl FnOnce(&mut TranslationOptions),
+    ) -> Vec<String> {
+        ycd.with_profile_mut(|profile| translation(&mut profile.options_mut().translation))
+            .unwrap();
+        let mut terms: Vec<String> = ycd
+            .search_page(text, 0, None)
+            .unwrap()
+            .dictionary_entries
+            .iter()
+            .map(|entry| entry.headwords[0].term.clone())
+            .collect();
+        terms.sort();
+        terms
+    }
+
+    #[test]
+    fn convert_half_width_characters() {
+        use TranslationConvertType::*;
+        let ycd = TestYomichan::new("convert_half_width", &[CONVERSION]);
+        for (convert, expected) in [
+            (False, vec![]),
+            (True, vec!["カタカナ"]),
+            (Variant, vec!["カタカナ"]),
+        ] {
+            let found = terms(&ycd, "ｶﾀｶﾅ", |t| {
+                t.convert_half_width_characters = convert
+            });
+            assert_eq!(found, expected, "{convert:?}");
+        }
+    }
+
+    #[test]
+    fn convert_numeric_characters() {
+        use TranslationConvertType::*;
+        let ycd = TestYomichan::new("convert_numeric", &[CONVERSION]);
+        for (convert, expected) in [
+            (False, vec!["１２３"]),
+            (True, vec!["123"]),
+            (Variant, vec!["123", "１２３"]),
+        ] {
+            let found = terms(&ycd, "１２３", |t| {
+                t.convert_numeric_characters = convert
+            });
+            assert_eq!(found, expected, "{convert:?}");
+        }
+    }
+
+    #[test]
+    fn collapse_emphatic_sequences() {
+        use TranslationCollapseEmphaticSequences::*;
+        let ycd = TestYomichan::new("collapse_emphatic", &[CONVERSION]);
+        for (collapse, expected) in [
+            (False, vec![]),
+            (True, vec!["すっごい"]),
+            (Full, vec!["すごい", "すっごい"]),
+        ] {
+            let found = terms(&ycd, "すっっごい", |t| {
+                t.collapse_emphatic_sequences = collapse
+            });
+            assert_eq!(found, expected, "{collapse:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod pagination_tests {
+    use crate::utils::test_utils::TestYomichan;
+
+    /// 紙, 神, 髪 and 加味, all read かみ and ranked in that order.
+    const HOMOPHONES: &str = "fixture-ja-homophones";
+
+    fn homophones(name: &str) -> TestYomichan {
+        let ycd = TestYomichan::new(name, &[HOMOPHONES]);
+        ycd.with_profile_mut(|profile| {
+            profile.options_mut().general.sort_frequency_dictionary = Some(HOMOPHONES.to_string());
+        })
+        .unwrap();
+        ycd
+    }
+
+    fn terms(entries: &[crate::TermDictionaryEntry]) -> Vec<&str> {
+        entries
+            .iter()
+            .map(|entry| entry.headwords[0].term.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn pages_split_the_sorted_results() {
+        let ycd = homophones("pagination");
+        let all = ycd.search_page("かみ", 0, None).unwrap();
+        assert_eq!(all.total_results, 4);
+        assert_eq!(terms(&all.dictionary_entries), ["紙", "神", "髪", "加味"]);
+
+        let page_0 = ycd.search_page("かみ", 0, Some(2)).unwrap();
+        let page_1 = ycd.search_page("かみ", 2, Some(2)).unwrap();
+        let past_end = ycd.search_page("かみ", 4, Some(2)).unwrap();
+        assert_eq!(terms(&page_0.dictionary_entries), ["紙", "神"]);
+        assert_eq!(terms(&page_1.dictionary_entries), ["髪", "加味"]);
+        assert!(past_end.dictionary_entries.is_empty());
+        for page in [&page_0, &page_1, &past_end] {
+            assert_eq!(page.total_results, 4);
+        }
+
+        // the meta and tags of a page are the same as without pagination
+        assert_eq!(page_0.dictionary_entries, all.dictionary_entries[..2]);
+        assert_eq!(page_1.dictionary_entries, all.dictionary_entries[2..]);
+        for entry in &page_1.dictionary_entries {
+            assert_eq!(entry.frequencies.len(), 1);
+            let tags = &entry.definitions[0].tags;
+            assert!(tags
+                .iter()
+                .any(|tag| tag.name == "n" && tag.category == "partOfSpeech"));
+        }
+    }
+
+    #[test]
+    fn max_results_cuts_off_after_sorting() {
+        let ycd = homophones("pagination_max_results");
+        ycd.with_profile_mut(|profile| profile.options_mut().general.max_results = 3)
+            .unwrap();
+
+        let result = ycd.search_page("かみ", 0, None).unwrap();
+        assert_eq!(result.total_results, 4);
+        assert_eq!(terms(&result.dictionary_entries), ["紙", "神", "髪"]);
+        // an explicit limit overrides max_results
+        let result = ycd.search_page("かみ", 1, Some(10)).unwrap();
+        assert_eq!(terms(&result.dictionary_entries), ["神", "髪", "加味"]);
+    }
+}
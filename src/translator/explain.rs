@@ -0,0 +1,419 @@
+//! A structured trace of a term lookup, for finding out why an entry was (or wasn't) found.
+
+use deinflector::descriptors::PreAndPostProcessorsWithId;
+use indexmap::{IndexMap, IndexSet};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend::FindTermsDetails,
+    models::dictionary::{DictionaryInflectionRuleChainCandidate, TermDictionaryEntry},
+    scanner::core::find_terms_mode,
+    settings::core::{ProfileError, RankingStrategyKind},
+    translator::{
+        core::{FindTermsMode, Translator, TranslatorError},
+        internal_types::{DatabaseDeinflection, TextProcessorRuleChainCandidate},
+        ranking::is_name_dictionary,
+        types::FindTermsOptions,
+    },
+    Yomichan,
+};
+
+/// Everything the translator did to look up a piece of text.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LookupTrace {
+    /// The text that was passed in.
+    pub text: String,
+    /// The text after non-Japanese characters were removed (if enabled).
+    pub search_text: String,
+    pub language: String,
+    /// Every text processor variant of every substring that was searched.
+    pub text_variants: Vec<TextVariantTrace>,
+    /// Every deinflection that was tried.
+    pub deinflections: Vec<DeinflectionTrace>,
+    /// The unique terms sent to the database.
+    pub query_terms: Vec<String>,
+    /// Database entries that were found but rejected for a deinflection.
+    pub filtered_candidates: Vec<FilteredCandidate>,
+    /// The strategy the final entries were sorted with.
+    pub ranking_strategy: RankingStrategyKind,
+    /// The final entries, in sorted order, with the values they were sorted by.
+    pub entries: Vec<EntryScoreTrace>,
+}
+
+/// A variant of a substring of the search text produced by the preprocessors.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextVariantTrace {
+    /// The substring of the search text.
+    pub source: String,
+    pub variant: String,
+    /// The text processor chains that produce `variant` from `source`.
+    pub rule_chains: Vec<TextProcessorRuleChainCandidate>,
+}
+
+/// A deinflection of a text variant.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeinflectionTrace {
+    pub original_text: String,
+    pub transformed_text: String,
+    /// The term that is looked up.
+    pub deinflected_text: String,
+    /// The condition flags the dictionary entry's parts of speech must match.
+    pub conditions: usize,
+    pub text_processor_rule_chains: Vec<TextProcessorRuleChainCandidate>,
+    pub inflection_rule_chains: Vec<DictionaryInflectionRuleChainCandidate>,
+}
+
+/// A database entry that was not matched to a deinflection.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FilteredCandidate {
+    pub deinflected_text: String,
+    pub term: String,
+    pub reading: String,
+    pub dictionary: String,
+    pub reason: FilterReason,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilterReason {
+    /// The dictionary has `parts_of_speech_filter` enabled and the entry's parts of speech
+    /// don't match the conditions of the deinflection.
+    PartsOfSpeechMismatch {
+        deinflection_conditions: usize,
+        entry_conditions: usize,
+        entry_rules: Vec<String>,
+    },
+}
+
+/// The sort keys the built-in ranking strategies use for a final entry.
+///
+/// Which keys decided the order, and in which order they were compared, depends on
+/// [LookupTrace::ranking_strategy]; the fields are listed in the order
+/// [YomitanRanking](crate::translator::ranking::YomitanRanking) compares them.
+/// A custom strategy may sort by values that are not recorded here.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EntryScoreTrace {
+    pub term: String,
+    pub reading: String,
+    pub dictionary: String,
+    pub match_primary_reading: bool,
+    pub max_original_text_length: usize,
+    pub shortest_text_processor_chain: usize,
+    pub shortest_inflection_chain: usize,
+    pub source_term_exact_match_count: usize,
+    pub frequency_order: i128,
+    pub aggregate_frequency: Option<i128>,
+    pub dictionary_index: usize,
+    pub score: i128,
+    /// Whether all definitions are from name dictionaries, which
+    /// [PenalizeNamesRanking](crate::translator::ranking::PenalizeNamesRanking) puts last.
+    pub name_entry: bool,
+}
+
+impl EntryScoreTrace {
+    fn from_entry(entry: &TermDictionaryEntry) -> Self {
+        let headword = entry.headwords.first();
+        let shortest_text_processor_chain = entry
+            .text_processor_rule_chain_candidates
+            .iter()
+            .map(Vec::len)
+            .min()
+            .unwrap_or(0);
+        let shortest_inflection_chain = entry
+            .inflection_rule_chain_candidates
+            .iter()
+            .map(|candidate| candidate.inflection_rules.len())
+            .min()
+            .unwrap_or(0);
+        Self {
+            term: headword.map(|h| h.term.clone()).unwrap_or_default(),
+            reading: headword.map(|h| h.reading.clone()).unwrap_or_default(),
+            dictionary: entry.dictionary_alias.clone(),
+            match_primary_reading: entry.match_primary_reading,
+            max_original_text_length: entry.max_original_text_length,
+            shortest_text_processor_chain,
+            shortest_inflection_chain,
+            source_term_exact_match_count: entry.source_term_exact_match_count,
+            frequency_order: entry.frequency_order,
+            aggregate_frequency: entry.aggregate_frequency,
+            dictionary_index: entry.dictionary_index,
+            score: entry.score,
+            name_entry: !entry.definitions.is_empty()
+                && entry
+                    .definitions
+                    .iter()
+                    .all(|definition| is_name_dictionary(&definition.dictionary)),
+        }
+    }
+}
+
+impl Translator {
+    /// Runs the lookup of `text` step by step and records what every step produced.
+    ///
+    /// Text variants are only traced with deinflection enabled,
+    /// since they are produced by the deinflection algorithm.
+    pub fn explain_terms(
+        &self,
+        mode: FindTermsMode,
+        text: &str,
+        opts: &FindTermsOptions,
+    ) -> Result<LookupTrace, TranslatorError> {
+        let mut search_text = text.to_string();
+        Translator::_prepare_search_text(&mut search_text, opts);
+
+        let mut text_variants = Vec::new();
+        if opts.deinflect && !search_text.is_empty() {
+            let Some(PreAndPostProcessorsWithId { pre, .. }) =
+                self.text_processors.get(opts.language.as_str())
+            else {
+                return Err(TranslatorError::UnsupportedLanguage(opts.language.clone()));
+            };
+            let mut source_cache = IndexMap::new();
+            let mut source = search_text.clone();
+            while !source.is_empty() {
+                let variants = Translator::_get_preprocessed_text_variants(
+                    &source,
+                    pre,
+                    opts,
+                    &mut source_cache,
+                );
+                text_variants.extend(variants.into_iter().map(|(variant, rule_chains)| {
+                    TextVariantTrace {
+                        source: source.clone(),
+                        variant,
+                        rule_chains,
+                    }
+                }));
+                source = Translator::_get_next_substring(opts.search_resolution, &source);
+            }
+        }
+
+        let candidates = if search_text.is_empty() {
+            vec![]
+        } else {
+            self._get_candidate_deinflections(&search_text, opts)?
+        };
+        let deinflections: Vec<DeinflectionTrace> = candidates
+            .iter()
+            .map(|deinflection| DeinflectionTrace {
+                original_text: deinflection.original_text.clone(),
+                transformed_text: deinflection.transformed_text.clone(),
+                deinflected_text: deinflection.deinflected_text.clone(),
+                conditions: deinflection.conditions,
+                text_processor_rule_chains: deinflection
+                    .text_processor_rule_chain_candidates
+                    .clone(),
+                inflection_rule_chains: deinflection
+                    .inflection_rule_chain_candidates
+                    .iter()
+                    .map(|candidate| DictionaryInflectionRuleChainCandidate {
+                        source: candidate.source.clone(),
+                        inflection_rules: candidate.inflection_rules.clone(),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let query_terms: Vec<String> = candidates
+            .iter()
+            .map(|deinflection| deinflection.deinflected_text.clone())
+            .filter(|term| !term.is_empty())
+            .collect::<IndexSet<_>>()
+            .into_iter()
+            .collect();
+        let filtered_candidates =
+            self.explain_filtered_candidates(&query_terms, &candidates, opts)?;
+
+        let entries = self
+            .find_terms(mode, text, opts)
+            .dictionary_entries
+            .iter()
+            .map(EntryScoreTrace::from_entry)
+            .collect();
+
+        Ok(LookupTrace {
+            text: text.to_string(),
+            search_text,
+            language: opts.language.clone(),
+            text_variants,
+            deinflections,
+            query_terms,
+            filtered_candidates,
+            ranking_strategy: opts.ranking_strategy.clone(),
+            entries,
+        })
+    }
+
+    /// Looks up `query_terms` and records the entries that the parts of speech filter
+    /// keeps away from the deinflections they were found for.
+    fn explain_filtered_candidates(
+        &self,
+        query_terms: &[String],
+        candidates: &[DatabaseDeinflection],
+        opts: &FindTermsOptions,
+    ) -> Result<Vec<FilteredCandidate>, TranslatorError> {
+        if query_terms.is_empty() {
+            return Ok(vec![]);
+        }
+        let database_entries =
+            self.db
+                .find_terms_bulk(query_terms, &opts.enabled_dictionary_map, opts.match_type)?;
+        let mut filtered = Vec::new();
+        for entry in database_entries {
+            let Some(entry_dictionary) = opts.enabled_dictionary_map.get(&entry.dictionary) else {
+                continue;
+            };
+            let Some(query_term) = query_terms.get(entry.index) else {
+                continue;
+            };
+            let entry_conditions = self
+                .mlt
+                .get_condition_flags_from_parts_of_speech(&opts.language, &entry.rules);
+            for deinflection in candidates
+                .iter()
+                .filter(|deinflection| &deinflection.deinflected_text == query_term)
+            {
+                if Translator::_passes_parts_of_speech_filter(
+                    entry_dictionary,
+                    deinflection.conditions,
+                    entry_conditions,
+                ) {
+                    continue;
+                }
+                filtered.push(FilteredCandidate {
+                    deinflected_text: query_term.clone(),
+                    term: entry.term.clone(),
+                    reading: entry.reading.clone(),
+                    dictionary: entry.dictionary.clone(),
+                    reason: FilterReason::PartsOfSpeechMismatch {
+                        deinflection_conditions: deinflection.conditions,
+                        entry_conditions,
+                        entry_rules: entry.rules.clone(),
+                    },
+                });
+            }
+        }
+        Ok(filtered)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ExplainError {
+    #[error("profile error: {0}")]
+    Profile(#[from] ProfileError),
+    #[error("translator error: {0}")]
+    Translator(#[from] TranslatorError),
+}
+
+impl Yomichan {
+    /// Traces the lookup of the terms at the start of `text` with the current profile's options
+    /// and output mode, listing the text variants, deinflections, database queries,
+    /// filtered candidates and the sort keys of the resulting entries.
+    ///
+    /// Fails if the current user profile cannot be accessed,
+    /// its language is not supported or the database cannot be read.
+    pub fn explain(&self, text: &str) -> Result<LookupTrace, ExplainError> {
+        let profile = self.backend.get_current_profile()?;
+        let profile = profile.read();
+        let mode = find_terms_mode(profile.options());
+        let opts = Translator::_get_translator_find_terms_options(
+            mode,
+            &FindTermsDetails::default(),
+            profile.options(),
+        );
+        Ok(self
+            .backend
+            .scanner
+            .translator()
+            .explain_terms(mode, text, &opts)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_utils::{TestYomichan, FIXTURE_DICTIONARY};
+
+    /// 鳴く tagged as an ichidan verb.
+    const MISTAGGED: &str = "fixture-ja-mistagged";
+
+    #[test]
+    fn explain_deinflected_lookup() {
+        let ycd = TestYomichan::new(
+            "explain_deinflected_lookup",
+            &[FIXTURE_DICTIONARY, MISTAGGED],
+        );
+        ycd.with_profile_mut(|profile| {
+            profile.dictionaries_mut()[MISTAGGED].parts_of_speech_filter = true;
+        })
+        .unwrap();
+        let trace = ycd.explain("鳴いた").unwrap();
+
+        assert_eq!(trace.search_text, "鳴いた");
+        let sources: IndexSet<&str> = trace
+            .text_variants
+            .iter()
+            .map(|variant| variant.source.as_str())
+            .collect();
+        assert_eq!(
+            sources.into_iter().collect::<Vec<_>>(),
+            ["鳴いた", "鳴い", "鳴"]
+        );
+        assert!(trace.text_variants.contains(&TextVariantTrace {
+            source: "鳴いた".to_string(),
+            variant: "鳴いた".to_string(),
+            rule_chains: vec![vec![]],
+        }));
+
+        let deinflection = trace
+            .deinflections
+            .iter()
+            .find(|deinflection| deinflection.deinflected_text == "鳴く")
+            .expect("鳴いた deinflects to 鳴く");
+        assert_eq!(deinflection.original_text, "鳴いた");
+        assert_eq!(deinflection.transformed_text, "鳴いた");
+        assert!(deinflection
+            .inflection_rule_chains
+            .iter()
+            .any(|chain| !chain.inflection_rules.is_empty()));
+        assert!(trace.query_terms.iter().any(|term| term == "鳴く"));
+        assert!(trace.query_terms.iter().any(|term| term == "鳴いた"));
+
+        // only the mistagged dictionary filters by part of speech
+        assert!(!trace.filtered_candidates.is_empty());
+        for candidate in &trace.filtered_candidates {
+            assert_eq!(candidate.dictionary, MISTAGGED);
+            assert_eq!(candidate.deinflected_text, "鳴く");
+            assert_eq!(candidate.term, "鳴く");
+            let FilterReason::PartsOfSpeechMismatch {
+                deinflection_conditions,
+                entry_conditions,
+                entry_rules,
+            } = &candidate.reason;
+            assert_eq!(entry_rules, &["v1"]);
+            assert_ne!(*entry_conditions, 0);
+            assert_eq!(deinflection_conditions & entry_conditions, 0);
+        }
+
+        assert_eq!(trace.ranking_strategy, RankingStrategyKind::Yomitan);
+        assert_eq!(trace.entries.len(), 1);
+        let entry = &trace.entries[0];
+        assert_eq!(entry.term, "鳴く");
+        assert_eq!(entry.reading, "なく");
+        assert_eq!(entry.dictionary, FIXTURE_DICTIONARY);
+        assert_eq!(entry.max_original_text_length, 3);
+        assert_eq!(entry.shortest_text_processor_chain, 0);
+        assert_eq!(entry.shortest_inflection_chain, 1);
+        assert!(!entry.name_entry);
+    }
+
+    #[test]
+    fn explain_empty_text() {
+        let ycd = TestYomichan::new("explain_empty_text", &[FIXTURE_DICTIONARY]);
+        let trace = ycd.explain("").unwrap();
+        assert!(trace.text_variants.is_empty());
+        assert!(trace.deinflections.is_empty());
+        assert!(trace.query_terms.is_empty());
+        assert!(trace.filtered_candidates.is_empty());
+        assert!(trace.entries.is_empty());
+    }
+}
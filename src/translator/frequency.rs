@@ -0,0 +1,188 @@
+//! Aggregation of several frequency dictionaries into one rank.
+
+use crate::{
+    models::dictionary::TermFrequency,
+    settings::core::{
+        FrequencyAggregationMethod, FrequencyAggregationOptions, FrequencyAggregationSource,
+        GeneralOptions, SortFrequencyDictionaryOrder,
+    },
+    translator::types::FindTermsSortOrder,
+};
+
+/// Combines the ranks that `aggregation`'s sources give in `frequencies`.
+///
+/// Only frequencies of `headword_index` are used, or of every headword if `None`.
+/// Occurrence counts are converted to ranks first, so the result is always rank-based
+/// (lower is more common). `None` if no source has a usable frequency.
+pub fn aggregate_frequency(
+    aggregation: &FrequencyAggregationOptions,
+    frequencies: &[TermFrequency],
+    headword_index: Option<usize>,
+) -> Option<i128> {
+    let ranks: Vec<(f64, f64)> = aggregation
+        .sources
+        .iter()
+        .filter(|source| source.weight > 0.0)
+        .filter_map(|source| {
+            let values = frequencies
+                .iter()
+                .filter(|f| f.dictionary == source.dictionary)
+                .filter(|f| headword_index.is_none_or(|i| f.headword_index == i))
+                .map(|f| f.frequency)
+                .filter(|frequency| *frequency > 0);
+            let best = match source.mode {
+                SortFrequencyDictionaryOrder::Rank => values.min(),
+                SortFrequencyDictionaryOrder::Occurance => values.max(),
+            }?;
+            Some((source_rank(source, best), source.weight))
+        })
+        .collect();
+    if ranks.is_empty() {
+        return None;
+    }
+
+    let total_weight: f64 = ranks.iter().map(|(_, weight)| weight).sum();
+    let rank = match aggregation.method {
+        FrequencyAggregationMethod::MinimumRank => ranks
+            .iter()
+            .map(|(rank, _)| *rank)
+            .fold(f64::INFINITY, f64::min),
+        FrequencyAggregationMethod::HarmonicMean => {
+            total_weight
+                / ranks
+                    .iter()
+                    .map(|(rank, weight)| weight / rank)
+                    .sum::<f64>()
+        }
+        FrequencyAggregationMethod::WeightedAverage => {
+            ranks
+                .iter()
+                .map(|(rank, weight)| rank * weight)
+                .sum::<f64>()
+                / total_weight
+        }
+    };
+    Some(rank.round() as i128)
+}
+
+/// The profile's frequency settings as one aggregation: its `frequency_aggregation`
+/// if it has sources, or else its `sort_frequency_dictionary` on its own.
+///
+/// Has no sources, so every [aggregate_frequency] is `None`, if neither is set.
+pub fn profile_frequency_aggregation(general: &GeneralOptions) -> FrequencyAggregationOptions {
+    if general.frequency_aggregation.is_enabled() {
+        return general.frequency_aggregation.clone();
+    }
+    let mode = match general.sort_frequency_dictionary_order {
+        FindTermsSortOrder::Ascending => SortFrequencyDictionaryOrder::Rank,
+        FindTermsSortOrder::Descending => SortFrequencyDictionaryOrder::Occurance,
+    };
+    FrequencyAggregationOptions {
+        method: FrequencyAggregationMethod::MinimumRank,
+        sources: general
+            .sort_frequency_dictionary
+            .iter()
+            .map(|dictionary| FrequencyAggregationSource::new(dictionary.clone(), mode))
+            .collect(),
+    }
+}
+
+/// The rank of a (positive) frequency value of `source`.
+fn source_rank(source: &FrequencyAggregationSource, frequency: i128) -> f64 {
+    match source.mode {
+        SortFrequencyDictionaryOrder::Rank => frequency as f64,
+        SortFrequencyDictionaryOrder::Occurance => {
+            (source.occurrence_scale / frequency as f64).max(1.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod frequency_tests {
+    use super::*;
+
+    fn frequency(dictionary: &str, headword_index: usize, frequency: i128) -> TermFrequency {
+        TermFrequency {
+            index: 0,
+            headword_index,
+            dictionary: dictionary.to_string(),
+            dictionary_index: 0,
+            dictionary_alias: dictionary.to_string(),
+            has_reading: false,
+            frequency,
+            display_value: None,
+            display_value_parsed: false,
+        }
+    }
+
+    fn options(method: FrequencyAggregationMethod) -> FrequencyAggregationOptions {
+        FrequencyAggregationOptions {
+            method,
+            sources: vec![
+                FrequencyAggregationSource::new("Novels", SortFrequencyDictionaryOrder::Rank),
+                FrequencyAggregationSource::new("Netflix", SortFrequencyDictionaryOrder::Rank),
+                FrequencyAggregationSource::new(
+                    "Wikipedia",
+                    SortFrequencyDictionaryOrder::Occurance,
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn methods() {
+        let frequencies = [
+            frequency("Novels", 0, 100),
+            frequency("Netflix", 0, 400),
+            // 1_000_000 / 5_000 = rank 200
+            frequency("Wikipedia", 0, 5_000),
+        ];
+        let aggregate = |method| aggregate_frequency(&options(method), &frequencies, Some(0));
+        assert_eq!(
+            aggregate(FrequencyAggregationMethod::MinimumRank),
+            Some(100)
+        );
+        assert_eq!(
+            aggregate(FrequencyAggregationMethod::WeightedAverage),
+            Some(233)
+        );
+        // 3 / (1/100 + 1/400 + 1/200)
+        assert_eq!(
+            aggregate(FrequencyAggregationMethod::HarmonicMean),
+            Some(171)
+        );
+    }
+
+    #[test]
+    fn missing_sources_and_headwords() {
+        let frequencies = [frequency("Novels", 1, 50), frequency("Other", 0, 1)];
+        let options = options(FrequencyAggregationMethod::HarmonicMean);
+        assert_eq!(aggregate_frequency(&options, &frequencies, Some(0)), None);
+        assert_eq!(
+            aggregate_frequency(&options, &frequencies, Some(1)),
+            Some(50)
+        );
+        assert_eq!(aggregate_frequency(&options, &frequencies, None), Some(50));
+    }
+
+    #[test]
+    fn profile_settings() {
+        let mut general = GeneralOptions::default();
+        assert!(!profile_frequency_aggregation(&general).is_enabled());
+
+        general.sort_frequency_dictionary = Some("Wikipedia".to_string());
+        general.sort_frequency_dictionary_order = FindTermsSortOrder::Descending;
+        let frequencies = [frequency("Wikipedia", 0, 5_000)];
+        let aggregation = profile_frequency_aggregation(&general);
+        assert_eq!(
+            aggregate_frequency(&aggregation, &frequencies, Some(0)),
+            Some(200)
+        );
+
+        general.frequency_aggregation = options(FrequencyAggregationMethod::MinimumRank);
+        assert_eq!(
+            profile_frequency_aggregation(&general),
+            general.frequency_aggregation
+        );
+    }
+}
@@ -28,6 +28,8 @@ pub struct InternalTermDictionaryEntry {
     pub score: i128,
     /// The sorting value based on the determined term frequency.
     pub frequency_order: i128,
+    /// The combined rank of the term across the aggregated frequency dictionaries.
+    pub aggregate_frequency: Option<i128>,
     /// The alias of the dictionary.
     pub dictionary_alias: String,
     /// The index of the dictionary in the original list of dictionaries used for the lookup.
@@ -6,7 +6,10 @@ pub use core::TextProcessorMap;
 pub use core::Translator;
 
 pub mod core;
+pub mod explain;
+pub mod frequency;
 pub mod internal_types;
+pub mod ranking;
 pub mod regex_util;
 pub mod top;
 pub mod types;
@@ -0,0 +1,339 @@
+//! Orderings of term search results.
+//!
+//! [YomitanRanking] reproduces Yomitan's comparator and is the default.
+//! Profiles pick a strategy with [RankingStrategyKind] (`translation.ranking_strategy`);
+//! apps can add their own with [Yomichan::register_ranking_strategy].
+
+use std::{cmp::Ordering, sync::Arc};
+
+use crate::{
+    settings::core::RankingStrategyKind,
+    translator::{core::Translator, internal_types::InternalTermDictionaryEntry},
+    Yomichan,
+};
+
+/// Decides the order of the term entries found for a lookup.
+///
+/// Entries that compare as [Ordering::Less] are shown first.
+pub trait RankingStrategy: Send + Sync {
+    fn compare(&self, a: &InternalTermDictionaryEntry, b: &InternalTermDictionaryEntry)
+        -> Ordering;
+}
+
+/// Yomitan's order: primary reading, source length, text processing chain, inflection chain,
+/// exact matches, frequency, dictionary order, score, and finally the headwords.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct YomitanRanking;
+
+impl RankingStrategy for YomitanRanking {
+    fn compare(
+        &self,
+        a: &InternalTermDictionaryEntry,
+        b: &InternalTermDictionaryEntry,
+    ) -> Ordering {
+        cmp_primary_reading(a, b)
+            .then_with(|| cmp_source_length(a, b))
+            .then_with(|| cmp_text_processing_chain(a, b))
+            .then_with(|| cmp_inflection_chain(a, b))
+            .then_with(|| cmp_exact_matches(a, b))
+            .then_with(|| cmp_frequency(a, b))
+            .then_with(|| cmp_dictionary_order(a, b))
+            .then_with(|| cmp_score(a, b))
+            .then_with(|| cmp_headwords(a, b))
+    }
+}
+
+/// Like [YomitanRanking], but among entries for the same source text the more frequent term
+/// comes first, regardless of how it was transformed or which dictionary it is from.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrequencyFirstRanking;
+
+impl RankingStrategy for FrequencyFirstRanking {
+    fn compare(
+        &self,
+        a: &InternalTermDictionaryEntry,
+        b: &InternalTermDictionaryEntry,
+    ) -> Ordering {
+        cmp_primary_reading(a, b)
+            .then_with(|| cmp_source_length(a, b))
+            .then_with(|| cmp_frequency(a, b))
+            .then_with(|| cmp_text_processing_chain(a, b))
+            .then_with(|| cmp_inflection_chain(a, b))
+            .then_with(|| cmp_exact_matches(a, b))
+            .then_with(|| cmp_dictionary_order(a, b))
+            .then_with(|| cmp_score(a, b))
+            .then_with(|| cmp_headwords(a, b))
+    }
+}
+
+/// Like [YomitanRanking], but entries only found in name dictionaries (e.g. JMnedict)
+/// come after the other entries for the same source text.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PenalizeNamesRanking;
+
+impl RankingStrategy for PenalizeNamesRanking {
+    fn compare(
+        &self,
+        a: &InternalTermDictionaryEntry,
+        b: &InternalTermDictionaryEntry,
+    ) -> Ordering {
+        cmp_primary_reading(a, b)
+            .then_with(|| cmp_source_length(a, b))
+            .then_with(|| is_name_entry(a).cmp(&is_name_entry(b)))
+            .then_with(|| YomitanRanking.compare(a, b))
+    }
+}
+
+/// Whether `dictionary` is a dictionary of proper names, judged by its title.
+pub fn is_name_dictionary(dictionary: &str) -> bool {
+    let dictionary = dictionary.to_lowercase();
+    dictionary.contains("jmnedict")
+        || dictionary.contains("人名")
+        || dictionary
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|word| word == "name" || word == "names")
+}
+
+fn is_name_entry(entry: &InternalTermDictionaryEntry) -> bool {
+    !entry.definitions.is_empty()
+        && entry
+            .definitions
+            .iter()
+            .all(|definition| is_name_dictionary(&definition.dictionary))
+}
+
+impl Translator {
+    /// Makes `strategy` selectable with [RankingStrategyKind::Custom]`(name)`,
+    /// replacing any strategy registered under the same name.
+    pub fn register_ranking_strategy(
+        &self,
+        name: impl Into<String>,
+        strategy: Arc<dyn RankingStrategy>,
+    ) {
+        self.ranking_strategies
+            .write()
+            .insert(name.into(), strategy);
+    }
+
+    /// The strategy for `kind`.
+    ///
+    /// Unregistered custom strategies fall back to [YomitanRanking].
+    pub fn ranking_strategy(&self, kind: &RankingStrategyKind) -> Arc<dyn RankingStrategy> {
+        match kind {
+            RankingStrategyKind::Yomitan => Arc::new(YomitanRanking),
+            RankingStrategyKind::FrequencyFirst => Arc::new(FrequencyFirstRanking),
+            RankingStrategyKind::PenalizeNames => Arc::new(PenalizeNamesRanking),
+            RankingStrategyKind::Custom(name) => self
+                .ranking_strategies
+                .read()
+                .get(name)
+                .cloned()
+                .unwrap_or_else(|| Arc::new(YomitanRanking)),
+        }
+    }
+}
+
+impl Yomichan {
+    /// Makes `strategy` selectable with [RankingStrategyKind::Custom]`(name)`
+    /// in the profile's `translation.ranking_strategy`,
+    /// replacing any strategy registered under the same name.
+    pub fn register_ranking_strategy(
+        &self,
+        name: impl Into<String>,
+        strategy: Arc<dyn RankingStrategy>,
+    ) {
+        self.backend
+            .scanner
+            .translator()
+            .register_ranking_strategy(name, strategy);
+    }
+}
+
+/// Entries matching the primary reading first.
+pub fn cmp_primary_reading(
+    a: &InternalTermDictionaryEntry,
+    b: &InternalTermDictionaryEntry,
+) -> Ordering {
+    b.match_primary_reading.cmp(&a.match_primary_reading)
+}
+
+/// Entries for longer source text first.
+pub fn cmp_source_length(
+    a: &InternalTermDictionaryEntry,
+    b: &InternalTermDictionaryEntry,
+) -> Ordering {
+    b.max_original_text_length.cmp(&a.max_original_text_length)
+}
+
+/// Entries with a shorter text processing chain first.
+pub fn cmp_text_processing_chain(
+    a: &InternalTermDictionaryEntry,
+    b: &InternalTermDictionaryEntry,
+) -> Ordering {
+    let shortest = |entry: &InternalTermDictionaryEntry| {
+        entry
+            .text_processor_rule_chain_candidates
+            .iter()
+            .map(Vec::len)
+            .min()
+            .unwrap_or(0)
+    };
+    shortest(a).cmp(&shortest(b))
+}
+
+/// Entries with a shorter inflection chain first.
+pub fn cmp_inflection_chain(
+    a: &InternalTermDictionaryEntry,
+    b: &InternalTermDictionaryEntry,
+) -> Ordering {
+    let shortest = |entry: &InternalTermDictionaryEntry| {
+        entry
+            .inflection_rule_chain_candidates
+            .iter()
+            .map(|candidate| candidate.inflection_rules.len())
+            .min()
+            .unwrap_or(0)
+    };
+    shortest(a).cmp(&shortest(b))
+}
+
+/// Entries whose terms exactly match the source more often first.
+pub fn cmp_exact_matches(
+    a: &InternalTermDictionaryEntry,
+    b: &InternalTermDictionaryEntry,
+) -> Ordering {
+    b.source_term_exact_match_count
+        .cmp(&a.source_term_exact_match_count)
+}
+
+/// Entries with a lower frequency order first.
+pub fn cmp_frequency(a: &InternalTermDictionaryEntry, b: &InternalTermDictionaryEntry) -> Ordering {
+    a.frequency_order.cmp(&b.frequency_order)
+}
+
+/// Entries from dictionaries higher up in the profile first.
+pub fn cmp_dictionary_order(
+    a: &InternalTermDictionaryEntry,
+    b: &InternalTermDictionaryEntry,
+) -> Ordering {
+    a.dictionary_index.cmp(&b.dictionary_index)
+}
+
+/// Entries with a higher score first.
+pub fn cmp_score(a: &InternalTermDictionaryEntry, b: &InternalTermDictionaryEntry) -> Ordering {
+    b.score.cmp(&a.score)
+}
+
+/// Longer headword terms first, then by term text, then entries with more definitions first.
+pub fn cmp_headwords(a: &InternalTermDictionaryEntry, b: &InternalTermDictionaryEntry) -> Ordering {
+    for (headword1, headword2) in a.headwords.iter().zip(&b.headwords) {
+        let (term1, term2) = (&headword1.term, &headword2.term);
+        let cmp = term2.len().cmp(&term1.len()).then_with(|| term1.cmp(term2));
+        if cmp != Ordering::Equal {
+            return cmp;
+        }
+    }
+    b.definitions.len().cmp(&a.definitions.len())
+}
+
+#[cfg(test)]
+mod ranking_tests {
+    use super::*;
+    use crate::utils::test_utils::{TestYomichan, FIXTURE_DICTIONARY};
+
+    /// 猫 read みょう, as a surname.
+    const NAMES: &str = "fixture-ja-names";
+    /// ない, with frequencies for ない and 鳴く.
+    const NAI: &str = "fixture-ja-nai";
+    /// 紙, 神, 髪 and 加味, all read かみ and ranked in that order.
+    const HOMOPHONES: &str = "fixture-ja-homophones";
+
+    #[test]
+    fn name_dictionaries() {
+        assert!(is_name_dictionary("JMnedict [2024-06-01]"));
+        assert!(is_name_dictionary("人名辞典"));
+        assert!(is_name_dictionary("Japanese Names"));
+        assert!(!is_name_dictionary("JMdict (English)"));
+        assert!(!is_name_dictionary("Vietnamese"));
+    }
+
+    /// The first headword term and reading of every entry found for `text`
+    /// with `strategy` selected in the profile.
+    fn ranked(ycd: &TestYomichan, text: &str, strategy: RankingStrategyKind) -> Vec<String> {
+        ycd.with_profile_mut(|profile| {
+            profile.options_mut().translation.ranking_strategy = strategy;
+        })
+        .unwrap();
+        ycd.search_page(text, 0, None)
+            .unwrap()
+            .dictionary_entries
+            .iter()
+            .map(|entry| format!("{}/{}", entry.headwords[0].term, entry.headwords[0].reading))
+            .collect()
+    }
+
+    #[test]
+    fn frequency_first_ranks_frequency_above_inflection() {
+        // ない (adverbial なく) is ranked far above 鳴く
+        let ycd = TestYomichan::new("ranking_frequency_first", &[FIXTURE_DICTIONARY, NAI]);
+        ycd.with_profile_mut(|profile| {
+            profile.options_mut().general.sort_frequency_dictionary = Some(NAI.to_string());
+        })
+        .unwrap();
+        assert_eq!(
+            ranked(&ycd, "なく", RankingStrategyKind::Yomitan),
+            ["鳴く/なく", "ない/ない"]
+        );
+        assert_eq!(
+            ranked(&ycd, "なく", RankingStrategyKind::FrequencyFirst),
+            ["ない/ない", "鳴く/なく"]
+        );
+    }
+
+    #[test]
+    fn penalize_names_ranks_names_last() {
+        // the name dictionary comes first in the dictionary order
+        let ycd = TestYomichan::new("ranking_penalize_names", &[NAMES, FIXTURE_DICTIONARY]);
+        assert_eq!(
+            ranked(&ycd, "猫", RankingStrategyKind::Yomitan),
+            ["猫/みょう", "猫/ねこ"]
+        );
+        assert_eq!(
+            ranked(&ycd, "猫", RankingStrategyKind::PenalizeNames),
+            ["猫/ねこ", "猫/みょう"]
+        );
+    }
+
+    /// [YomitanRanking] reversed.
+    struct ReversedRanking;
+
+    impl RankingStrategy for ReversedRanking {
+        fn compare(
+            &self,
+            a: &InternalTermDictionaryEntry,
+            b: &InternalTermDictionaryEntry,
+        ) -> Ordering {
+            YomitanRanking.compare(b, a)
+        }
+    }
+
+    #[test]
+    fn custom_strategy() {
+        let ycd = TestYomichan::new("ranking_custom", &[HOMOPHONES]);
+        ycd.with_profile_mut(|profile| {
+            profile.options_mut().general.sort_frequency_dictionary = Some(HOMOPHONES.to_string());
+        })
+        .unwrap();
+        let reversed = RankingStrategyKind::Custom("reversed".to_string());
+        // unregistered strategies fall back to Yomitan's order
+        assert_eq!(
+            ranked(&ycd, "かみ", reversed.clone()),
+            ["紙/かみ", "神/かみ", "髪/かみ", "加味/かみ"]
+        );
+        ycd.register_ranking_strategy("reversed", Arc::new(ReversedRanking));
+        assert_eq!(
+            ranked(&ycd, "かみ", reversed),
+            ["加味/かみ", "髪/かみ", "神/かみ", "紙/かみ"]
+        );
+    }
+}
@@ -3,7 +3,10 @@ use yomichan_importer::dictionary_database::TermSourceMatchType;
 use indexmap::{IndexMap, IndexSet};
 use serde::{Deserialize, Serialize};
 
-use crate::settings::core::SearchResolution;
+use crate::settings::core::{
+    FrequencyAggregationOptions, RankingStrategyKind, SearchResolution,
+    TranslationCollapseEmphaticSequences, TranslationConvertType,
+};
 
 // Kanji
 
@@ -56,6 +59,28 @@ pub struct FindTermsOptions {
     pub search_resolution: SearchResolution,
     /// ISO-639 code of the language.
     pub language: String,
+    /// Whether romaji input (e.g. "taberu") is converted to hiragana before deinflection.
+    /// Only applies to Japanese.
+    pub alphabetic_to_hiragana: TranslationConvertType,
+    /// The number of sorted entries to skip, for paginating results.
+    pub offset: usize,
+    /// The maximum number of entries to return after `offset`; `None` returns every entry.
+    pub max_results: Option<usize>,
+    /// Which variants of the `convertHalfWidthCharacters` text processor are searched.
+    pub convert_half_width_characters: TranslationConvertType,
+    /// Which variants of the `alphanumericWidthVariants` text processor are searched.
+    pub convert_numeric_characters: TranslationConvertType,
+    /// Which `direct` variants of the `convertHiraganaToKatakana` text processor are searched.
+    pub convert_hiragana_to_katakana: TranslationConvertType,
+    /// Which `inverse` variants of the `convertHiraganaToKatakana` text processor are searched.
+    pub convert_katakana_to_hiragana: TranslationConvertType,
+    /// Which variants of the `collapseEmphaticSequences` text processor are searched.
+    pub collapse_emphatic_sequences: TranslationCollapseEmphaticSequences,
+    /// How the found entries are ordered.
+    pub ranking_strategy: RankingStrategyKind,
+    /// Frequency dictionaries combined into an aggregate frequency, which is used for sorting
+    /// instead of `sort_frequency_dictionary` when enabled.
+    pub frequency_aggregation: FrequencyAggregationOptions,
 }
 impl FindTermsOptions {
     pub fn default_for_language(language: &str) -> Self {
@@ -72,6 +97,16 @@ impl FindTermsOptions {
             exclude_dictionary_definitions: None,
             search_resolution: SearchResolution::Word,
             language: language.to_string(),
+            alphabetic_to_hiragana: TranslationConvertType::False,
+            offset: 0,
+            max_results: None,
+            convert_half_width_characters: TranslationConvertType::Variant,
+            convert_numeric_characters: TranslationConvertType::Variant,
+            convert_hiragana_to_katakana: TranslationConvertType::Variant,
+            convert_katakana_to_hiragana: TranslationConvertType::Variant,
+            collapse_emphatic_sequences: TranslationCollapseEmphaticSequences::Full,
+            ranking_strategy: RankingStrategyKind::Yomitan,
+            frequency_aggregation: FrequencyAggregationOptions::default(),
         }
     }
 }
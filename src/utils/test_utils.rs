@@ -1,4 +1,4 @@
-use std::{path::PathBuf, sync::LazyLock};
+use std::{ops::Deref, path::PathBuf, sync::LazyLock};
 
 use crate::{database::dictionary_database::DictionaryDatabase, Yomichan};
 
@@ -33,6 +33,52 @@ pub static SHARED_DB_INSTANCE: LazyLock<DictionaryDatabase> = LazyLock::new(|| {
     DictionaryDatabase::new(db_path)
 });
 
+/// A small sequenced Japanese dictionary in [TestPaths::test_dicts_dir],
+/// quick enough to import in every test that needs one.
+pub const FIXTURE_DICTIONARY: &str = "fixture-ja";
+
+/// A [Yomichan] with its own database in `tests/<name>_tmp`, removed on drop.
+///
+/// Unlike [YCD], tests can change its options without affecting each other.
+pub struct TestYomichan {
+    ycd: Yomichan,
+    dir: PathBuf,
+}
+
+impl TestYomichan {
+    /// Imports `dictionaries` from [TestPaths::test_dicts_dir]
+    /// and sets the language to Japanese.
+    pub fn new(name: &str, dictionaries: &[&str]) -> Self {
+        let dir = TEST_PATHS.tests_dir.join(format!("{name}_tmp"));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let ycd = Yomichan::new(&dir).unwrap();
+        let paths: Vec<PathBuf> = dictionaries
+            .iter()
+            .map(|dictionary| TEST_PATHS.test_dicts_dir.join(dictionary))
+            .collect();
+        if !paths.is_empty() {
+            ycd.import_dictionaries(&paths).unwrap();
+        }
+        ycd.set_language("ja").unwrap();
+        Self { ycd, dir }
+    }
+}
+
+impl Deref for TestYomichan {
+    type Target = Yomichan;
+
+    fn deref(&self) -> &Self::Target {
+        &self.ycd
+    }
+}
+
+impl Drop for TestYomichan {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
 impl std::fmt::Display for BacktraceKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let x = match self {